@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS webhook_jobs (
+  id bigserial PRIMARY KEY,
+  signature text UNIQUE NOT NULL,
+  status text NOT NULL DEFAULT 'queued',
+  attempts integer NOT NULL DEFAULT 0,
+  max_attempts integer NOT NULL,
+  run_at timestamptz NOT NULL DEFAULT now(),
+  locked_at timestamptz NULL,
+  last_error text NULL,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_jobs_runnable ON webhook_jobs(status, run_at);
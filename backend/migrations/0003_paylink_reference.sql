@@ -0,0 +1,3 @@
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS reference text NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_paylinks_reference ON paylinks(reference);
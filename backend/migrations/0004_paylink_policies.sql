@@ -0,0 +1,6 @@
+ALTER TABLE paylinks
+  ADD COLUMN IF NOT EXISTS memo_policy jsonb NOT NULL
+    DEFAULT '{"enabled": false, "template": ""}'::jsonb,
+  ADD COLUMN IF NOT EXISTS receipt_fields_policy jsonb NOT NULL
+    DEFAULT '{"merchant": true, "amount": true, "token": true, "timeWindow": true, "invoiceRef": true, "paylinkId": true}'::jsonb,
+  ADD COLUMN IF NOT EXISTS expected_memo text NULL;
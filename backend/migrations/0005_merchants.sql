@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS merchants (
+  id uuid PRIMARY KEY,
+  name text NOT NULL,
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS api_keys (
+  id uuid PRIMARY KEY,
+  merchant_id uuid NOT NULL REFERENCES merchants(id),
+  prefix text NOT NULL,
+  key_hash text UNIQUE NOT NULL,
+  scopes text[] NOT NULL,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  last_used_at timestamptz NULL,
+  revoked_at timestamptz NULL
+);
+
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS merchant_id uuid NULL REFERENCES merchants(id);
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_merchant ON api_keys(merchant_id);
+CREATE INDEX IF NOT EXISTS idx_paylinks_merchant_id ON paylinks(merchant_id);
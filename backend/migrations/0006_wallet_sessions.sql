@@ -0,0 +1,27 @@
+CREATE TABLE IF NOT EXISTS auth_nonces (
+  nonce text PRIMARY KEY,
+  pubkey text NOT NULL,
+  message text NOT NULL,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  expires_at timestamptz NOT NULL,
+  used_at timestamptz NULL
+);
+
+CREATE TABLE IF NOT EXISTS merchant_wallets (
+  pubkey text PRIMARY KEY,
+  merchant_id uuid NOT NULL REFERENCES merchants(id),
+  verified_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS wallet_sessions (
+  id uuid PRIMARY KEY,
+  token_hash text UNIQUE NOT NULL,
+  pubkey text NOT NULL,
+  merchant_id uuid NOT NULL REFERENCES merchants(id),
+  created_at timestamptz NOT NULL DEFAULT now(),
+  expires_at timestamptz NOT NULL,
+  revoked_at timestamptz NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_merchant_wallets_merchant ON merchant_wallets(merchant_id);
+CREATE INDEX IF NOT EXISTS idx_auth_nonces_expires ON auth_nonces(expires_at);
@@ -0,0 +1,5 @@
+ALTER TABLE receipts ADD COLUMN IF NOT EXISTS simulated boolean NOT NULL DEFAULT false;
+
+UPDATE receipts r SET simulated = true
+FROM paylinks p
+WHERE r.paylink_id = p.id AND p.paid_signature LIKE 'simulated-%';
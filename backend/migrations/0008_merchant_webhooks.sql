@@ -0,0 +1,23 @@
+ALTER TABLE merchants ADD COLUMN IF NOT EXISTS webhook_url text NULL;
+ALTER TABLE merchants ADD COLUMN IF NOT EXISTS webhook_secret text NULL;
+
+CREATE TABLE IF NOT EXISTS merchant_webhook_deliveries (
+  id uuid PRIMARY KEY,
+  merchant_id uuid NOT NULL REFERENCES merchants(id),
+  paylink_id uuid NULL REFERENCES paylinks(id),
+  event_type text NOT NULL,
+  url text NOT NULL,
+  payload jsonb NOT NULL,
+  status text NOT NULL DEFAULT 'pending',
+  attempts integer NOT NULL DEFAULT 0,
+  run_at timestamptz NOT NULL DEFAULT now(),
+  locked_at timestamptz NULL,
+  last_status_code integer NULL,
+  last_error text NULL,
+  delivered_at timestamptz NULL,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_merchant_webhook_deliveries_runnable ON merchant_webhook_deliveries(status, run_at);
+CREATE INDEX IF NOT EXISTS idx_merchant_webhook_deliveries_merchant ON merchant_webhook_deliveries(merchant_id, created_at DESC);
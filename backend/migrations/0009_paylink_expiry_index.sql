@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS idx_paylinks_pending_expiry ON paylinks(expires_at) WHERE status = 'pending';
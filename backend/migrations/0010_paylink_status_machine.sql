@@ -0,0 +1,21 @@
+ALTER TABLE paylinks ADD CONSTRAINT paylinks_status_check
+  CHECK (status IN ('pending', 'paid', 'expired', 'cancelled'));
+
+-- Mirrors PaylinkStatus::can_transition_to so that no write path, including
+-- manual SQL, can move a paylink out of a terminal state.
+CREATE OR REPLACE FUNCTION paylinks_check_status_transition() RETURNS trigger AS $$
+BEGIN
+  IF NEW.status IS DISTINCT FROM OLD.status AND NOT (
+    (OLD.status = 'pending' AND NEW.status IN ('paid', 'expired', 'cancelled'))
+    OR (OLD.status = 'expired' AND NEW.status = 'paid')
+  ) THEN
+    RAISE EXCEPTION 'invalid paylink status transition % -> %', OLD.status, NEW.status
+      USING ERRCODE = 'check_violation';
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER paylinks_status_transition
+  BEFORE UPDATE OF status ON paylinks
+  FOR EACH ROW EXECUTE FUNCTION paylinks_check_status_transition();
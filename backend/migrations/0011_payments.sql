@@ -0,0 +1,45 @@
+CREATE TABLE IF NOT EXISTS payments (
+  id uuid PRIMARY KEY,
+  paylink_id uuid NOT NULL REFERENCES paylinks(id),
+  signature text NOT NULL,
+  amount bigint NOT NULL,
+  mint text NOT NULL,
+  slot bigint NULL,
+  block_time timestamptz NULL,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  UNIQUE (paylink_id, signature)
+);
+
+CREATE INDEX IF NOT EXISTS idx_payments_paylink ON payments(paylink_id, created_at);
+
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS amount_received bigint NOT NULL DEFAULT 0;
+
+-- Paylinks paid before payments were tracked count as fully paid by their
+-- paying signature.
+INSERT INTO payments (id, paylink_id, signature, amount, mint, slot)
+SELECT gen_random_uuid(), id, paid_signature, expected_amount, mint, paid_slot
+FROM paylinks WHERE status = 'paid' AND paid_signature IS NOT NULL
+ON CONFLICT DO NOTHING;
+UPDATE paylinks SET amount_received = expected_amount WHERE status = 'paid';
+
+ALTER TABLE paylinks DROP CONSTRAINT IF EXISTS paylinks_status_check;
+ALTER TABLE paylinks ADD CONSTRAINT paylinks_status_check
+  CHECK (status IN ('pending', 'partially_paid', 'paid', 'expired', 'cancelled'));
+
+CREATE OR REPLACE FUNCTION paylinks_check_status_transition() RETURNS trigger AS $$
+BEGIN
+  IF NEW.status IS DISTINCT FROM OLD.status AND NOT (
+    (OLD.status = 'pending' AND NEW.status IN ('partially_paid', 'paid', 'expired', 'cancelled'))
+    OR (OLD.status = 'partially_paid' AND NEW.status IN ('paid', 'expired', 'cancelled'))
+    OR (OLD.status = 'expired' AND NEW.status = 'paid')
+  ) THEN
+    RAISE EXCEPTION 'invalid paylink status transition % -> %', OLD.status, NEW.status
+      USING ERRCODE = 'check_violation';
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP INDEX IF EXISTS idx_paylinks_pending_expiry;
+CREATE INDEX IF NOT EXISTS idx_paylinks_open_expiry ON paylinks(expires_at)
+  WHERE status IN ('pending', 'partially_paid');
@@ -0,0 +1,6 @@
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS amount_tolerance jsonb NOT NULL
+  DEFAULT '{"kind":"absolute","value":0}'::jsonb;
+
+ALTER TABLE payments ADD COLUMN IF NOT EXISTS refund_due bigint NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_payments_refund_due ON payments(paylink_id) WHERE refund_due > 0;
@@ -0,0 +1,13 @@
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS multi_use boolean NOT NULL DEFAULT false;
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS max_uses integer NULL;
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS max_total bigint NULL;
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS open_amount boolean NOT NULL DEFAULT false;
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS min_amount bigint NULL;
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS max_amount bigint NULL;
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS use_count integer NOT NULL DEFAULT 0;
+
+UPDATE paylinks p SET use_count = (SELECT count(*) FROM payments pm WHERE pm.paylink_id = p.id);
+
+-- Links that take many payments get one receipt per payment.
+ALTER TABLE receipts ADD COLUMN IF NOT EXISTS payment_id uuid NULL REFERENCES payments(id);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_receipts_payment ON receipts(payment_id) WHERE payment_id IS NOT NULL;
@@ -0,0 +1,54 @@
+CREATE TABLE IF NOT EXISTS subscriptions (
+  id uuid PRIMARY KEY,
+  merchant_id uuid NOT NULL REFERENCES merchants(id),
+  merchant_pubkey text NOT NULL,
+  amount bigint NOT NULL,
+  mint text NOT NULL,
+  interval_unit text NOT NULL CHECK (interval_unit IN ('day', 'week', 'month', 'year')),
+  interval_count integer NOT NULL CHECK (interval_count > 0),
+  anchor_at timestamptz NOT NULL,
+  trial_ends_at timestamptz NULL,
+  status text NOT NULL CHECK (status IN ('trialing', 'active', 'past_due', 'cancelled')),
+  customer_ref text NULL,
+  memo_policy jsonb NOT NULL,
+  receipt_fields_policy jsonb NOT NULL,
+  amount_tolerance jsonb NOT NULL DEFAULT '{"kind":"absolute","value":0}',
+  next_cycle_index integer NOT NULL,
+  next_cycle_at timestamptz NOT NULL,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  cancelled_at timestamptz NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_subscriptions_merchant ON subscriptions(merchant_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_subscriptions_due ON subscriptions(next_cycle_at)
+  WHERE status <> 'cancelled';
+
+-- One row per billing period, each with the paylink issued for it.
+CREATE TABLE IF NOT EXISTS subscription_cycles (
+  id uuid PRIMARY KEY,
+  subscription_id uuid NOT NULL REFERENCES subscriptions(id),
+  cycle_index integer NOT NULL,
+  period_start timestamptz NOT NULL,
+  period_end timestamptz NOT NULL,
+  paylink_id uuid NOT NULL UNIQUE REFERENCES paylinks(id),
+  status text NOT NULL CHECK (status IN ('open', 'paid', 'missed', 'cancelled')),
+  created_at timestamptz NOT NULL DEFAULT now(),
+  settled_at timestamptz NULL,
+  UNIQUE (subscription_id, cycle_index)
+);
+
+CREATE TABLE IF NOT EXISTS subscription_events (
+  id bigserial PRIMARY KEY,
+  subscription_id uuid NOT NULL REFERENCES subscriptions(id),
+  type text NOT NULL,
+  at timestamptz NOT NULL DEFAULT now(),
+  detail jsonb NOT NULL DEFAULT '{}'
+);
+
+CREATE INDEX IF NOT EXISTS idx_subscription_events_subscription ON subscription_events(subscription_id, at);
+
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS subscription_id uuid NULL REFERENCES subscriptions(id);
+ALTER TABLE receipts ADD COLUMN IF NOT EXISTS subscription_id uuid NULL REFERENCES subscriptions(id);
+CREATE INDEX IF NOT EXISTS idx_receipts_subscription ON receipts(subscription_id) WHERE subscription_id IS NOT NULL;
+
+ALTER TABLE merchant_webhook_deliveries ADD COLUMN IF NOT EXISTS subscription_id uuid NULL REFERENCES subscriptions(id);
@@ -0,0 +1,19 @@
+-- The invoice behind a paylink. The computed amounts are stored alongside
+-- the lines so the paylink amount can always be traced back to them.
+CREATE TABLE IF NOT EXISTS invoices (
+  id uuid PRIMARY KEY,
+  paylink_id uuid NOT NULL UNIQUE REFERENCES paylinks(id),
+  customer jsonb NOT NULL,
+  line_items jsonb NOT NULL,
+  discounts jsonb NOT NULL DEFAULT '[]',
+  taxes jsonb NOT NULL DEFAULT '[]',
+  subtotal bigint NOT NULL,
+  discount_total bigint NOT NULL,
+  tax_total bigint NOT NULL,
+  total bigint NOT NULL CHECK (total > 0),
+  due_date timestamptz NULL,
+  notes text NULL,
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS has_invoice boolean NOT NULL DEFAULT false;
@@ -0,0 +1,7 @@
+-- Fiat-priced paylinks: the merchant's price and the quote that turned it
+-- into `expected_amount`. `quote_ttl_secs` is set when the quote is taken
+-- again each time a payer opens the link after the previous one lapsed.
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS fiat_currency text NULL CHECK (fiat_currency IN ('USD', 'EUR'));
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS fiat_amount bigint NULL CHECK (fiat_amount > 0);
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS price_quote jsonb NULL;
+ALTER TABLE paylinks ADD COLUMN IF NOT EXISTS quote_ttl_secs integer NULL CHECK (quote_ttl_secs > 0);
@@ -0,0 +1,4 @@
+-- Mints are stored as addresses since the token registry resolves symbols
+-- at creation; native SOL is stored as the wrapped SOL mint.
+UPDATE paylinks SET mint = 'So11111111111111111111111111111111111111112' WHERE mint = 'SOL';
+UPDATE subscriptions SET mint = 'So11111111111111111111111111111111111111112' WHERE mint = 'SOL';
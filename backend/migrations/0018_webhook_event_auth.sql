@@ -0,0 +1,3 @@
+-- Transfer data carried inline in a webhook is only trusted when the delivery
+-- was authenticated; other events are verified against the chain provider.
+ALTER TABLE webhook_events ADD COLUMN IF NOT EXISTS authenticated boolean NOT NULL DEFAULT false;
@@ -0,0 +1,10 @@
+-- A wallet gets the scopes of the API key that linked it, and each session
+-- the scopes of its wallet, rather than every scope. Existing links and
+-- sessions keep the full set they were granted.
+ALTER TABLE merchant_wallets ADD COLUMN IF NOT EXISTS scopes text[];
+UPDATE merchant_wallets SET scopes = ARRAY['paylinks:read', 'paylinks:write', 'receipts:read', 'webhooks:write'] WHERE scopes IS NULL;
+ALTER TABLE merchant_wallets ALTER COLUMN scopes SET NOT NULL;
+
+ALTER TABLE wallet_sessions ADD COLUMN IF NOT EXISTS scopes text[];
+UPDATE wallet_sessions SET scopes = ARRAY['paylinks:read', 'paylinks:write', 'receipts:read', 'webhooks:write'] WHERE scopes IS NULL;
+ALTER TABLE wallet_sessions ALTER COLUMN scopes SET NOT NULL;
@@ -0,0 +1,3 @@
+-- A transaction pays at most one paylink; insert_payment checks for the
+-- signature across all paylinks.
+CREATE INDEX IF NOT EXISTS idx_payments_signature ON payments(signature);
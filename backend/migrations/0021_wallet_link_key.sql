@@ -0,0 +1,4 @@
+-- The API key that linked each wallet, so revoking the key also unlinks the
+-- wallet. Links made before this migration have no key recorded.
+ALTER TABLE merchant_wallets ADD COLUMN IF NOT EXISTS api_key_id uuid NULL REFERENCES api_keys(id);
+CREATE INDEX IF NOT EXISTS idx_merchant_wallets_api_key ON merchant_wallets(api_key_id);
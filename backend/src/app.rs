@@ -1,19 +1,25 @@
 use std::net::SocketAddr;
+use std::sync::Arc;
 
 use axum::{routing::get, Router};
 use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::trace::TraceLayer;
 
+use crate::chain::{owners::OwnerResolver, tokens::TokenRegistry, ChainProvider};
 use crate::config::Config;
 use crate::db::Db;
+use crate::pricing::PriceSource;
 use crate::routes;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Db,
-    pub http: reqwest::Client,
     pub config: Config,
     pub rail: crate::privacy::rail::RailSelector,
+    pub chain: Arc<dyn ChainProvider>,
+    pub owners: Arc<OwnerResolver>,
+    pub tokens: Arc<TokenRegistry>,
+    pub prices: Arc<dyn PriceSource>,
 }
 
 pub fn build_router(state: AppState) -> Router {
@@ -32,12 +38,17 @@ pub fn build_router(state: AppState) -> Router {
             .allow_methods([
                 axum::http::Method::GET,
                 axum::http::Method::POST,
+                axum::http::Method::PUT,
+                axum::http::Method::PATCH,
+                axum::http::Method::DELETE,
                 axum::http::Method::OPTIONS,
             ])
             .allow_headers([
                 axum::http::header::CONTENT_TYPE,
                 axum::http::header::AUTHORIZATION,
-                axum::http::HeaderName::from_static("x-webhook-secret"),
+                axum::http::HeaderName::from_static(crate::auth::API_KEY_HEADER),
+                axum::http::HeaderName::from_static(crate::util::webhook_auth::SECRET_HEADER),
+                axum::http::HeaderName::from_static(crate::util::webhook_auth::SIGNATURE_HEADER),
             ])
     };
 
@@ -45,7 +56,12 @@ pub fn build_router(state: AppState) -> Router {
         .route("/health", get(|| async { "ok" }))
         .nest("/paylinks", routes::paylinks::router())
         .nest("/receipts", routes::receipts::router())
+        .nest("/subscriptions", routes::subscriptions::router())
         .nest("/fees", routes::fees::router())
+        .nest("/admin", routes::admin::router())
+        .nest("/auth", routes::auth::router())
+        .nest("/merchant/webhook", routes::merchant_webhooks::router())
+        .nest("/public", routes::public::router())
         .route("/helius/webhook", axum::routing::post(routes::helius_webhook::handle))
         .with_state(state)
         .layer(cors)
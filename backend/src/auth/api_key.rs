@@ -0,0 +1,30 @@
+use rand::RngCore;
+
+use crate::util::crypto;
+
+const KEY_PREFIX: &str = "rl_";
+
+/// A freshly minted API key. `key` is shown to the merchant once; only
+/// `hash` is stored.
+pub struct NewApiKey {
+    pub key: String,
+    pub prefix: String,
+    pub hash: String,
+}
+
+pub fn generate() -> NewApiKey {
+    let mut buf = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut buf);
+    let key = format!("{}{}", KEY_PREFIX, bs58::encode(buf).into_string());
+    NewApiKey {
+        prefix: key.chars().take(KEY_PREFIX.len() + 6).collect(),
+        hash: hash(&key),
+        key,
+    }
+}
+
+/// Keys carry 256 bits of entropy, so a plain SHA-256 is enough to make the
+/// stored value useless without turning lookups into a slow KDF.
+pub fn hash(key: &str) -> String {
+    crypto::sha256_hex(key.as_bytes())
+}
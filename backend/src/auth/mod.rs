@@ -0,0 +1,105 @@
+pub mod api_key;
+pub mod siws;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap},
+};
+use uuid::Uuid;
+
+use crate::{app::AppState, db::queries, error::AppError};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+pub const SCOPE_PAYLINKS_READ: &str = "paylinks:read";
+pub const SCOPE_PAYLINKS_WRITE: &str = "paylinks:write";
+pub const SCOPE_RECEIPTS_READ: &str = "receipts:read";
+pub const SCOPE_WEBHOOKS_WRITE: &str = "webhooks:write";
+pub const ALL_SCOPES: &[&str] = &[
+    SCOPE_PAYLINKS_READ,
+    SCOPE_PAYLINKS_WRITE,
+    SCOPE_RECEIPTS_READ,
+    SCOPE_WEBHOOKS_WRITE,
+];
+
+/// The merchant behind the request's API key or wallet session. Taking this
+/// as a handler argument makes the route require `Authorization: Bearer <key>`
+/// (or `x-api-key`).
+#[derive(Debug, Clone)]
+pub struct MerchantAuth {
+    pub merchant_id: Uuid,
+    pub scopes: Vec<String>,
+    /// Set when the request carries a wallet session rather than an API key.
+    pub wallet: Option<String>,
+    /// Set when the request carries an API key.
+    pub api_key_id: Option<Uuid>,
+}
+
+impl MerchantAuth {
+    pub fn require(&self, scope: &str) -> Result<(), AppError> {
+        if self.scopes.iter().any(|s| s == scope) {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden(format!("API key lacks scope {}", scope)))
+        }
+    }
+}
+
+pub fn validate_scopes(scopes: &[String]) -> Result<(), AppError> {
+    match scopes.iter().find(|s| !ALL_SCOPES.contains(&s.as_str())) {
+        Some(unknown) => Err(AppError::BadRequest(format!("unknown scope {}", unknown))),
+        None => Ok(()),
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for MerchantAuth {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let key = bearer_token(&parts.headers)
+            .or_else(|| {
+                parts
+                    .headers
+                    .get(API_KEY_HEADER)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|v| v.trim())
+                    .filter(|v| !v.is_empty())
+            })
+            .ok_or_else(|| AppError::Unauthorized("missing API key".to_string()))?;
+
+        if key.starts_with(siws::SESSION_PREFIX) {
+            let session = queries::get_active_wallet_session(&state.db, &api_key::hash(key))
+                .await?
+                .ok_or_else(|| AppError::Unauthorized("invalid or expired session".to_string()))?;
+            return Ok(MerchantAuth {
+                merchant_id: session.merchant_id,
+                scopes: session.scopes,
+                wallet: Some(session.pubkey),
+                api_key_id: None,
+            });
+        }
+
+        let api_key = queries::use_api_key(&state.db, &api_key::hash(key))
+            .await?
+            .ok_or_else(|| AppError::Unauthorized("invalid API key".to_string()))?;
+
+        Ok(MerchantAuth {
+            merchant_id: api_key.merchant_id,
+            scopes: api_key.scopes,
+            wallet: None,
+            api_key_id: Some(api_key.id),
+        })
+    }
+}
+
+/// The raw bearer token on a request, if any.
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+}
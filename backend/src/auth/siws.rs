@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use rand::RngCore;
+
+use crate::config::Config;
+
+pub const SESSION_PREFIX: &str = "sess_";
+
+pub fn new_nonce() -> String {
+    let mut buf = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut buf);
+    bs58::encode(buf).into_string()
+}
+
+pub fn new_session_token() -> String {
+    let mut buf = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut buf);
+    format!("{}{}", SESSION_PREFIX, bs58::encode(buf).into_string())
+}
+
+/// The Sign-In With Solana message the wallet is asked to sign. It is stored
+/// with the nonce, so verification checks the exact bytes that were issued.
+pub fn message(
+    config: &Config,
+    pubkey: &str,
+    nonce: &str,
+    issued_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+) -> String {
+    let uri = config.base_pay_url.trim_end_matches('/');
+    let domain = uri.split("://").nth(1).unwrap_or(uri);
+    format!(
+        "{domain} wants you to sign in with your Solana account:\n\
+         {pubkey}\n\
+         \n\
+         Sign in to {label} to manage PayLinks for this wallet.\n\
+         \n\
+         URI: {uri}\n\
+         Version: 1\n\
+         Chain ID: {chain}\n\
+         Nonce: {nonce}\n\
+         Issued At: {issued}\n\
+         Expiration Time: {expires}",
+        label = config.pay_label,
+        chain = config.helius_cluster,
+        issued = issued_at.to_rfc3339(),
+        expires = expires_at.to_rfc3339(),
+    )
+}
+
+/// Checks an ed25519 signature over `message`. Wallets return signatures as
+/// bytes, which clients send either base58 or base64 encoded.
+pub fn verify_signature(pubkey: &str, message: &str, signature: &str) -> bool {
+    let Some(key) = crate::solana::tx::decode_pubkey(pubkey) else {
+        return false;
+    };
+    let Ok(key) = VerifyingKey::from_bytes(&key) else {
+        return false;
+    };
+    let bytes = bs58::decode(signature).into_vec().ok().filter(|b| b.len() == 64).or_else(|| {
+        use base64::Engine;
+        base64::engine::general_purpose::STANDARD.decode(signature).ok()
+    });
+    let Some(sig) = bytes.and_then(|b| <[u8; 64]>::try_from(b).ok()) else {
+        return false;
+    };
+    key.verify(message.as_bytes(), &Signature::from_bytes(&sig)).is_ok()
+}
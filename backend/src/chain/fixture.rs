@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::error::AppError;
+use crate::helius::enhanced_tx::TxView;
+use crate::helius::webhook_payload;
+use crate::privacy::rail::BoxFuture;
+
+use super::{default_priority_fees, rpc_tx, ChainProvider, PriorityFeeEstimate};
+
+/// Serves chain data from JSON files so the payment flow can run offline.
+///
+/// Layout under the fixture directory:
+/// - `transactions/<signature>.json`: a Helius enhanced transaction (object or
+///   one-element array) or a `getTransaction` jsonParsed result
+/// - `priority_fees.json`: `{"levels": {...}, "recommended": n}`
+/// - `signatures/<address>.json`: an array of signature strings, newest first
+/// - `accounts/<address>.json`: the `value` of a jsonParsed `getAccountInfo`
+/// - `latest_blockhash.json`: `{"blockhash": "..."}`
+pub struct FixtureProvider {
+    dir: PathBuf,
+}
+
+impl FixtureProvider {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    async fn read_json(&self, path: &Path) -> Result<Option<Value>, AppError> {
+        let bytes = match tokio::fs::read(path).await {
+            Ok(b) => b,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(AppError::Other(format!(
+                    "failed to read fixture {}: {}",
+                    path.display(),
+                    err
+                )))
+            }
+        };
+        serde_json::from_slice(&bytes).map(Some).map_err(|e| {
+            AppError::Other(format!("invalid fixture {}: {}", path.display(), e))
+        })
+    }
+}
+
+impl ChainProvider for FixtureProvider {
+    fn name(&self) -> &'static str {
+        "fixture"
+    }
+
+    fn fetch_transaction<'a>(&'a self, signature: &'a str) -> BoxFuture<'a, Result<TxView, AppError>> {
+        Box::pin(async move {
+            if !crate::solana::is_signature(signature) {
+                return Err(AppError::NotFound(format!("no fixture for {}", signature)));
+            }
+            let path = self
+                .dir
+                .join("transactions")
+                .join(format!("{}.json", signature));
+            let value = self
+                .read_json(&path)
+                .await?
+                .ok_or_else(|| AppError::NotFound(format!("no fixture for {}", signature)))?;
+
+            if value.get("meta").is_some() && value.get("transaction").is_some() {
+                return Ok(rpc_tx::parse_transaction(signature, value));
+            }
+            webhook_payload::tx_view_from_payload(signature, &value).ok_or_else(|| {
+                AppError::Other(format!("fixture for {} has no usable transfer data", signature))
+            })
+        })
+    }
+
+    fn priority_fee_estimate<'a>(
+        &'a self,
+        _serialized_tx: Option<String>,
+        _account_keys: Option<Vec<String>>,
+    ) -> BoxFuture<'a, Result<PriorityFeeEstimate, AppError>> {
+        Box::pin(async move {
+            let value = self.read_json(&self.dir.join("priority_fees.json")).await?;
+            let fallback = default_priority_fees();
+            Ok(match value {
+                Some(v) => PriorityFeeEstimate {
+                    levels: v.get("levels").cloned().unwrap_or(fallback.levels),
+                    recommended: v
+                        .get("recommended")
+                        .and_then(|r| r.as_i64())
+                        .unwrap_or(fallback.recommended),
+                },
+                None => fallback,
+            })
+        })
+    }
+
+    fn signatures_for_address<'a>(
+        &'a self,
+        address: &'a str,
+        limit: usize,
+    ) -> BoxFuture<'a, Result<Vec<String>, AppError>> {
+        Box::pin(async move {
+            let path = self.dir.join("signatures").join(format!("{}.json", address));
+            let value = self.read_json(&path).await?.unwrap_or(Value::Array(Vec::new()));
+            Ok(value
+                .as_array()
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|v| v.as_str())
+                        .take(limit)
+                        .map(|s| s.to_string())
+                        .collect()
+                })
+                .unwrap_or_default())
+        })
+    }
+
+    fn account_info<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<Option<Value>, AppError>> {
+        Box::pin(async move {
+            if !crate::solana::is_pubkey(address) {
+                return Ok(None);
+            }
+            let path = self.dir.join("accounts").join(format!("{}.json", address));
+            self.read_json(&path).await
+        })
+    }
+
+    fn latest_blockhash(&self) -> BoxFuture<'_, Result<String, AppError>> {
+        Box::pin(async move {
+            self.read_json(&self.dir.join("latest_blockhash.json"))
+                .await?
+                .and_then(|v| v.get("blockhash").and_then(|b| b.as_str()).map(|s| s.to_string()))
+                .ok_or_else(|| AppError::NotFound("no latest_blockhash fixture".to_string()))
+        })
+    }
+}
@@ -0,0 +1,97 @@
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::helius::enhanced_tx::{self, TxView};
+use crate::helius::priority_fee;
+use crate::privacy::rail::BoxFuture;
+
+use super::{default_priority_fees, rpc, ChainProvider, PriorityFeeEstimate};
+
+pub struct HeliusProvider {
+    http: reqwest::Client,
+    config: Config,
+}
+
+impl HeliusProvider {
+    pub fn new(http: reqwest::Client, config: Config) -> Self {
+        Self { http, config }
+    }
+
+    fn rpc_url(&self) -> String {
+        format!(
+            "{}/?api-key={}",
+            crate::helius::base_url(&self.config),
+            self.config.helius_api_key
+        )
+    }
+}
+
+impl ChainProvider for HeliusProvider {
+    fn name(&self) -> &'static str {
+        "helius"
+    }
+
+    fn fetch_transaction<'a>(&'a self, signature: &'a str) -> BoxFuture<'a, Result<TxView, AppError>> {
+        Box::pin(async move {
+            match enhanced_tx::fetch_enhanced_tx(&self.http, &self.config, signature).await? {
+                Some(tx) => Ok(tx),
+                None => rpc::get_transaction(&self.http, &self.rpc_url(), signature).await,
+            }
+        })
+    }
+
+    fn priority_fee_estimate<'a>(
+        &'a self,
+        serialized_tx: Option<String>,
+        account_keys: Option<Vec<String>>,
+    ) -> BoxFuture<'a, Result<PriorityFeeEstimate, AppError>> {
+        Box::pin(async move {
+            let value = priority_fee::get_priority_fee_estimate(
+                &self.http,
+                &self.config,
+                serialized_tx,
+                account_keys,
+            )
+            .await?;
+            Ok(parse_priority_fee(&value))
+        })
+    }
+
+    fn signatures_for_address<'a>(
+        &'a self,
+        address: &'a str,
+        limit: usize,
+    ) -> BoxFuture<'a, Result<Vec<String>, AppError>> {
+        Box::pin(async move {
+            rpc::get_signatures_for_address(&self.http, &self.rpc_url(), address, limit).await
+        })
+    }
+
+    fn account_info<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<Option<Value>, AppError>> {
+        Box::pin(async move { rpc::get_account_info(&self.http, &self.rpc_url(), address).await })
+    }
+
+    fn latest_blockhash(&self) -> BoxFuture<'_, Result<String, AppError>> {
+        Box::pin(async move { rpc::get_latest_blockhash(&self.http, &self.rpc_url()).await })
+    }
+}
+
+fn parse_priority_fee(value: &Value) -> PriorityFeeEstimate {
+    let estimate = value
+        .get("result")
+        .and_then(|v| v.get("priorityFeeEstimate"));
+    let fallback = default_priority_fees();
+
+    let levels = estimate.cloned().unwrap_or(fallback.levels);
+    let recommended = estimate
+        .and_then(|v| v.get("medium"))
+        .and_then(|v| v.as_f64())
+        .map(|v| v.round() as i64)
+        .unwrap_or(fallback.recommended);
+
+    PriorityFeeEstimate {
+        levels,
+        recommended,
+    }
+}
@@ -0,0 +1,66 @@
+pub mod fixture;
+pub mod helius;
+pub mod owners;
+pub mod rpc;
+pub mod rpc_tx;
+pub mod tokens;
+
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::helius::enhanced_tx::TxView;
+use crate::privacy::rail::BoxFuture;
+
+#[derive(Debug, Clone)]
+pub struct PriorityFeeEstimate {
+    /// Fee levels in micro-lamports per compute unit, keyed by level name.
+    pub levels: Value,
+    pub recommended: i64,
+}
+
+/// Source of on-chain data. Helius is the default; a plain Solana JSON-RPC
+/// node or a directory of fixtures can be swapped in via `CHAIN_PROVIDER`.
+pub trait ChainProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn fetch_transaction<'a>(&'a self, signature: &'a str) -> BoxFuture<'a, Result<TxView, AppError>>;
+    fn priority_fee_estimate<'a>(
+        &'a self,
+        serialized_tx: Option<String>,
+        account_keys: Option<Vec<String>>,
+    ) -> BoxFuture<'a, Result<PriorityFeeEstimate, AppError>>;
+    fn signatures_for_address<'a>(
+        &'a self,
+        address: &'a str,
+        limit: usize,
+    ) -> BoxFuture<'a, Result<Vec<String>, AppError>>;
+    /// `getAccountInfo` value in jsonParsed encoding, `None` if the account
+    /// does not exist.
+    fn account_info<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<Option<Value>, AppError>>;
+    fn latest_blockhash(&self) -> BoxFuture<'_, Result<String, AppError>>;
+}
+
+/// Fixtures describe transactions that never happened, so like simulated
+/// payments they are refused in production.
+pub fn from_config(config: &Config, http: reqwest::Client) -> Result<Arc<dyn ChainProvider>, AppError> {
+    Ok(match config.chain_provider.as_str() {
+        "rpc" => Arc::new(rpc::RpcProvider::new(http, config.solana_rpc_url.clone())),
+        "fixture" if !config.environment.allows_simulation() => {
+            return Err(AppError::Other(
+                "CHAIN_PROVIDER=fixture is not allowed in production".to_string(),
+            ))
+        }
+        "fixture" => Arc::new(fixture::FixtureProvider::new(&config.chain_fixture_dir)),
+        _ => Arc::new(helius::HeliusProvider::new(http, config.clone())),
+    })
+}
+
+/// Fallback used when a provider has no fee data to offer.
+pub fn default_priority_fees() -> PriorityFeeEstimate {
+    PriorityFeeEstimate {
+        levels: serde_json::json!({"low":1000,"medium":2000,"high":5000}),
+        recommended: 2000,
+    }
+}
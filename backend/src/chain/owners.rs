@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use crate::error::AppError;
+use crate::helius::enhanced_tx::TxView;
+
+use super::ChainProvider;
+
+const MAX_CACHE_ENTRIES: usize = 10_000;
+/// Token account owners can be changed with `SetAuthority`, so entries are
+/// looked up again after this long.
+const OWNER_TTL: Duration = Duration::from_secs(600);
+
+/// Maps token accounts to the wallet that owns them so payments into an ATA
+/// (or any other token account) can be matched against the merchant wallet.
+/// Lookups go through the chain provider and found owners are cached for
+/// `OWNER_TTL`. Misses are not cached, as the account may be created later.
+pub struct OwnerResolver {
+    chain: Arc<dyn ChainProvider>,
+    cache: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl OwnerResolver {
+    pub fn new(chain: Arc<dyn ChainProvider>) -> Self {
+        Self {
+            chain,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Fills in `owner` on token transfers that the transaction data did not
+    /// already attribute to a wallet.
+    pub async fn resolve(&self, tx: &mut TxView) -> Result<(), AppError> {
+        for transfer in tx.token_transfers.iter_mut() {
+            if transfer.owner.is_none() {
+                transfer.owner = self.token_account_owner(&transfer.destination).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn token_account_owner(&self, address: &str) -> Result<Option<String>, AppError> {
+        if let Some((owner, cached_at)) = self.cache.lock().unwrap().get(address) {
+            if cached_at.elapsed() < OWNER_TTL {
+                return Ok(Some(owner.clone()));
+            }
+        }
+
+        let info = self.chain.account_info(address).await?;
+        let owner = match info.as_ref().and_then(parse_token_account_owner) {
+            Some(owner) => owner,
+            None => return Ok(None),
+        };
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHE_ENTRIES {
+            cache.retain(|_, (_, cached_at)| cached_at.elapsed() < OWNER_TTL);
+            if cache.len() >= MAX_CACHE_ENTRIES {
+                cache.clear();
+            }
+        }
+        cache.insert(address.to_string(), (owner.clone(), Instant::now()));
+        Ok(Some(owner))
+    }
+}
+
+fn parse_token_account_owner(info: &Value) -> Option<String> {
+    let data = info.get("data")?;
+    let program = data.get("program").and_then(|v| v.as_str())?;
+    if program != "spl-token" && program != "spl-token-2022" {
+        return None;
+    }
+    let parsed = data.get("parsed")?;
+    if parsed.get("type").and_then(|v| v.as_str()) != Some("account") {
+        return None;
+    }
+    parsed
+        .pointer("/info/owner")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+}
@@ -0,0 +1,186 @@
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::helius::enhanced_tx::TxView;
+use crate::privacy::rail::BoxFuture;
+
+use super::{default_priority_fees, rpc_tx, ChainProvider, PriorityFeeEstimate};
+
+/// Talks to any Solana JSON-RPC node (our own, or a public endpoint).
+pub struct RpcProvider {
+    http: reqwest::Client,
+    url: String,
+}
+
+impl RpcProvider {
+    pub fn new(http: reqwest::Client, url: String) -> Self {
+        Self { http, url }
+    }
+}
+
+impl ChainProvider for RpcProvider {
+    fn name(&self) -> &'static str {
+        "rpc"
+    }
+
+    fn fetch_transaction<'a>(&'a self, signature: &'a str) -> BoxFuture<'a, Result<TxView, AppError>> {
+        Box::pin(get_transaction(&self.http, &self.url, signature))
+    }
+
+    fn priority_fee_estimate<'a>(
+        &'a self,
+        _serialized_tx: Option<String>,
+        account_keys: Option<Vec<String>>,
+    ) -> BoxFuture<'a, Result<PriorityFeeEstimate, AppError>> {
+        Box::pin(async move {
+            let params = match account_keys {
+                Some(keys) => json!([keys]),
+                None => json!([]),
+            };
+            let result = call(&self.http, &self.url, "getRecentPrioritizationFees", params).await?;
+            let mut fees: Vec<i64> = result
+                .as_array()
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|v| v.get("prioritizationFee").and_then(|f| f.as_i64()))
+                        .collect()
+                })
+                .unwrap_or_default();
+            if fees.is_empty() {
+                return Ok(default_priority_fees());
+            }
+            fees.sort_unstable();
+
+            let pick = |pct: usize| fees[(fees.len() - 1) * pct / 100];
+            let levels = json!({
+                "min": fees[0],
+                "low": pick(25),
+                "medium": pick(50),
+                "high": pick(75),
+                "veryHigh": pick(95),
+                "unsafeMax": fees[fees.len() - 1],
+            });
+            Ok(PriorityFeeEstimate {
+                levels,
+                recommended: pick(50),
+            })
+        })
+    }
+
+    fn signatures_for_address<'a>(
+        &'a self,
+        address: &'a str,
+        limit: usize,
+    ) -> BoxFuture<'a, Result<Vec<String>, AppError>> {
+        Box::pin(get_signatures_for_address(&self.http, &self.url, address, limit))
+    }
+
+    fn account_info<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<Option<Value>, AppError>> {
+        Box::pin(get_account_info(&self.http, &self.url, address))
+    }
+
+    fn latest_blockhash(&self) -> BoxFuture<'_, Result<String, AppError>> {
+        Box::pin(get_latest_blockhash(&self.http, &self.url))
+    }
+}
+
+pub async fn call(
+    client: &reqwest::Client,
+    url: &str,
+    method: &str,
+    params: Value,
+) -> Result<Value, AppError> {
+    let body = json!({
+        "jsonrpc": "2.0",
+        "id": method,
+        "method": method,
+        "params": params
+    });
+    let resp = client.post(url).json(&body).send().await?;
+    let mut value: Value = resp.json().await?;
+    if let Some(err) = value.get("error") {
+        return Err(AppError::Other(format!("{} failed: {}", method, err)));
+    }
+    Ok(value.get_mut("result").map(Value::take).unwrap_or(Value::Null))
+}
+
+pub async fn get_transaction(
+    client: &reqwest::Client,
+    url: &str,
+    signature: &str,
+) -> Result<TxView, AppError> {
+    let result = call(
+        client,
+        url,
+        "getTransaction",
+        json!([signature, {
+            "encoding": "jsonParsed",
+            "commitment": "confirmed",
+            "maxSupportedTransactionVersion": 0
+        }]),
+    )
+    .await?;
+    if result.is_null() {
+        return Err(AppError::NotFound(format!("transaction {} not found", signature)));
+    }
+    Ok(rpc_tx::parse_transaction(signature, result))
+}
+
+pub async fn get_signatures_for_address(
+    client: &reqwest::Client,
+    url: &str,
+    address: &str,
+    limit: usize,
+) -> Result<Vec<String>, AppError> {
+    let result = call(
+        client,
+        url,
+        "getSignaturesForAddress",
+        json!([address, {"limit": limit.clamp(1, 1000), "commitment": "confirmed"}]),
+    )
+    .await?;
+    let signatures = result
+        .as_array()
+        .map(|arr| {
+            arr.iter()
+                .filter(|v| v.get("err").map(|e| e.is_null()).unwrap_or(true))
+                .filter_map(|v| v.get("signature").and_then(|s| s.as_str()))
+                .map(|s| s.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(signatures)
+}
+
+pub async fn get_account_info(
+    client: &reqwest::Client,
+    url: &str,
+    address: &str,
+) -> Result<Option<Value>, AppError> {
+    let mut result = call(
+        client,
+        url,
+        "getAccountInfo",
+        json!([address, {"encoding": "jsonParsed", "commitment": "confirmed"}]),
+    )
+    .await?;
+    Ok(result
+        .get_mut("value")
+        .map(Value::take)
+        .filter(|v| !v.is_null()))
+}
+
+pub async fn get_latest_blockhash(client: &reqwest::Client, url: &str) -> Result<String, AppError> {
+    let result = call(
+        client,
+        url,
+        "getLatestBlockhash",
+        json!([{"commitment": "confirmed"}]),
+    )
+    .await?;
+    result
+        .pointer("/value/blockhash")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .ok_or_else(|| AppError::Other("getLatestBlockhash returned no blockhash".to_string()))
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::helius::enhanced_tx::{NativeTransfer, TokenTransfer, TxView};
+use crate::solana;
+
+/// Builds a `TxView` from a standard `getTransaction` result. Handles both the
+/// `jsonParsed` and plain `json` encodings, which is also the shape Helius
+/// uses for raw webhooks.
+pub fn parse_transaction(signature: &str, result: Value) -> TxView {
+    let account_keys = account_keys(&result);
+    let meta = result.get("meta").cloned().unwrap_or(Value::Null);
+
+    let token_transfers = token_transfers(&meta, &account_keys);
+    let native_transfers = native_transfers(&meta, &account_keys);
+
+    let mut memo_strings = Vec::new();
+    for ix in instructions(&result, &meta) {
+        if let Some(memo) = instruction_memo(ix, &account_keys) {
+            push_unique(&mut memo_strings, memo);
+        }
+    }
+    for log in meta
+        .get("logMessages")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str())
+    {
+        if let Some(memo) = log_memo(log) {
+            push_unique(&mut memo_strings, memo);
+        }
+    }
+
+    TxView {
+        signature: signature.to_string(),
+        slot: result.get("slot").and_then(|v| v.as_i64()),
+        timestamp: result.get("blockTime").and_then(|v| v.as_i64()),
+        memo_strings,
+        token_transfers,
+        native_transfers,
+        account_keys,
+        raw: result,
+    }
+}
+
+/// Static account keys followed by any addresses loaded from lookup tables,
+/// in the order that `accountIndex` and `programIdIndex` refer to.
+fn account_keys(result: &Value) -> Vec<String> {
+    let mut keys: Vec<String> = result
+        .pointer("/transaction/message/accountKeys")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|k| {
+            k.as_str()
+                .or_else(|| k.get("pubkey").and_then(|p| p.as_str()))
+                .map(|s| s.to_string())
+        })
+        .collect();
+
+    // jsonParsed already inlines lookup-table addresses into accountKeys.
+    let parsed = result
+        .pointer("/transaction/message/accountKeys/0/pubkey")
+        .is_some();
+    if !parsed {
+        for section in ["writable", "readonly"] {
+            let loaded = result
+                .pointer(&format!("/meta/loadedAddresses/{}", section))
+                .and_then(|v| v.as_array());
+            for key in loaded.into_iter().flatten().filter_map(|k| k.as_str()) {
+                keys.push(key.to_string());
+            }
+        }
+    }
+    keys
+}
+
+fn instructions<'a>(result: &'a Value, meta: &'a Value) -> Vec<&'a Value> {
+    let mut out: Vec<&Value> = result
+        .pointer("/transaction/message/instructions")
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().collect())
+        .unwrap_or_default();
+    for inner in meta
+        .get("innerInstructions")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+    {
+        if let Some(ixs) = inner.get("instructions").and_then(|v| v.as_array()) {
+            out.extend(ixs.iter());
+        }
+    }
+    out
+}
+
+fn instruction_memo(ix: &Value, account_keys: &[String]) -> Option<String> {
+    let program_id = ix
+        .get("programId")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .or_else(|| {
+            ix.get("programIdIndex")
+                .and_then(|v| v.as_u64())
+                .and_then(|i| account_keys.get(i as usize).cloned())
+        })?;
+    if !solana::is_memo_program(&program_id) {
+        return None;
+    }
+    if let Some(parsed) = ix.get("parsed").and_then(|v| v.as_str()) {
+        return Some(parsed.to_string());
+    }
+    let data = ix.get("data").and_then(|v| v.as_str())?;
+    let bytes = bs58::decode(data).into_vec().ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// The memo program logs `Memo (len N): "<text>"`; the text is Rust
+/// debug-escaped, so only the common escapes need undoing.
+fn log_memo(log: &str) -> Option<String> {
+    let rest = log.strip_prefix("Program log: Memo (len ")?;
+    let (_, quoted) = rest.split_once("): ")?;
+    let inner = quoted.strip_prefix('"')?.strip_suffix('"')?;
+    Some(inner.replace("\\\"", "\"").replace("\\\\", "\\"))
+}
+
+fn token_transfers(meta: &Value, account_keys: &[String]) -> Vec<TokenTransfer> {
+    let mut pre: HashMap<(u64, String), i64> = HashMap::new();
+    for balance in token_balances(meta, "preTokenBalances") {
+        pre.insert((balance.index, balance.mint.clone()), balance.amount);
+    }
+
+    let mut out = Vec::new();
+    for balance in token_balances(meta, "postTokenBalances") {
+        let before = pre
+            .get(&(balance.index, balance.mint.clone()))
+            .copied()
+            .unwrap_or(0);
+        let delta = balance.amount - before;
+        if delta <= 0 {
+            continue;
+        }
+        let destination = match account_keys.get(balance.index as usize) {
+            Some(d) => d.clone(),
+            None => continue,
+        };
+        out.push(TokenTransfer {
+            mint: balance.mint,
+            amount: delta,
+            destination,
+            owner: balance.owner,
+        });
+    }
+    out
+}
+
+struct TokenBalance {
+    index: u64,
+    mint: String,
+    owner: Option<String>,
+    amount: i64,
+}
+
+fn token_balances(meta: &Value, field: &str) -> Vec<TokenBalance> {
+    meta.get(field)
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|b| {
+            Some(TokenBalance {
+                index: b.get("accountIndex")?.as_u64()?,
+                mint: b.get("mint")?.as_str()?.to_string(),
+                owner: b.get("owner").and_then(|v| v.as_str()).map(|s| s.to_string()),
+                amount: b
+                    .pointer("/uiTokenAmount/amount")?
+                    .as_str()?
+                    .parse::<i64>()
+                    .ok()?,
+            })
+        })
+        .collect()
+}
+
+fn native_transfers(meta: &Value, account_keys: &[String]) -> Vec<NativeTransfer> {
+    let balances = |field: &str| -> Vec<i64> {
+        meta.get(field)
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter().filter_map(|v| v.as_i64()).collect())
+            .unwrap_or_default()
+    };
+    let pre = balances("preBalances");
+    let post = balances("postBalances");
+
+    pre.iter()
+        .zip(post.iter())
+        .enumerate()
+        .filter_map(|(i, (before, after))| {
+            let delta = after - before;
+            if delta <= 0 {
+                return None;
+            }
+            Some(NativeTransfer {
+                lamports: delta,
+                destination: account_keys.get(i)?.clone(),
+            })
+        })
+        .collect()
+}
+
+fn push_unique(out: &mut Vec<String>, value: String) {
+    if !out.contains(&value) {
+        out.push(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SIGNATURE: &str = "5dxAVh64ycF3iAtJ7wUxRuLT9bf5g5XrhfNsb5uiyP7FwKEBZge7ELxfAEeMkd8j215ymUtWrYvGzRRHWzKHLX7X";
+    const FIXTURE: &str = include_str!(
+        "../../fixtures/transactions/5dxAVh64ycF3iAtJ7wUxRuLT9bf5g5XrhfNsb5uiyP7FwKEBZge7ELxfAEeMkd8j215ymUtWrYvGzRRHWzKHLX7X.json"
+    );
+
+    #[test]
+    fn parses_a_json_parsed_token_transfer() {
+        let tx = parse_transaction(SIGNATURE, serde_json::from_str(FIXTURE).unwrap());
+        assert_eq!(tx.slot, Some(312345700));
+        assert_eq!(tx.timestamp, Some(1767225700));
+        assert_eq!(tx.memo_strings, vec!["INV-2026-01".to_string()]);
+        assert_eq!(tx.token_transfers.len(), 1);
+        let transfer = &tx.token_transfers[0];
+        assert_eq!(transfer.mint, "4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU");
+        assert_eq!(transfer.amount, 2_500_000);
+        assert_eq!(transfer.destination, "4ajKRcMqWNAd3k2rQRSA5FVCUrk2g9p3b6tZTZ2ZBCo8");
+        assert_eq!(transfer.owner.as_deref(), Some("9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin"));
+        // The fee payer only lost lamports.
+        assert!(tx.native_transfers.is_empty());
+        assert_eq!(tx.account_keys.len(), 6);
+    }
+
+    #[test]
+    fn parses_plain_json_with_lookup_tables_and_memo_data() {
+        let memo = bs58::encode("paylink:abc").into_string();
+        let result = json!({
+            "slot": 7,
+            "transaction": {"message": {
+                "accountKeys": ["payer", "merchant", "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr"],
+                "instructions": [{"programIdIndex": 2, "accounts": [], "data": memo}],
+            }},
+            "meta": {
+                "err": null,
+                "preBalances": [5_000_000, 100, 1, 0],
+                "postBalances": [3_995_000, 1_000_100, 1, 0],
+                "loadedAddresses": {"writable": ["loaded"], "readonly": []},
+                "preTokenBalances": [],
+                "postTokenBalances": [{
+                    "accountIndex": 3, "mint": "mint", "owner": "merchant",
+                    "uiTokenAmount": {"amount": "42", "decimals": 0}
+                }],
+                "logMessages": ["Program log: Memo (len 11): \"paylink:abc\""],
+            },
+        });
+        let tx = parse_transaction("sig", result);
+        assert_eq!(tx.account_keys, vec!["payer", "merchant", "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr", "loaded"]);
+        assert_eq!(tx.memo_strings, vec!["paylink:abc".to_string()]);
+        assert_eq!(tx.native_transfers.len(), 1);
+        assert_eq!(tx.native_transfers[0].destination, "merchant");
+        assert_eq!(tx.native_transfers[0].lamports, 1_000_000);
+        assert_eq!(tx.token_transfers.len(), 1);
+        assert_eq!(tx.token_transfers[0].destination, "loaded");
+        assert_eq!(tx.token_transfers[0].amount, 42);
+    }
+
+    #[test]
+    fn unescapes_logged_memos() {
+        assert_eq!(
+            log_memo(r#"Program log: Memo (len 9): "a \"b\" \\c""#).as_deref(),
+            Some(r#"a "b" \c"#)
+        );
+        assert_eq!(log_memo("Program log: Instruction: Transfer"), None);
+    }
+}
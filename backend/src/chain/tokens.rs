@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::Deserialize;
+
+use crate::error::AppError;
+use crate::solana;
+use crate::util::amount::base_units_to_ui;
+
+use super::ChainProvider;
+
+#[derive(Debug, Clone)]
+pub struct TokenInfo {
+    /// On-chain mint address; for native SOL this is the wrapped SOL mint.
+    pub address: String,
+    /// Set for SOL, USDC and tokens listed in the registry file.
+    pub symbol: Option<String>,
+    pub decimals: u8,
+    /// Owning token program (SPL Token or Token-2022).
+    pub token_program: String,
+    /// Paid as a plain lamport transfer rather than an SPL token transfer.
+    pub native: bool,
+}
+
+impl TokenInfo {
+    /// `amount` base units as a decimal string in whole tokens.
+    pub fn ui(&self, amount: i64) -> String {
+        base_units_to_ui(amount, self.decimals)
+    }
+}
+
+/// An entry of the `TOKEN_REGISTRY_PATH` file. Entries for another cluster
+/// are skipped; `cluster` defaults to any.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RegistryEntry {
+    symbol: String,
+    mint: String,
+    decimals: u8,
+    token_program: Option<String>,
+    cluster: Option<String>,
+}
+
+/// Known tokens for the configured cluster: SOL, USDC and those listed in the
+/// registry file. Any other mint address is looked up with `getAccountInfo`
+/// on first use and cached, without a symbol.
+pub struct TokenRegistry {
+    chain: Arc<dyn ChainProvider>,
+    /// Keyed by mint address.
+    known: HashMap<String, TokenInfo>,
+    /// Upper-case symbol to mint address.
+    symbols: HashMap<String, String>,
+    cache: Mutex<HashMap<String, TokenInfo>>,
+}
+
+impl TokenRegistry {
+    pub fn load(chain: Arc<dyn ChainProvider>, cluster: &str, path: &str) -> Self {
+        let mut registry = Self {
+            chain,
+            known: HashMap::new(),
+            symbols: HashMap::new(),
+            cache: Mutex::new(HashMap::new()),
+        };
+        registry.add(TokenInfo {
+            address: solana::WRAPPED_SOL_MINT.to_string(),
+            symbol: Some("SOL".to_string()),
+            decimals: 9,
+            token_program: solana::tx::TOKEN_PROGRAM_ID.to_string(),
+            native: true,
+        });
+        registry.add(TokenInfo {
+            address: solana::usdc_mint(cluster).to_string(),
+            symbol: Some("USDC".to_string()),
+            decimals: 6,
+            token_program: solana::tx::TOKEN_PROGRAM_ID.to_string(),
+            native: false,
+        });
+
+        let entries = match std::fs::read_to_string(path) {
+            Ok(text) => match serde_json::from_str::<Vec<RegistryEntry>>(&text) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    eprintln!("⚠️  token registry {} is invalid: {}", path, err);
+                    Vec::new()
+                }
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => {
+                eprintln!("⚠️  token registry {} could not be read: {}", path, err);
+                Vec::new()
+            }
+        };
+        for entry in entries {
+            if entry.cluster.as_deref().is_some_and(|c| c != cluster) {
+                continue;
+            }
+            let token_program = entry
+                .token_program
+                .unwrap_or_else(|| solana::tx::TOKEN_PROGRAM_ID.to_string());
+            if !solana::is_pubkey(&entry.mint) || !is_token_program(&token_program) {
+                eprintln!("⚠️  skipping token registry entry {}", entry.symbol);
+                continue;
+            }
+            registry.add(TokenInfo {
+                address: entry.mint,
+                symbol: Some(entry.symbol),
+                decimals: entry.decimals,
+                token_program,
+                native: false,
+            });
+        }
+        println!("   Tokens: {} known", registry.known.len());
+        registry
+    }
+
+    fn add(&mut self, token: TokenInfo) {
+        if let Some(symbol) = &token.symbol {
+            self.symbols
+                .insert(symbol.to_ascii_uppercase(), token.address.clone());
+        }
+        self.known.insert(token.address.clone(), token);
+    }
+
+    /// Resolves a symbol ("SOL", "usdc") or a mint address. Unknown
+    /// addresses must be an initialized mint owned by a token program.
+    pub async fn resolve(&self, mint: &str) -> Result<TokenInfo, AppError> {
+        let address = self
+            .symbols
+            .get(&mint.trim().to_ascii_uppercase())
+            .map(String::as_str)
+            .unwrap_or(mint);
+        if let Some(token) = self.known.get(address) {
+            return Ok(token.clone());
+        }
+        if !solana::is_pubkey(address) {
+            return Err(AppError::BadRequest(format!("unknown mint {}", mint)));
+        }
+
+        let cached = self.cache.lock().unwrap().get(address).cloned();
+        match cached {
+            Some(token) => Ok(token),
+            None => {
+                let token = self.fetch_mint(address).await?;
+                self.cache
+                    .lock()
+                    .unwrap()
+                    .insert(address.to_string(), token.clone());
+                Ok(token)
+            }
+        }
+    }
+
+    async fn fetch_mint(&self, address: &str) -> Result<TokenInfo, AppError> {
+        let info = self
+            .chain
+            .account_info(address)
+            .await?
+            .ok_or_else(|| AppError::BadRequest(format!("mint {} not found", address)))?;
+        let parsed = info.pointer("/data/parsed");
+        if parsed.and_then(|p| p.get("type")).and_then(|v| v.as_str()) != Some("mint") {
+            return Err(AppError::BadRequest(format!("{} is not a token mint", address)));
+        }
+        if parsed.and_then(|p| p.pointer("/info/isInitialized")).and_then(|v| v.as_bool()) == Some(false) {
+            return Err(AppError::BadRequest(format!("mint {} is not initialized", address)));
+        }
+        let decimals = parsed
+            .and_then(|p| p.pointer("/info/decimals"))
+            .and_then(|v| v.as_u64())
+            .map(|d| d as u8)
+            .ok_or_else(|| AppError::Other(format!("mint {} has no decimals", address)))?;
+        let token_program = info
+            .get("owner")
+            .and_then(|v| v.as_str())
+            .unwrap_or(solana::tx::TOKEN_PROGRAM_ID);
+        if !is_token_program(token_program) {
+            return Err(AppError::BadRequest(format!(
+                "{} is not owned by a token program",
+                address
+            )));
+        }
+        Ok(TokenInfo {
+            address: address.to_string(),
+            symbol: None,
+            decimals,
+            token_program: token_program.to_string(),
+            native: false,
+        })
+    }
+}
+
+fn is_token_program(program_id: &str) -> bool {
+    program_id == solana::tx::TOKEN_PROGRAM_ID || program_id == solana::tx::TOKEN_2022_PROGRAM_ID
+}
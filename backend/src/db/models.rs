@@ -1,5 +1,6 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Months, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -11,11 +12,546 @@ pub struct PayLink {
     pub mint: String,
     pub expires_at: DateTime<Utc>,
     pub invoice_ref: Option<String>,
-    pub status: String,
+    pub status: PaylinkStatus,
+    /// Sum of the recorded payments, in base units of `mint`.
+    pub amount_received: i64,
     pub created_at: DateTime<Utc>,
     pub paid_signature: Option<String>,
     pub paid_slot: Option<i64>,
     pub privacy_rail: String,
+    pub reference: Option<String>,
+    pub memo_policy: Json<MemoPolicy>,
+    pub receipt_fields_policy: Json<ReceiptFieldsPolicy>,
+    pub amount_tolerance: Json<AmountTolerance>,
+    /// `memo_policy` rendered at creation; required on payment when set.
+    pub expected_memo: Option<String>,
+    #[serde(skip_serializing)]
+    pub merchant_id: Option<Uuid>,
+    /// Accepts any number of payments of `expected_amount` (or of any amount
+    /// when `open_amount`) until a cap is reached or it expires.
+    pub multi_use: bool,
+    pub max_uses: Option<i32>,
+    /// Cap on `amount_received` for multi-use links.
+    pub max_total: Option<i64>,
+    /// The payer chooses the amount, within `min_amount`..=`max_amount`.
+    /// `expected_amount` is 0.
+    pub open_amount: bool,
+    pub min_amount: Option<i64>,
+    pub max_amount: Option<i64>,
+    /// Number of recorded payments.
+    pub use_count: i32,
+    /// Set on paylinks issued for a subscription billing cycle.
+    pub subscription_id: Option<Uuid>,
+    /// The amount is the total of an `Invoice` stored for this paylink.
+    pub has_invoice: bool,
+    /// Set on fiat-priced paylinks; `fiat_amount` is in cents.
+    pub fiat_currency: Option<FiatCurrency>,
+    pub fiat_amount: Option<i64>,
+    /// The quote `expected_amount` was computed from.
+    pub price_quote: Option<Json<PriceQuote>>,
+    /// Set when the quote is taken again as payers open the link, rather
+    /// than locked at creation.
+    pub quote_ttl_secs: Option<i32>,
+    /// Token details and the amounts above in whole tokens; filled in for
+    /// API responses, not stored.
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<TokenAmounts>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenAmounts {
+    pub symbol: Option<String>,
+    pub decimals: u8,
+    pub token_program: String,
+    pub expected_amount: String,
+    pub amount_received: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_amount: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_amount: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_total: Option<String>,
+}
+
+impl PayLink {
+    /// Multi-use and open-amount links treat each payment as a sale of its
+    /// own, with its own receipt, instead of summing towards one amount.
+    pub fn per_payment(&self) -> bool {
+        self.multi_use || self.open_amount
+    }
+
+    /// Amount still owed; never negative. For a fixed multi-use link this is
+    /// the price of one use, and 0 when the payer picks the amount.
+    pub fn remaining_amount(&self) -> i64 {
+        if self.open_amount {
+            0
+        } else if self.multi_use {
+            self.expected_amount
+        } else {
+            (self.expected_amount - self.amount_received).max(0)
+        }
+    }
+
+    /// Checks one transaction's amount before it is applied. Single-use
+    /// fixed links take any amount, since payments add up.
+    pub fn check_payment_amount(&self, amount: i64) -> Result<(), String> {
+        if self.open_amount {
+            if self.min_amount.is_some_and(|min| amount < min)
+                || self.max_amount.is_some_and(|max| amount > max)
+            {
+                return Err(format!("Amount {} outside the allowed range", amount));
+            }
+        } else if self.multi_use
+            && amount < self.expected_amount - self.amount_tolerance.allowance(self.expected_amount)
+        {
+            return Err(format!("Amount {} is less than the {} per use", amount, self.expected_amount));
+        }
+        Ok(())
+    }
+
+    fn use_cap(&self) -> Option<i32> {
+        match (self.multi_use, self.open_amount) {
+            (true, _) => self.max_uses,
+            (false, true) => Some(1),
+            (false, false) => None,
+        }
+    }
+
+    /// Whether the amounts received so far settle the paylink, or for
+    /// per-payment links whether a use or total cap has been reached.
+    pub fn is_settled(&self) -> bool {
+        if self.per_payment() {
+            return self.use_cap().is_some_and(|cap| self.use_count >= cap)
+                || self.max_total.is_some_and(|cap| self.amount_received >= cap);
+        }
+        self.remaining_amount() <= self.amount_tolerance.allowance(self.expected_amount)
+    }
+
+    /// The part of a just-recorded payment of `amount` that the link did not
+    /// ask for. Call on the paylink as updated by that payment. A per-payment
+    /// link takes no payments once it has expired, so all of it is excess.
+    pub fn excess_of(&self, amount: i64) -> i64 {
+        if !self.per_payment() {
+            return amount.min(self.amount_delta().max(0));
+        }
+        if self.status == PaylinkStatus::Expired
+            || Utc::now() > self.expires_at
+            || self.use_cap().is_some_and(|cap| self.use_count > cap)
+        {
+            return amount;
+        }
+        let mut excess = if self.open_amount {
+            0
+        } else {
+            (amount - self.expected_amount).max(0)
+        };
+        if let Some(cap) = self.max_total {
+            excess = excess.max(amount.min((self.amount_received - cap).max(0)));
+        }
+        excess
+    }
+
+    /// Received minus expected; positive when overpaid.
+    pub fn amount_delta(&self) -> i64 {
+        self.amount_received - self.expected_amount
+    }
+
+    /// Whether the fiat quote should be taken again before a payer is shown
+    /// the amount: the quote was taken on open, has lapsed, and no payment
+    /// has been applied against it yet.
+    pub fn needs_requote(&self, now: DateTime<Utc>) -> bool {
+        let lapsed = self
+            .price_quote
+            .as_ref()
+            .and_then(|q| q.expires_at)
+            .is_some_and(|at| at <= now);
+        lapsed
+            && self.status == PaylinkStatus::Pending
+            && (self.multi_use || self.amount_received == 0)
+    }
+}
+
+/// Stored as text; the status migrations enforce the same transitions with a
+/// check constraint and trigger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum PaylinkStatus {
+    Pending,
+    PartiallyPaid,
+    Paid,
+    Expired,
+    Cancelled,
+}
+
+impl PaylinkStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::PartiallyPaid => "partially_paid",
+            Self::Paid => "paid",
+            Self::Expired => "expired",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    /// Paid and cancelled are terminal. An expired paylink can still be paid
+    /// by a late transfer.
+    pub fn can_transition_to(&self, to: PaylinkStatus) -> bool {
+        matches!(
+            (self, to),
+            (
+                Self::Pending,
+                Self::PartiallyPaid | Self::Paid | Self::Expired | Self::Cancelled
+            ) | (Self::PartiallyPaid, Self::Paid | Self::Expired | Self::Cancelled)
+                | (Self::Expired, Self::Paid)
+        )
+    }
+
+    /// The states `to` can be reached from.
+    pub fn sources(to: PaylinkStatus) -> Vec<String> {
+        [Self::Pending, Self::PartiallyPaid, Self::Paid, Self::Expired, Self::Cancelled]
+            .into_iter()
+            .filter(|from| from.can_transition_to(to))
+            .map(|from| from.as_str().to_string())
+            .collect()
+    }
+
+    /// Activity event recorded when a paylink enters this state.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            Self::Pending => "PAYLINK_CREATED",
+            Self::PartiallyPaid => "PAYLINK_PARTIALLY_PAID",
+            Self::Paid => "PAYLINK_MARKED_PAID",
+            Self::Expired => "PAYLINK_EXPIRED",
+            Self::Cancelled => "PAYLINK_CANCELLED",
+        }
+    }
+}
+
+impl std::fmt::Display for PaylinkStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoPolicy {
+    pub enabled: bool,
+    pub template: String,
+}
+
+impl MemoPolicy {
+    /// Renders the template for a paylink. Supported placeholders are `{id}`,
+    /// `{invoiceRef}` and `{reference}`.
+    pub fn render(&self, paylink: &PayLink) -> Option<String> {
+        if !self.enabled || self.template.trim().is_empty() {
+            return None;
+        }
+        Some(
+            self.template
+                .replace("{id}", &paylink.id.to_string())
+                .replace("{invoiceRef}", paylink.invoice_ref.as_deref().unwrap_or_default())
+                .replace("{reference}", paylink.reference.as_deref().unwrap_or_default()),
+        )
+    }
+}
+
+/// How far a payment may differ from the amount owed and still count as
+/// paying it. `value` is in base units for `absolute` and in hundredths of a
+/// percent of the expected amount for `bps`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AmountTolerance {
+    pub kind: ToleranceKind,
+    pub value: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToleranceKind {
+    Absolute,
+    Bps,
+}
+
+impl Default for AmountTolerance {
+    fn default() -> Self {
+        Self {
+            kind: ToleranceKind::Absolute,
+            value: 0,
+        }
+    }
+}
+
+impl AmountTolerance {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.value < 0 {
+            return Err("amountTolerance.value must not be negative");
+        }
+        if self.kind == ToleranceKind::Bps && self.value > 10_000 {
+            return Err("amountTolerance.value must be at most 10000 bps");
+        }
+        Ok(())
+    }
+
+    /// The tolerance in base units for a paylink of `expected` base units.
+    pub fn allowance(&self, expected: i64) -> i64 {
+        match self.kind {
+            ToleranceKind::Absolute => self.value,
+            ToleranceKind::Bps => (i128::from(expected) * i128::from(self.value) / 10_000) as i64,
+        }
+    }
+}
+
+/// Which receipt facts the merchant allows a payer to disclose in a proof.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptFieldsPolicy {
+    pub merchant: bool,
+    pub amount: bool,
+    pub token: bool,
+    pub time_window: bool,
+    pub invoice_ref: bool,
+    pub paylink_id: bool,
+    /// The invoice breakdown: line items, discounts, taxes and totals.
+    #[serde(default)]
+    pub invoice: bool,
+}
+
+impl ReceiptFieldsPolicy {
+    /// Fields set in `requested` that this policy does not allow.
+    pub fn disallowed(&self, requested: &ReceiptFieldsPolicy) -> Vec<&'static str> {
+        [
+            ("merchant", requested.merchant, self.merchant),
+            ("amount", requested.amount, self.amount),
+            ("token", requested.token, self.token),
+            ("timeWindow", requested.time_window, self.time_window),
+            ("invoiceRef", requested.invoice_ref, self.invoice_ref),
+            ("paylinkId", requested.paylink_id, self.paylink_id),
+            ("invoice", requested.invoice, self.invoice),
+        ]
+        .into_iter()
+        .filter(|(_, wanted, allowed)| *wanted && !*allowed)
+        .map(|(name, _, _)| name)
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "UPPERCASE")]
+#[sqlx(type_name = "text", rename_all = "UPPERCASE")]
+pub enum FiatCurrency {
+    Usd,
+    Eur,
+}
+
+impl FiatCurrency {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Usd => "USD",
+            Self::Eur => "EUR",
+        }
+    }
+
+    /// Both supported currencies are priced in cents.
+    pub fn decimals(&self) -> u8 {
+        2
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_uppercase().as_str() {
+            "USD" => Some(Self::Usd),
+            "EUR" => Some(Self::Eur),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for FiatCurrency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A fiat price converted to a token amount. `rate` is the price of one
+/// whole token in the fiat currency, as reported by `source`; `amount` is in
+/// base units. `expires_at` is unset for quotes locked at creation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceQuote {
+    pub source: String,
+    pub asset: String,
+    pub currency: FiatCurrency,
+    pub fiat_amount: i64,
+    pub rate: String,
+    pub amount: i64,
+    pub quoted_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Itemised bill behind a paylink; `total` is the paylink's expected amount.
+/// All amounts are in base units of the paylink mint.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Invoice {
+    pub id: Uuid,
+    pub paylink_id: Uuid,
+    pub customer: Json<Customer>,
+    pub line_items: Json<Vec<LineItem>>,
+    pub discounts: Json<Vec<Discount>>,
+    pub taxes: Json<Vec<TaxLine>>,
+    pub subtotal: i64,
+    pub discount_total: i64,
+    pub tax_total: i64,
+    pub total: i64,
+    pub due_date: Option<DateTime<Utc>>,
+    pub notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Customer {
+    pub name: String,
+    pub email: Option<String>,
+    pub address: Option<String>,
+    pub tax_id: Option<String>,
+}
+
+/// `amount` is computed as `quantity * unit_price`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineItem {
+    pub description: String,
+    pub quantity: i64,
+    pub unit_price: i64,
+    #[serde(default)]
+    pub amount: i64,
+}
+
+/// Taken off the subtotal: `value` base units, or `value` basis points of the
+/// subtotal. `amount` is computed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Discount {
+    pub description: String,
+    pub kind: DiscountKind,
+    pub value: i64,
+    #[serde(default)]
+    pub amount: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscountKind {
+    /// A fixed amount in base units.
+    Absolute,
+    /// Basis points of the subtotal; at most 10000.
+    Bps,
+}
+
+impl Discount {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.value < 0 {
+            return Err("discount value must not be negative");
+        }
+        if self.kind == DiscountKind::Bps && self.value > 10_000 {
+            return Err("discount value must be at most 10000 bps");
+        }
+        Ok(())
+    }
+
+    /// The discount on `subtotal`, rounded down.
+    pub fn amount_of(&self, subtotal: i64) -> i64 {
+        match self.kind {
+            DiscountKind::Absolute => self.value,
+            DiscountKind::Bps => (i128::from(subtotal) * i128::from(self.value) / 10_000) as i64,
+        }
+    }
+}
+
+/// Charged on the subtotal after discounts, rounded half up. `amount` is
+/// computed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaxLine {
+    pub name: String,
+    pub rate_bps: i64,
+    #[serde(default)]
+    pub amount: i64,
+}
+
+const MAX_INVOICE_LINES: usize = 100;
+
+impl Invoice {
+    /// Fills in the computed amount of every line, discount and tax, and the
+    /// totals. Discounts never take the subtotal below zero.
+    pub fn compute(&mut self) -> Result<(), &'static str> {
+        let lines = self.line_items.len() + self.discounts.len() + self.taxes.len();
+        if self.line_items.is_empty() {
+            return Err("invoice needs at least one line item");
+        }
+        if lines > MAX_INVOICE_LINES {
+            return Err("invoice has too many lines");
+        }
+        const OVERFLOW: &str = "invoice amounts overflow";
+
+        let mut subtotal: i64 = 0;
+        for item in self.line_items.iter_mut() {
+            if item.description.trim().is_empty() {
+                return Err("line item description is required");
+            }
+            if item.quantity <= 0 || item.unit_price < 0 {
+                return Err("line item quantity must be positive and unitPrice not negative");
+            }
+            item.amount = item.quantity.checked_mul(item.unit_price).ok_or(OVERFLOW)?;
+            subtotal = subtotal.checked_add(item.amount).ok_or(OVERFLOW)?;
+        }
+
+        let mut discount_total: i64 = 0;
+        for discount in self.discounts.iter_mut() {
+            discount.validate()?;
+            discount.amount = discount.amount_of(subtotal).min(subtotal - discount_total);
+            discount_total += discount.amount;
+        }
+
+        let taxable = subtotal - discount_total;
+        let mut tax_total: i64 = 0;
+        for tax in self.taxes.iter_mut() {
+            if !(0..=10_000).contains(&tax.rate_bps) {
+                return Err("tax rateBps must be between 0 and 10000");
+            }
+            tax.amount = ((i128::from(taxable) * i128::from(tax.rate_bps) + 5_000) / 10_000) as i64;
+            tax_total = tax_total.checked_add(tax.amount).ok_or(OVERFLOW)?;
+        }
+
+        self.subtotal = subtotal;
+        self.discount_total = discount_total;
+        self.tax_total = tax_total;
+        self.total = taxable.checked_add(tax_total).ok_or(OVERFLOW)?;
+        if self.total <= 0 {
+            return Err("invoice total must be positive");
+        }
+        Ok(())
+    }
+
+    /// The facts a receipt records about the invoice. Customer details and
+    /// notes are left out.
+    pub fn breakdown(&self) -> serde_json::Value {
+        serde_json::json!({
+            "lineItems": self.line_items,
+            "discounts": self.discounts,
+            "taxes": self.taxes,
+            "subtotal": self.subtotal,
+            "discountTotal": self.discount_total,
+            "taxTotal": self.tax_total,
+            "total": self.total,
+            "dueDate": self.due_date,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -27,6 +563,180 @@ pub struct Receipt {
     pub issued_at: DateTime<Utc>,
     pub facts: serde_json::Value,
     pub rail: String,
+    /// Issued by `/paylinks/:id/simulate` rather than an on-chain payment.
+    pub simulated: bool,
+    /// The payment this receipt is for, on links with a receipt per payment.
+    pub payment_id: Option<Uuid>,
+    pub subscription_id: Option<Uuid>,
+}
+
+/// One transaction's contribution to a paylink.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Payment {
+    pub id: Uuid,
+    pub paylink_id: Uuid,
+    pub signature: String,
+    pub amount: i64,
+    pub mint: String,
+    pub slot: Option<i64>,
+    pub block_time: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    /// Part of `amount` above what the paylink asked for, owed back to the
+    /// payer.
+    pub refund_due: i64,
+}
+
+/// Bills `amount` every `interval_count` `interval_unit`s, counted from
+/// `anchor_at`. Cycle `n` starts at `anchor_at + n * interval`; cycles that
+/// start before `trial_ends_at` are not billed.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Subscription {
+    pub id: Uuid,
+    #[serde(skip_serializing)]
+    pub merchant_id: Uuid,
+    pub merchant_pubkey: String,
+    pub amount: i64,
+    pub mint: String,
+    pub interval_unit: IntervalUnit,
+    pub interval_count: i32,
+    pub anchor_at: DateTime<Utc>,
+    pub trial_ends_at: Option<DateTime<Utc>>,
+    pub status: SubscriptionStatus,
+    /// Used as the invoice reference of each cycle's paylink, suffixed with
+    /// the cycle number.
+    pub customer_ref: Option<String>,
+    pub memo_policy: Json<MemoPolicy>,
+    pub receipt_fields_policy: Json<ReceiptFieldsPolicy>,
+    pub amount_tolerance: Json<AmountTolerance>,
+    pub next_cycle_index: i32,
+    pub next_cycle_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+}
+
+/// Upper bound on the cycles walked to find the first billable one.
+const MAX_CYCLE_SEARCH: i32 = 100_000;
+
+impl Subscription {
+    pub fn cycle_start(&self, index: i32) -> Option<DateTime<Utc>> {
+        self.interval_unit
+            .add(self.anchor_at, i64::from(self.interval_count) * i64::from(index))
+    }
+
+    /// The first cycle to bill: the first one starting at or after the end
+    /// of the trial, or else the one in progress at `now` (cycle 0 when the
+    /// anchor is still ahead).
+    pub fn first_cycle_index(&self, now: DateTime<Utc>) -> Option<i32> {
+        let mut index = 0;
+        if let Some(trial_ends_at) = self.trial_ends_at {
+            while self.cycle_start(index)? < trial_ends_at {
+                index = (index < MAX_CYCLE_SEARCH).then_some(index + 1)?;
+            }
+            return Some(index);
+        }
+        while self.cycle_start(index + 1)? <= now {
+            index = (index < MAX_CYCLE_SEARCH).then_some(index + 1)?;
+        }
+        Some(index)
+    }
+
+    /// The invoice reference for cycle `index`, numbered from 1.
+    pub fn cycle_invoice_ref(&self, index: i32) -> Option<String> {
+        self.customer_ref
+            .as_ref()
+            .map(|customer_ref| format!("{}-{}", customer_ref, index + 1))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum IntervalUnit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl IntervalUnit {
+    /// `at` moved forward by `count` units. Months and years keep the day of
+    /// the month, clamped to the month's last day.
+    pub fn add(&self, at: DateTime<Utc>, count: i64) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Day => at.checked_add_signed(Duration::try_days(count)?),
+            Self::Week => at.checked_add_signed(Duration::try_weeks(count)?),
+            Self::Month => at.checked_add_months(Months::new(u32::try_from(count).ok()?)),
+            Self::Year => at.checked_add_months(Months::new(u32::try_from(count.checked_mul(12)?).ok()?)),
+        }
+    }
+}
+
+/// `past_due` while any billed cycle is missed; paying the missed cycles
+/// (late) makes the subscription `active` again. Cancelled is final.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum SubscriptionStatus {
+    Trialing,
+    Active,
+    PastDue,
+    Cancelled,
+}
+
+impl SubscriptionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Trialing => "trialing",
+            Self::Active => "active",
+            Self::PastDue => "past_due",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl std::fmt::Display for SubscriptionStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One billing period and the paylink issued for it.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionCycle {
+    pub id: Uuid,
+    pub subscription_id: Uuid,
+    pub cycle_index: i32,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub paylink_id: Uuid,
+    pub status: CycleStatus,
+    pub created_at: DateTime<Utc>,
+    pub settled_at: Option<DateTime<Utc>>,
+}
+
+/// Follows the cycle's paylink: `paid` when it is paid, `missed` when it
+/// expires unpaid and `cancelled` when it is cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum CycleStatus {
+    Open,
+    Paid,
+    Missed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionEvent {
+    pub id: i64,
+    pub subscription_id: Uuid,
+    pub r#type: String,
+    pub at: DateTime<Utc>,
+    pub detail: serde_json::Value,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -38,3 +748,529 @@ pub struct ActivityEvent {
     pub at: DateTime<Utc>,
     pub detail: serde_json::Value,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookEvent {
+    pub id: i64,
+    pub signature: String,
+    pub received_at: DateTime<Utc>,
+    pub payload: serde_json::Value,
+    /// Whether the delivery passed webhook authentication.
+    pub authenticated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookJob {
+    pub id: i64,
+    pub signature: String,
+    pub status: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub locked_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Merchant {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub webhook_url: Option<String>,
+    #[serde(skip_serializing)]
+    pub webhook_secret: Option<String>,
+}
+
+/// Activity event types that are also delivered to the merchant's webhook.
+pub const MERCHANT_WEBHOOK_EVENTS: &[&str] = &[
+    "PAYLINK_CREATED",
+    "PAYLINK_PARTIALLY_PAID",
+    "PAYMENT_OVERPAID",
+    "PAYLINK_MARKED_PAID",
+    "RECEIPT_ISSUED",
+    "TX_VERIFIED_MISMATCH",
+    "PAYLINK_EXPIRED",
+    "PAYLINK_CANCELLED",
+    "SUBSCRIPTION_CREATED",
+    "SUBSCRIPTION_TRIAL_ENDED",
+    "SUBSCRIPTION_CYCLE_ISSUED",
+    "SUBSCRIPTION_CYCLE_PAID",
+    "SUBSCRIPTION_CYCLE_MISSED",
+    "SUBSCRIPTION_CANCELLED",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct MerchantWebhookDelivery {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub paylink_id: Option<Uuid>,
+    pub subscription_id: Option<Uuid>,
+    pub event_type: String,
+    pub url: String,
+    pub payload: serde_json::Value,
+    /// `pending`, `sending`, `delivered` or `dead`.
+    pub status: String,
+    pub attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub locked_at: Option<DateTime<Utc>>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub prefix: String,
+    #[serde(skip_serializing)]
+    pub key_hash: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthNonce {
+    pub nonce: String,
+    pub pubkey: String,
+    pub message: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletSession {
+    pub id: Uuid,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub pubkey: String,
+    pub merchant_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// Those of the API key that linked the wallet.
+    pub scopes: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [PaylinkStatus; 5] = [
+        PaylinkStatus::Pending,
+        PaylinkStatus::PartiallyPaid,
+        PaylinkStatus::Paid,
+        PaylinkStatus::Expired,
+        PaylinkStatus::Cancelled,
+    ];
+
+    #[test]
+    fn allows_only_the_documented_transitions() {
+        use PaylinkStatus::*;
+        let allowed = [
+            (Pending, PartiallyPaid),
+            (Pending, Paid),
+            (Pending, Expired),
+            (Pending, Cancelled),
+            (PartiallyPaid, Paid),
+            (PartiallyPaid, Expired),
+            (PartiallyPaid, Cancelled),
+            (Expired, Paid),
+        ];
+        for from in ALL {
+            for to in ALL {
+                assert_eq!(
+                    from.can_transition_to(to),
+                    allowed.contains(&(from, to)),
+                    "{} -> {}",
+                    from,
+                    to
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn paid_and_cancelled_are_terminal() {
+        for to in ALL {
+            assert!(!PaylinkStatus::Paid.can_transition_to(to));
+            assert!(!PaylinkStatus::Cancelled.can_transition_to(to));
+        }
+    }
+
+    #[test]
+    fn lists_the_sources_of_a_state() {
+        assert_eq!(PaylinkStatus::sources(PaylinkStatus::Paid), ["pending", "partially_paid", "expired"]);
+        assert_eq!(PaylinkStatus::sources(PaylinkStatus::PartiallyPaid), ["pending"]);
+        assert!(PaylinkStatus::sources(PaylinkStatus::Pending).is_empty());
+    }
+
+    fn paylink(expected_amount: i64) -> PayLink {
+        crate::test_support::paylink(crate::test_support::FIXTURE_MERCHANT, "SOL", expected_amount)
+    }
+
+    fn bps(value: i64) -> Json<AmountTolerance> {
+        Json(AmountTolerance {
+            kind: ToleranceKind::Bps,
+            value,
+        })
+    }
+
+    #[test]
+    fn tolerance_allowance_is_absolute_or_a_share_of_expected() {
+        let absolute = AmountTolerance {
+            kind: ToleranceKind::Absolute,
+            value: 25,
+        };
+        assert_eq!(absolute.allowance(1_000_000), 25);
+        assert_eq!(bps(50).allowance(1_000_000), 5_000);
+        assert_eq!(bps(1).allowance(9_999), 0);
+        assert_eq!(bps(10_000).allowance(i64::MAX), i64::MAX);
+        assert!(bps(10_001).validate().is_err());
+        assert!(AmountTolerance { kind: ToleranceKind::Absolute, value: -1 }.validate().is_err());
+    }
+
+    #[test]
+    fn remaining_amount_depends_on_the_link_kind() {
+        let mut single = paylink(1_000);
+        single.amount_received = 400;
+        assert_eq!(single.remaining_amount(), 600);
+        single.amount_received = 1_500;
+        assert_eq!(single.remaining_amount(), 0);
+
+        let mut multi = paylink(1_000);
+        multi.multi_use = true;
+        multi.amount_received = 5_000;
+        assert_eq!(multi.remaining_amount(), 1_000);
+
+        let mut open = paylink(0);
+        open.open_amount = true;
+        assert_eq!(open.remaining_amount(), 0);
+    }
+
+    #[test]
+    fn checks_payment_amounts_against_the_link_kind() {
+        let single = paylink(1_000);
+        assert!(single.check_payment_amount(1).is_ok());
+
+        let mut multi = paylink(1_000);
+        multi.multi_use = true;
+        multi.amount_tolerance = bps(100);
+        assert!(multi.check_payment_amount(990).is_ok());
+        assert!(multi.check_payment_amount(989).is_err());
+
+        let mut open = paylink(0);
+        open.open_amount = true;
+        open.min_amount = Some(100);
+        open.max_amount = Some(500);
+        assert!(open.check_payment_amount(100).is_ok());
+        assert!(open.check_payment_amount(500).is_ok());
+        assert!(open.check_payment_amount(99).is_err());
+        assert!(open.check_payment_amount(501).is_err());
+    }
+
+    #[test]
+    fn settles_within_tolerance_or_at_a_cap() {
+        let mut single = paylink(1_000);
+        single.amount_tolerance = bps(100);
+        single.amount_received = 989;
+        assert!(!single.is_settled());
+        single.amount_received = 990;
+        assert!(single.is_settled());
+
+        let mut multi = paylink(1_000);
+        multi.multi_use = true;
+        multi.use_count = 5;
+        assert!(!multi.is_settled());
+        multi.max_uses = Some(5);
+        assert!(multi.is_settled());
+        multi.max_uses = None;
+        multi.max_total = Some(3_000);
+        multi.amount_received = 3_000;
+        assert!(multi.is_settled());
+
+        let mut open = paylink(0);
+        open.open_amount = true;
+        assert!(!open.is_settled());
+        open.use_count = 1;
+        assert!(open.is_settled());
+    }
+
+    #[test]
+    fn excess_is_what_the_link_did_not_ask_for() {
+        // Single-use: the amount beyond expected, capped at this payment.
+        let mut single = paylink(1_000);
+        single.amount_received = 700;
+        assert_eq!(single.excess_of(700), 0);
+        single.amount_received = 1_200;
+        assert_eq!(single.excess_of(500), 200);
+        single.amount_received = 1_500;
+        assert_eq!(single.excess_of(300), 300);
+
+        // Fixed multi-use: each payment beyond the price, and all of a
+        // payment past the use cap.
+        let mut multi = paylink(1_000);
+        multi.multi_use = true;
+        multi.max_uses = Some(2);
+        multi.use_count = 2;
+        assert_eq!(multi.excess_of(1_000), 0);
+        assert_eq!(multi.excess_of(1_300), 300);
+        multi.use_count = 3;
+        assert_eq!(multi.excess_of(1_000), 1_000);
+
+        // Open amount with a total cap: the part past the cap.
+        let mut open = paylink(0);
+        open.open_amount = true;
+        open.multi_use = true;
+        open.max_total = Some(1_000);
+        open.use_count = 2;
+        open.amount_received = 1_250;
+        assert_eq!(open.excess_of(500), 250);
+        open.amount_received = 900;
+        assert_eq!(open.excess_of(500), 0);
+    }
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    fn subscription(unit: IntervalUnit, count: i32, anchor_at: &str) -> Subscription {
+        let paylink = paylink(1_000);
+        Subscription {
+            id: Uuid::new_v4(),
+            merchant_id: Uuid::new_v4(),
+            merchant_pubkey: paylink.merchant_pubkey,
+            amount: 1_000,
+            mint: paylink.mint,
+            interval_unit: unit,
+            interval_count: count,
+            anchor_at: at(anchor_at),
+            trial_ends_at: None,
+            status: SubscriptionStatus::Active,
+            customer_ref: None,
+            memo_policy: paylink.memo_policy,
+            receipt_fields_policy: paylink.receipt_fields_policy,
+            amount_tolerance: paylink.amount_tolerance,
+            next_cycle_index: 0,
+            next_cycle_at: at(anchor_at),
+            created_at: at(anchor_at),
+            cancelled_at: None,
+        }
+    }
+
+    #[test]
+    fn interval_units_add_calendar_periods() {
+        let start = at("2024-01-31T09:30:00Z");
+        assert_eq!(IntervalUnit::Day.add(start, 1), Some(at("2024-02-01T09:30:00Z")));
+        assert_eq!(IntervalUnit::Week.add(start, 2), Some(at("2024-02-14T09:30:00Z")));
+        // Months keep the day of the month, clamped to the last day.
+        assert_eq!(IntervalUnit::Month.add(start, 1), Some(at("2024-02-29T09:30:00Z")));
+        assert_eq!(IntervalUnit::Month.add(start, 2), Some(at("2024-03-31T09:30:00Z")));
+        assert_eq!(IntervalUnit::Year.add(at("2024-02-29T00:00:00Z"), 1), Some(at("2025-02-28T00:00:00Z")));
+        assert_eq!(IntervalUnit::Day.add(start, 0), Some(start));
+    }
+
+    #[test]
+    fn interval_units_refuse_overflow() {
+        let start = at("2024-01-31T09:30:00Z");
+        assert_eq!(IntervalUnit::Day.add(start, i64::MAX), None);
+        assert_eq!(IntervalUnit::Month.add(start, -1), None);
+        assert_eq!(IntervalUnit::Year.add(start, i64::from(i32::MAX)), None);
+        assert_eq!(IntervalUnit::Year.add(start, i64::MAX), None);
+    }
+
+    #[test]
+    fn first_cycle_is_the_one_in_progress() {
+        let monthly = subscription(IntervalUnit::Month, 1, "2024-01-15T00:00:00Z");
+        assert_eq!(monthly.first_cycle_index(at("2024-01-01T00:00:00Z")), Some(0));
+        assert_eq!(monthly.first_cycle_index(at("2024-01-15T00:00:00Z")), Some(0));
+        assert_eq!(monthly.first_cycle_index(at("2024-02-14T23:59:59Z")), Some(0));
+        assert_eq!(monthly.first_cycle_index(at("2024-02-15T00:00:00Z")), Some(1));
+        assert_eq!(monthly.first_cycle_index(at("2025-01-20T00:00:00Z")), Some(12));
+    }
+
+    #[test]
+    fn first_cycle_after_a_trial_starts_at_or_after_its_end() {
+        let mut weekly = subscription(IntervalUnit::Week, 2, "2024-01-01T00:00:00Z");
+        weekly.trial_ends_at = Some(at("2024-01-20T00:00:00Z"));
+        // Cycles start every two weeks: Jan 1, Jan 15, Jan 29.
+        assert_eq!(weekly.first_cycle_index(at("2024-01-02T00:00:00Z")), Some(2));
+        weekly.trial_ends_at = Some(at("2024-01-15T00:00:00Z"));
+        assert_eq!(weekly.first_cycle_index(at("2024-01-02T00:00:00Z")), Some(1));
+    }
+
+    #[test]
+    fn first_cycle_gives_up_past_the_search_bound() {
+        let daily = subscription(IntervalUnit::Day, 1, "2024-01-01T00:00:00Z");
+        assert_eq!(daily.first_cycle_index(at("9999-01-01T00:00:00Z")), None);
+    }
+
+    fn invoice(line_items: Vec<(i64, i64)>, discounts: Vec<(DiscountKind, i64)>, tax_bps: Vec<i64>) -> Invoice {
+        Invoice {
+            id: Uuid::new_v4(),
+            paylink_id: Uuid::new_v4(),
+            customer: Json(Customer {
+                name: "Ada".to_string(),
+                email: None,
+                address: None,
+                tax_id: None,
+            }),
+            line_items: Json(
+                line_items
+                    .into_iter()
+                    .map(|(quantity, unit_price)| LineItem {
+                        description: "item".to_string(),
+                        quantity,
+                        unit_price,
+                        amount: 0,
+                    })
+                    .collect(),
+            ),
+            discounts: Json(
+                discounts
+                    .into_iter()
+                    .map(|(kind, value)| Discount {
+                        description: "discount".to_string(),
+                        kind,
+                        value,
+                        amount: 0,
+                    })
+                    .collect(),
+            ),
+            taxes: Json(
+                tax_bps
+                    .into_iter()
+                    .map(|rate_bps| TaxLine {
+                        name: "tax".to_string(),
+                        rate_bps,
+                        amount: 0,
+                    })
+                    .collect(),
+            ),
+            subtotal: 0,
+            discount_total: 0,
+            tax_total: 0,
+            total: 0,
+            due_date: None,
+            notes: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn computes_invoice_lines_and_totals() {
+        let mut inv = invoice(
+            vec![(2, 1_500), (1, 7_000)],
+            vec![(DiscountKind::Bps, 1_000), (DiscountKind::Absolute, 500)],
+            vec![825, 100],
+        );
+        inv.compute().unwrap();
+        assert_eq!(inv.line_items[0].amount, 3_000);
+        assert_eq!(inv.subtotal, 10_000);
+        assert_eq!(inv.discounts[0].amount, 1_000);
+        assert_eq!(inv.discounts[1].amount, 500);
+        assert_eq!(inv.discount_total, 1_500);
+        // 8.25% and 1% of 8500, rounded half up: 701.25 and 85.
+        assert_eq!(inv.taxes[0].amount, 701);
+        assert_eq!(inv.taxes[1].amount, 85);
+        assert_eq!(inv.tax_total, 786);
+        assert_eq!(inv.total, 9_286);
+    }
+
+    #[test]
+    fn rounds_tax_half_up() {
+        let mut inv = invoice(vec![(1, 150)], vec![], vec![100]);
+        inv.compute().unwrap();
+        assert_eq!(inv.taxes[0].amount, 2);
+        assert_eq!(inv.total, 152);
+    }
+
+    #[test]
+    fn discounts_stop_at_the_subtotal() {
+        let mut inv = invoice(
+            vec![(1, 1_000)],
+            vec![(DiscountKind::Absolute, 800), (DiscountKind::Absolute, 800)],
+            vec![],
+        );
+        assert_eq!(inv.compute(), Err("invoice total must be positive"));
+        assert_eq!(inv.discounts[1].amount, 200);
+        assert_eq!(inv.discount_total, 1_000);
+    }
+
+    #[test]
+    fn rejects_invalid_invoices() {
+        let cases = [
+            (invoice(vec![], vec![], vec![]), "invoice needs at least one line item"),
+            (
+                invoice(vec![(0, 100)], vec![], vec![]),
+                "line item quantity must be positive and unitPrice not negative",
+            ),
+            (
+                invoice(vec![(1, 100)], vec![(DiscountKind::Absolute, -1)], vec![]),
+                "discount value must not be negative",
+            ),
+            (
+                invoice(vec![(1, 100)], vec![(DiscountKind::Bps, 10_001)], vec![]),
+                "discount value must be at most 10000 bps",
+            ),
+            (
+                invoice(vec![(1, 100)], vec![], vec![10_001]),
+                "tax rateBps must be between 0 and 10000",
+            ),
+            (invoice(vec![(2, i64::MAX)], vec![], vec![]), "invoice amounts overflow"),
+            (invoice(vec![(1, 0)], vec![], vec![]), "invoice total must be positive"),
+        ];
+        for (mut inv, error) in cases {
+            assert_eq!(inv.compute(), Err(error));
+        }
+        let mut blank = invoice(vec![(1, 100)], vec![], vec![]);
+        blank.line_items[0].description = " ".to_string();
+        assert_eq!(blank.compute(), Err("line item description is required"));
+    }
+
+    #[test]
+    fn discount_kinds_keep_their_wire_names() {
+        let discount: Discount =
+            serde_json::from_value(serde_json::json!({"description": "d", "kind": "absolute", "value": 5})).unwrap();
+        assert_eq!(discount.kind, DiscountKind::Absolute);
+        assert_eq!(serde_json::to_value(DiscountKind::Bps).unwrap(), "bps");
+    }
+
+    #[test]
+    fn payments_to_expired_per_payment_links_are_all_excess() {
+        let mut multi = paylink(1_000);
+        multi.multi_use = true;
+        multi.use_count = 1;
+        multi.amount_received = 1_000;
+        assert_eq!(multi.excess_of(1_000), 0);
+        multi.status = PaylinkStatus::Expired;
+        assert_eq!(multi.excess_of(1_000), 1_000);
+
+        let mut open = paylink(0);
+        open.open_amount = true;
+        open.use_count = 1;
+        open.amount_received = 300;
+        open.expires_at = Utc::now() - Duration::minutes(1);
+        assert_eq!(open.excess_of(300), 300);
+
+        // A late transfer can still complete a single-use link.
+        let mut single = paylink(1_000);
+        single.status = PaylinkStatus::Expired;
+        single.amount_received = 1_000;
+        assert_eq!(single.excess_of(1_000), 0);
+    }
+}
@@ -1,27 +1,166 @@
-use sqlx::{PgPool, Postgres, Transaction};
+use chrono::{DateTime, Utc};
+use sqlx::{types::Json, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-use super::models::{ActivityEvent, PayLink, Receipt};
+use super::models::{
+    ActivityEvent, ApiKey, AuthNonce, CycleStatus, Invoice, Merchant, MerchantWebhookDelivery, PayLink,
+    PaylinkStatus, Payment, PriceQuote, Receipt, Subscription, SubscriptionCycle, SubscriptionEvent,
+    SubscriptionStatus, WalletSession, WebhookEvent, WebhookJob, MERCHANT_WEBHOOK_EVENTS,
+};
 
-pub async fn expire_paylinks(pool: &PgPool) -> Result<(), sqlx::Error> {
-    sqlx::query("UPDATE paylinks SET status='expired' WHERE status != 'paid' AND expires_at < now()")
-        .execute(pool)
-        .await?;
-    Ok(())
+/// The one place paylink status changes. Moves the paylink to `to` if its
+/// current state allows it, and records `to.event_type()` with `detail` in the
+/// same transaction. Returns `None` when the transition is not allowed (or the
+/// paylink belongs to another merchant). `paid` sets the paying signature and
+/// slot. A subscription cycle paylink also settles its cycle.
+pub async fn transition_paylink(
+    tx: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+    merchant_id: Option<Uuid>,
+    to: PaylinkStatus,
+    paid: Option<(&str, Option<i64>)>,
+    detail: serde_json::Value,
+) -> Result<Option<PayLink>, sqlx::Error> {
+    let paylink = sqlx::query_as::<_, PayLink>(
+        "UPDATE paylinks SET status = $2, paid_signature = COALESCE($3, paid_signature), paid_slot = COALESCE($4, paid_slot)\n         WHERE id = $1 AND status = ANY($5) AND ($6::uuid IS NULL OR merchant_id = $6)\n         RETURNING *",
+    )
+    .bind(id)
+    .bind(to)
+    .bind(paid.map(|(signature, _)| signature))
+    .bind(paid.and_then(|(_, slot)| slot))
+    .bind(PaylinkStatus::sources(to))
+    .bind(merchant_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+    if let Some(paylink) = &paylink {
+        insert_activity_event_tx(tx, paylink.id, to.event_type(), detail).await?;
+        if paylink.subscription_id.is_some() {
+            settle_subscription_cycle(tx, paylink.id, to).await?;
+        }
+    }
+    Ok(paylink)
 }
 
-pub async fn expire_paylink_by_id(pool: &PgPool, id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query("UPDATE paylinks SET status='expired' WHERE id = $1 AND status != 'paid' AND expires_at < now()")
-        .bind(id)
-        .execute(pool)
+/// Expires up to `limit` overdue unpaid paylinks in one transaction.
+/// Concurrent sweepers skip each other's rows.
+pub async fn expire_due_paylinks(pool: &PgPool, limit: i64) -> Result<Vec<PayLink>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let due: Vec<(Uuid, DateTime<Utc>)> = sqlx::query_as(
+        "SELECT id, expires_at FROM paylinks\n         WHERE status IN ('pending', 'partially_paid') AND expires_at < now()\n         ORDER BY expires_at ASC\n         LIMIT $1\n         FOR UPDATE SKIP LOCKED",
+    )
+    .bind(limit)
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut expired = Vec::with_capacity(due.len());
+    for (id, expires_at) in due {
+        if let Some(paylink) = transition_paylink(
+            &mut tx,
+            id,
+            None,
+            PaylinkStatus::Expired,
+            None,
+            serde_json::json!({"expiresAt": expires_at}),
+        )
+        .await?
+        {
+            expired.push(paylink);
+        }
+    }
+    tx.commit().await?;
+    Ok(expired)
+}
+
+/// Records a payment and adds it to the paylink's received total and use
+/// count, locking the paylink row for the rest of `tx`. The part the paylink
+/// did not ask for (`PayLink::excess_of`) is stored as its `refund_due`.
+/// Returns `None` if this signature was already recorded, against this or any
+/// other paylink: one transaction pays at most one paylink.
+pub async fn insert_payment(
+    tx: &mut Transaction<'_, Postgres>,
+    payment: &Payment,
+) -> Result<Option<(Payment, PayLink)>, sqlx::Error> {
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('payments'), hashtext($1))")
+        .bind(&payment.signature)
+        .execute(&mut **tx)
         .await?;
-    Ok(())
+    let inserted = sqlx::query(
+        "INSERT INTO payments (id, paylink_id, signature, amount, mint, slot, block_time, created_at)\n         SELECT $1,$2,$3,$4,$5,$6,$7,$8\n         WHERE NOT EXISTS (SELECT 1 FROM payments WHERE signature = $3)",
+    )
+    .bind(payment.id)
+    .bind(payment.paylink_id)
+    .bind(&payment.signature)
+    .bind(payment.amount)
+    .bind(&payment.mint)
+    .bind(payment.slot)
+    .bind(payment.block_time)
+    .bind(payment.created_at)
+    .execute(&mut **tx)
+    .await?
+    .rows_affected()
+        > 0;
+    if !inserted {
+        return Ok(None);
+    }
+    let paylink = sqlx::query_as::<_, PayLink>(
+        "UPDATE paylinks SET amount_received = amount_received + $2, use_count = use_count + 1\n         WHERE id = $1 RETURNING *",
+    )
+    .bind(payment.paylink_id)
+    .bind(payment.amount)
+    .fetch_one(&mut **tx)
+    .await?;
+    let payment = sqlx::query_as::<_, Payment>(
+        "UPDATE payments SET refund_due = $2 WHERE id = $1 RETURNING *",
+    )
+    .bind(payment.id)
+    .bind(paylink.excess_of(payment.amount))
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(Some((payment, paylink)))
+}
+
+pub async fn get_payment_by_signature(
+    pool: &PgPool,
+    signature: &str,
+) -> Result<Option<Payment>, sqlx::Error> {
+    sqlx::query_as::<_, Payment>("SELECT * FROM payments WHERE signature = $1 LIMIT 1")
+        .bind(signature)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn list_payments(
+    tx: &mut Transaction<'_, Postgres>,
+    paylink_id: Uuid,
+) -> Result<Vec<Payment>, sqlx::Error> {
+    sqlx::query_as::<_, Payment>(
+        "SELECT * FROM payments WHERE paylink_id = $1 ORDER BY created_at ASC, signature ASC",
+    )
+    .bind(paylink_id)
+    .fetch_all(&mut **tx)
+    .await
+}
+
+pub async fn list_merchant_payments(
+    pool: &PgPool,
+    merchant_id: Uuid,
+    paylink_id: Uuid,
+) -> Result<Vec<Payment>, sqlx::Error> {
+    sqlx::query_as::<_, Payment>(
+        "SELECT pm.* FROM payments pm\n         JOIN paylinks p ON pm.paylink_id = p.id\n         WHERE pm.paylink_id = $1 AND p.merchant_id = $2\n         ORDER BY pm.created_at ASC, pm.signature ASC",
+    )
+    .bind(paylink_id)
+    .bind(merchant_id)
+    .fetch_all(pool)
+    .await
 }
 
-pub async fn insert_paylink(pool: &PgPool, paylink: &PayLink) -> Result<(), sqlx::Error> {
+pub async fn insert_paylink(
+    tx: &mut Transaction<'_, Postgres>,
+    paylink: &PayLink,
+) -> Result<(), sqlx::Error> {
     sqlx::query(
-        "INSERT INTO paylinks (id, merchant_pubkey, expected_amount, mint, expires_at, invoice_ref, status, created_at, paid_signature, paid_slot, privacy_rail) \
-        VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11)",
+        "INSERT INTO paylinks (id, merchant_pubkey, expected_amount, mint, expires_at, invoice_ref, status, created_at, paid_signature, paid_slot, privacy_rail, reference, memo_policy, receipt_fields_policy, expected_memo, merchant_id, amount_tolerance, multi_use, max_uses, max_total, open_amount, min_amount, max_amount, subscription_id, has_invoice, fiat_currency, fiat_amount, price_quote, quote_ttl_secs) \
+        VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13,$14,$15,$16,$17,$18,$19,$20,$21,$22,$23,$24,$25,$26,$27,$28,$29)",
     )
     .bind(paylink.id)
     .bind(&paylink.merchant_pubkey)
@@ -29,16 +168,80 @@ pub async fn insert_paylink(pool: &PgPool, paylink: &PayLink) -> Result<(), sqlx
     .bind(&paylink.mint)
     .bind(paylink.expires_at)
     .bind(&paylink.invoice_ref)
-    .bind(&paylink.status)
+    .bind(paylink.status)
     .bind(paylink.created_at)
     .bind(&paylink.paid_signature)
     .bind(paylink.paid_slot)
     .bind(&paylink.privacy_rail)
-    .execute(pool)
+    .bind(&paylink.reference)
+    .bind(&paylink.memo_policy)
+    .bind(&paylink.receipt_fields_policy)
+    .bind(&paylink.expected_memo)
+    .bind(paylink.merchant_id)
+    .bind(paylink.amount_tolerance)
+    .bind(paylink.multi_use)
+    .bind(paylink.max_uses)
+    .bind(paylink.max_total)
+    .bind(paylink.open_amount)
+    .bind(paylink.min_amount)
+    .bind(paylink.max_amount)
+    .bind(paylink.subscription_id)
+    .bind(paylink.has_invoice)
+    .bind(paylink.fiat_currency)
+    .bind(paylink.fiat_amount)
+    .bind(&paylink.price_quote)
+    .bind(paylink.quote_ttl_secs)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn insert_invoice(
+    tx: &mut Transaction<'_, Postgres>,
+    invoice: &Invoice,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO invoices (id, paylink_id, customer, line_items, discounts, taxes, subtotal, discount_total, tax_total, total, due_date, notes, created_at) \
+        VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13)",
+    )
+    .bind(invoice.id)
+    .bind(invoice.paylink_id)
+    .bind(&invoice.customer)
+    .bind(&invoice.line_items)
+    .bind(&invoice.discounts)
+    .bind(&invoice.taxes)
+    .bind(invoice.subtotal)
+    .bind(invoice.discount_total)
+    .bind(invoice.tax_total)
+    .bind(invoice.total)
+    .bind(invoice.due_date)
+    .bind(&invoice.notes)
+    .bind(invoice.created_at)
+    .execute(&mut **tx)
     .await?;
     Ok(())
 }
 
+pub async fn get_invoice_by_paylink(
+    pool: &PgPool,
+    paylink_id: Uuid,
+) -> Result<Option<Invoice>, sqlx::Error> {
+    sqlx::query_as::<_, Invoice>("SELECT * FROM invoices WHERE paylink_id = $1")
+        .bind(paylink_id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn get_invoice_by_paylink_tx(
+    tx: &mut Transaction<'_, Postgres>,
+    paylink_id: Uuid,
+) -> Result<Option<Invoice>, sqlx::Error> {
+    sqlx::query_as::<_, Invoice>("SELECT * FROM invoices WHERE paylink_id = $1")
+        .bind(paylink_id)
+        .fetch_optional(&mut **tx)
+        .await
+}
+
 pub async fn get_paylink(pool: &PgPool, id: Uuid) -> Result<Option<PayLink>, sqlx::Error> {
     let res = sqlx::query_as::<_, PayLink>("SELECT * FROM paylinks WHERE id = $1")
         .bind(id)
@@ -47,8 +250,90 @@ pub async fn get_paylink(pool: &PgPool, id: Uuid) -> Result<Option<PayLink>, sql
     Ok(res)
 }
 
+pub async fn get_merchant_paylink(
+    pool: &PgPool,
+    merchant_id: Uuid,
+    id: Uuid,
+) -> Result<Option<PayLink>, sqlx::Error> {
+    sqlx::query_as::<_, PayLink>("SELECT * FROM paylinks WHERE id = $1 AND merchant_id = $2")
+        .bind(id)
+        .bind(merchant_id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn cancel_paylink(
+    pool: &PgPool,
+    merchant_id: Uuid,
+    id: Uuid,
+    reason: Option<&str>,
+) -> Result<Option<PayLink>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let paylink = transition_paylink(
+        &mut tx,
+        id,
+        Some(merchant_id),
+        PaylinkStatus::Cancelled,
+        None,
+        serde_json::json!({"reason": reason}),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(paylink)
+}
+
+/// Writes the editable fields of a pending paylink and records
+/// `PAYLINK_UPDATED` with `changes`. Returns `None` if it is no longer pending.
+pub async fn update_pending_paylink(
+    pool: &PgPool,
+    paylink: &PayLink,
+    changes: serde_json::Value,
+) -> Result<Option<PayLink>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let updated = sqlx::query_as::<_, PayLink>(
+        "UPDATE paylinks SET expires_at = $3, invoice_ref = $4\n         WHERE id = $1 AND merchant_id = $2 AND status='pending'\n         RETURNING *",
+    )
+    .bind(paylink.id)
+    .bind(paylink.merchant_id)
+    .bind(paylink.expires_at)
+    .bind(&paylink.invoice_ref)
+    .fetch_optional(&mut *tx)
+    .await?;
+    if updated.is_some() {
+        insert_activity_event_tx(&mut tx, paylink.id, "PAYLINK_UPDATED", changes).await?;
+    }
+    tx.commit().await?;
+    Ok(updated)
+}
+
+/// Replaces a lapsed fiat quote and the amount derived from it, recording
+/// `QUOTE_REFRESHED`. Returns `None` once the paylink is no longer pending or
+/// a payment has been applied against the previous quote.
+pub async fn requote_paylink(
+    pool: &PgPool,
+    id: Uuid,
+    quote: &PriceQuote,
+) -> Result<Option<PayLink>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let updated = sqlx::query_as::<_, PayLink>(
+        "UPDATE paylinks SET expected_amount = $2, price_quote = $3\n         WHERE id = $1 AND status='pending' AND quote_ttl_secs IS NOT NULL\n           AND (multi_use OR amount_received = 0)\n         RETURNING *",
+    )
+    .bind(id)
+    .bind(quote.amount)
+    .bind(Json(quote))
+    .fetch_optional(&mut *tx)
+    .await?;
+    if updated.is_some() {
+        insert_activity_event_tx(&mut tx, id, "QUOTE_REFRESHED", serde_json::json!({ "quote": quote }))
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(updated)
+}
+
 pub async fn list_paylinks(
     pool: &PgPool,
+    merchant_id: Uuid,
     status: Option<String>,
     mint: Option<String>,
     q: Option<String>,
@@ -58,32 +343,80 @@ pub async fn list_paylinks(
     let offset = (page - 1) * page_size;
 
     let items = sqlx::query_as::<_, PayLink>(
-        "SELECT * FROM paylinks\n         WHERE ($1::text IS NULL OR status = $1)\n           AND ($2::text IS NULL OR mint = $2)\n           AND ($3::text IS NULL OR merchant_pubkey ILIKE '%' || $3 || '%' OR invoice_ref ILIKE '%' || $3 || '%')\n         ORDER BY created_at DESC\n         LIMIT $4 OFFSET $5",
+        "SELECT * FROM paylinks\n         WHERE merchant_id = $6\n           AND ($1::text IS NULL OR status = $1)\n           AND ($2::text IS NULL OR mint = $2)\n           AND ($3::text IS NULL OR merchant_pubkey ILIKE '%' || $3 || '%' OR invoice_ref ILIKE '%' || $3 || '%')\n         ORDER BY created_at DESC\n         LIMIT $4 OFFSET $5",
     )
     .bind(&status)
     .bind(&mint)
     .bind(&q)
     .bind(page_size)
     .bind(offset)
+    .bind(merchant_id)
     .fetch_all(pool)
     .await?;
 
     let total: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM paylinks\n         WHERE ($1::text IS NULL OR status = $1)\n           AND ($2::text IS NULL OR mint = $2)\n           AND ($3::text IS NULL OR merchant_pubkey ILIKE '%' || $3 || '%' OR invoice_ref ILIKE '%' || $3 || '%')",
+        "SELECT COUNT(*) FROM paylinks\n         WHERE merchant_id = $4\n           AND ($1::text IS NULL OR status = $1)\n           AND ($2::text IS NULL OR mint = $2)\n           AND ($3::text IS NULL OR merchant_pubkey ILIKE '%' || $3 || '%' OR invoice_ref ILIKE '%' || $3 || '%')",
     )
     .bind(&status)
     .bind(&mint)
     .bind(&q)
+    .bind(merchant_id)
     .fetch_one(pool)
     .await?;
 
     Ok((items, total.0))
 }
 
-pub async fn insert_activity_event(
+/// Records an event about `signature` unless the paylink already has one of
+/// the same type for it, so reprocessing a signature does not repeat events
+/// (or their merchant webhooks). Returns whether the event was recorded.
+pub async fn insert_signature_event(
+    pool: &PgPool,
+    paylink_id: Uuid,
+    event_type: &str,
+    signature: &str,
+    detail: serde_json::Value,
+) -> Result<bool, sqlx::Error> {
+    insert_activity_event_once(pool, paylink_id, event_type, ("signature", signature), detail).await
+}
+
+/// Records an event unless the paylink already has one of the same type whose
+/// `detail` has `key.0` set to `key.1`. Returns whether it was recorded.
+pub async fn insert_activity_event_once(
     pool: &PgPool,
     paylink_id: Uuid,
     event_type: &str,
+    key: (&str, &str),
+    detail: serde_json::Value,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+        .bind(key.1)
+        .execute(&mut *tx)
+        .await?;
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM activity_events WHERE paylink_id = $1 AND type = $2 AND detail->>$3 = $4)",
+    )
+    .bind(paylink_id)
+    .bind(event_type)
+    .bind(key.0)
+    .bind(key.1)
+    .fetch_one(&mut *tx)
+    .await?;
+    if !exists {
+        insert_activity_event_tx(&mut tx, paylink_id, event_type, detail).await?;
+    }
+    tx.commit().await?;
+    Ok(!exists)
+}
+
+/// Records an activity event. Event types in `MERCHANT_WEBHOOK_EVENTS` are
+/// queued for delivery to the paylink merchant's webhook in the same
+/// transaction, so no event is recorded without its delivery.
+pub async fn insert_activity_event_tx(
+    tx: &mut Transaction<'_, Postgres>,
+    paylink_id: Uuid,
+    event_type: &str,
     detail: serde_json::Value,
 ) -> Result<(), sqlx::Error> {
     sqlx::query(
@@ -91,27 +424,168 @@ pub async fn insert_activity_event(
     )
     .bind(paylink_id)
     .bind(event_type)
+    .bind(&detail)
+    .execute(&mut **tx)
+    .await?;
+    if MERCHANT_WEBHOOK_EVENTS.contains(&event_type) {
+        enqueue_merchant_webhook(tx, paylink_id, event_type, &detail).await?;
+    }
+    Ok(())
+}
+
+/// Queues a webhook delivery when the paylink's merchant has a webhook URL.
+/// The payload carries the paylink's fixed terms; state changes are described
+/// by the event type and `detail`.
+async fn enqueue_merchant_webhook(
+    tx: &mut Transaction<'_, Postgres>,
+    paylink_id: Uuid,
+    event_type: &str,
+    detail: &serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO merchant_webhook_deliveries (id, merchant_id, paylink_id, event_type, url, payload)\n         SELECT $1, m.id, p.id, $3, m.webhook_url, jsonb_build_object(\n           'id', $1, 'type', $3, 'createdAt', now(), 'merchantId', m.id,\n           'data', jsonb_build_object(\n             'paylinkId', p.id, 'merchantPubkey', p.merchant_pubkey, 'expectedAmount', p.expected_amount,\n             'mint', p.mint, 'invoiceRef', p.invoice_ref, 'reference', p.reference,\n             'subscriptionId', p.subscription_id, 'detail', $4::jsonb))\n         FROM paylinks p JOIN merchants m ON m.id = p.merchant_id\n         WHERE p.id = $2 AND m.webhook_url IS NOT NULL",
+    )
+    .bind(Uuid::new_v4())
+    .bind(paylink_id)
+    .bind(event_type)
     .bind(detail)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_merchant_webhook(
+    pool: &PgPool,
+    merchant_id: Uuid,
+    url: Option<&str>,
+    secret: Option<&str>,
+) -> Result<Option<Merchant>, sqlx::Error> {
+    sqlx::query_as::<_, Merchant>(
+        "UPDATE merchants SET webhook_url = $2, webhook_secret = COALESCE($3, webhook_secret) WHERE id = $1 RETURNING *",
+    )
+    .bind(merchant_id)
+    .bind(url)
+    .bind(secret)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn list_merchant_webhook_deliveries(
+    pool: &PgPool,
+    merchant_id: Uuid,
+    status: Option<String>,
+    page: i64,
+    page_size: i64,
+) -> Result<(Vec<MerchantWebhookDelivery>, i64), sqlx::Error> {
+    let offset = (page - 1) * page_size;
+    let items = sqlx::query_as::<_, MerchantWebhookDelivery>(
+        "SELECT * FROM merchant_webhook_deliveries\n         WHERE merchant_id = $1 AND ($2::text IS NULL OR status = $2)\n         ORDER BY created_at DESC\n         LIMIT $3 OFFSET $4",
+    )
+    .bind(merchant_id)
+    .bind(status.clone())
+    .bind(page_size)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+    let total: (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM merchant_webhook_deliveries WHERE merchant_id = $1 AND ($2::text IS NULL OR status = $2)",
+    )
+    .bind(merchant_id)
+    .bind(status)
+    .fetch_one(pool)
+    .await?;
+    Ok((items, total.0))
+}
+
+/// Sends a delivery again with a fresh attempt budget, to the merchant's
+/// current webhook URL. Deliveries that are being sent are left alone.
+pub async fn redeliver_merchant_webhook(
+    pool: &PgPool,
+    merchant_id: Uuid,
+    id: Uuid,
+) -> Result<Option<MerchantWebhookDelivery>, sqlx::Error> {
+    sqlx::query_as::<_, MerchantWebhookDelivery>(
+        "UPDATE merchant_webhook_deliveries d\n         SET status='pending', attempts=0, run_at=now(), locked_at=NULL, last_error=NULL,\n             url=COALESCE(m.webhook_url, d.url), updated_at=now()\n         FROM merchants m\n         WHERE d.id = $2 AND d.merchant_id = $1 AND m.id = d.merchant_id AND d.status != 'sending'\n         RETURNING d.*",
+    )
+    .bind(merchant_id)
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn claim_merchant_webhook(
+    pool: &PgPool,
+) -> Result<Option<(MerchantWebhookDelivery, Option<String>)>, sqlx::Error> {
+    let delivery = sqlx::query_as::<_, MerchantWebhookDelivery>(
+        "UPDATE merchant_webhook_deliveries SET status='sending', attempts=attempts+1, locked_at=now(), updated_at=now()\n         WHERE id = (\n           SELECT id FROM merchant_webhook_deliveries\n           WHERE status='pending' AND run_at <= now()\n           ORDER BY run_at ASC\n           LIMIT 1\n           FOR UPDATE SKIP LOCKED\n         )\n         RETURNING *",
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(delivery) = delivery else {
+        return Ok(None);
+    };
+    let secret: Option<(Option<String>,)> =
+        sqlx::query_as("SELECT webhook_secret FROM merchants WHERE id = $1")
+            .bind(delivery.merchant_id)
+            .fetch_optional(pool)
+            .await?;
+    Ok(Some((delivery, secret.and_then(|s| s.0))))
+}
+
+/// Records the outcome of a delivery attempt. A failure with `retry_at` goes
+/// back to `pending`; without it the delivery is dead-lettered.
+pub async fn finish_merchant_webhook(
+    pool: &PgPool,
+    id: Uuid,
+    status_code: Option<i32>,
+    error: Option<&str>,
+    retry_at: Option<DateTime<Utc>>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE merchant_webhook_deliveries SET\n           status = CASE WHEN $3::text IS NULL THEN 'delivered' WHEN $4::timestamptz IS NULL THEN 'dead' ELSE 'pending' END,\n           delivered_at = CASE WHEN $3::text IS NULL THEN now() ELSE delivered_at END,\n           run_at = COALESCE($4, run_at), locked_at=NULL, last_status_code=$2, last_error=$3, updated_at=now()\n         WHERE id = $1",
+    )
+    .bind(id)
+    .bind(status_code)
+    .bind(error)
+    .bind(retry_at)
     .execute(pool)
     .await?;
     Ok(())
 }
 
+pub async fn requeue_stale_merchant_webhooks(
+    pool: &PgPool,
+    stale_after_secs: i64,
+) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE merchant_webhook_deliveries SET status='pending', locked_at=NULL, run_at=now(), updated_at=now()\n         WHERE status='sending' AND locked_at < now() - make_interval(secs => $1)",
+    )
+    .bind(stale_after_secs as f64)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+
 pub async fn list_activity_events(
     pool: &PgPool,
+    merchant_id: Uuid,
     paylink_id: Uuid,
 ) -> Result<Vec<ActivityEvent>, sqlx::Error> {
     sqlx::query_as::<_, ActivityEvent>(
-        "SELECT * FROM activity_events WHERE paylink_id = $1 ORDER BY at ASC",
+        "SELECT e.* FROM activity_events e\n         JOIN paylinks p ON e.paylink_id = p.id\n         WHERE e.paylink_id = $1 AND p.merchant_id = $2\n         ORDER BY e.at ASC",
     )
     .bind(paylink_id)
+    .bind(merchant_id)
     .fetch_all(pool)
     .await
 }
 
-pub async fn insert_receipt(pool: &PgPool, receipt: &Receipt) -> Result<(), sqlx::Error> {
+pub async fn insert_receipt(
+    tx: &mut Transaction<'_, Postgres>,
+    receipt: &Receipt,
+) -> Result<(), sqlx::Error> {
     sqlx::query(
-        "INSERT INTO receipts (id, paylink_id, commitment, issued_at, facts, rail) VALUES ($1,$2,$3,$4,$5,$6)",
+        "INSERT INTO receipts (id, paylink_id, commitment, issued_at, facts, rail, simulated, payment_id, subscription_id) VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9)",
     )
     .bind(receipt.id)
     .bind(receipt.paylink_id)
@@ -119,19 +593,24 @@ pub async fn insert_receipt(pool: &PgPool, receipt: &Receipt) -> Result<(), sqlx
     .bind(receipt.issued_at)
     .bind(&receipt.facts)
     .bind(&receipt.rail)
-    .execute(pool)
+    .bind(receipt.simulated)
+    .bind(receipt.payment_id)
+    .bind(receipt.subscription_id)
+    .execute(&mut **tx)
     .await?;
     Ok(())
 }
 
 pub async fn list_receipts_by_paylink(
     pool: &PgPool,
+    merchant_id: Uuid,
     paylink_id: Uuid,
 ) -> Result<Vec<Receipt>, sqlx::Error> {
     sqlx::query_as::<_, Receipt>(
-        "SELECT * FROM receipts WHERE paylink_id = $1 ORDER BY issued_at DESC",
+        "SELECT r.* FROM receipts r\n         JOIN paylinks p ON r.paylink_id = p.id\n         WHERE r.paylink_id = $1 AND p.merchant_id = $2\n         ORDER BY r.issued_at DESC",
     )
     .bind(paylink_id)
+    .bind(merchant_id)
     .fetch_all(pool)
     .await
 }
@@ -143,6 +622,20 @@ pub async fn get_receipt(pool: &PgPool, id: Uuid) -> Result<Option<Receipt>, sql
         .await
 }
 
+pub async fn get_merchant_receipt(
+    pool: &PgPool,
+    merchant_id: Uuid,
+    id: Uuid,
+) -> Result<Option<Receipt>, sqlx::Error> {
+    sqlx::query_as::<_, Receipt>(
+        "SELECT r.* FROM receipts r JOIN paylinks p ON r.paylink_id = p.id WHERE r.id = $1 AND p.merchant_id = $2",
+    )
+    .bind(id)
+    .bind(merchant_id)
+    .fetch_optional(pool)
+    .await
+}
+
 pub async fn get_receipt_by_commitment(
     pool: &PgPool,
     commitment: &str,
@@ -155,91 +648,731 @@ pub async fn get_receipt_by_commitment(
 
 pub async fn list_receipts_by_merchant(
     pool: &PgPool,
-    merchant: Option<String>,
+    merchant_id: Uuid,
+    merchant_pubkey: Option<String>,
     page: i64,
     page_size: i64,
 ) -> Result<(Vec<Receipt>, i64), sqlx::Error> {
     let offset = (page - 1) * page_size;
 
-    let items = if let Some(merchant) = merchant.clone() {
-        sqlx::query_as::<_, Receipt>(
-            "SELECT r.* FROM receipts r\n             JOIN paylinks p ON r.paylink_id = p.id\n             WHERE p.merchant_pubkey = $1\n             ORDER BY r.issued_at DESC\n             LIMIT $2 OFFSET $3",
-        )
-        .bind(merchant)
-        .bind(page_size)
-        .bind(offset)
-        .fetch_all(pool)
-        .await?
-    } else {
-        sqlx::query_as::<_, Receipt>(
-            "SELECT * FROM receipts ORDER BY issued_at DESC LIMIT $1 OFFSET $2",
-        )
-        .bind(page_size)
-        .bind(offset)
-        .fetch_all(pool)
-        .await?
+    let items = sqlx::query_as::<_, Receipt>(
+        "SELECT r.* FROM receipts r\n         JOIN paylinks p ON r.paylink_id = p.id\n         WHERE p.merchant_id = $1\n           AND ($2::text IS NULL OR p.merchant_pubkey = $2)\n         ORDER BY r.issued_at DESC\n         LIMIT $3 OFFSET $4",
+    )
+    .bind(merchant_id)
+    .bind(&merchant_pubkey)
+    .bind(page_size)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    let total: (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM receipts r JOIN paylinks p ON r.paylink_id = p.id\n         WHERE p.merchant_id = $1 AND ($2::text IS NULL OR p.merchant_pubkey = $2)",
+    )
+    .bind(merchant_id)
+    .bind(&merchant_pubkey)
+    .fetch_one(pool)
+    .await?;
+
+    Ok((items, total.0))
+}
+
+pub async fn list_receipts_by_subscription(
+    pool: &PgPool,
+    merchant_id: Uuid,
+    subscription_id: Uuid,
+) -> Result<Vec<Receipt>, sqlx::Error> {
+    sqlx::query_as::<_, Receipt>(
+        "SELECT r.* FROM receipts r\n         JOIN subscriptions s ON r.subscription_id = s.id\n         WHERE r.subscription_id = $1 AND s.merchant_id = $2\n         ORDER BY r.issued_at DESC",
+    )
+    .bind(subscription_id)
+    .bind(merchant_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn insert_subscription(
+    tx: &mut Transaction<'_, Postgres>,
+    subscription: &Subscription,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO subscriptions (id, merchant_id, merchant_pubkey, amount, mint, interval_unit, interval_count, anchor_at, trial_ends_at, status, customer_ref, memo_policy, receipt_fields_policy, amount_tolerance, next_cycle_index, next_cycle_at, created_at) \
+        VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13,$14,$15,$16,$17)",
+    )
+    .bind(subscription.id)
+    .bind(subscription.merchant_id)
+    .bind(&subscription.merchant_pubkey)
+    .bind(subscription.amount)
+    .bind(&subscription.mint)
+    .bind(subscription.interval_unit)
+    .bind(subscription.interval_count)
+    .bind(subscription.anchor_at)
+    .bind(subscription.trial_ends_at)
+    .bind(subscription.status)
+    .bind(&subscription.customer_ref)
+    .bind(&subscription.memo_policy)
+    .bind(&subscription.receipt_fields_policy)
+    .bind(subscription.amount_tolerance)
+    .bind(subscription.next_cycle_index)
+    .bind(subscription.next_cycle_at)
+    .bind(subscription.created_at)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_merchant_subscription(
+    pool: &PgPool,
+    merchant_id: Uuid,
+    id: Uuid,
+) -> Result<Option<Subscription>, sqlx::Error> {
+    sqlx::query_as::<_, Subscription>("SELECT * FROM subscriptions WHERE id = $1 AND merchant_id = $2")
+        .bind(id)
+        .bind(merchant_id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn list_subscriptions(
+    pool: &PgPool,
+    merchant_id: Uuid,
+    status: Option<String>,
+    page: i64,
+    page_size: i64,
+) -> Result<(Vec<Subscription>, i64), sqlx::Error> {
+    let offset = (page - 1) * page_size;
+
+    let items = sqlx::query_as::<_, Subscription>(
+        "SELECT * FROM subscriptions\n         WHERE merchant_id = $1 AND ($2::text IS NULL OR status = $2)\n         ORDER BY created_at DESC\n         LIMIT $3 OFFSET $4",
+    )
+    .bind(merchant_id)
+    .bind(&status)
+    .bind(page_size)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    let total: (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM subscriptions WHERE merchant_id = $1 AND ($2::text IS NULL OR status = $2)",
+    )
+    .bind(merchant_id)
+    .bind(&status)
+    .fetch_one(pool)
+    .await?;
+
+    Ok((items, total.0))
+}
+
+/// Locks up to `limit` subscriptions whose next cycle has started, for the
+/// rest of `tx`. Concurrent schedulers skip each other's rows.
+pub async fn lock_due_subscriptions(
+    tx: &mut Transaction<'_, Postgres>,
+    limit: i64,
+    exclude: &[Uuid],
+) -> Result<Vec<Subscription>, sqlx::Error> {
+    sqlx::query_as::<_, Subscription>(
+        "SELECT * FROM subscriptions\n         WHERE status <> 'cancelled' AND next_cycle_at <= now() AND id <> ALL($2)\n         ORDER BY next_cycle_at ASC\n         LIMIT $1\n         FOR UPDATE SKIP LOCKED",
+    )
+    .bind(limit)
+    .bind(exclude)
+    .fetch_all(&mut **tx)
+    .await
+}
+
+/// Records a cycle's paylink and moves the subscription on to the following
+/// cycle. The first billed cycle ends a trial.
+pub async fn insert_subscription_cycle(
+    tx: &mut Transaction<'_, Postgres>,
+    cycle: &SubscriptionCycle,
+    next_cycle_at: DateTime<Utc>,
+) -> Result<Subscription, sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO subscription_cycles (id, subscription_id, cycle_index, period_start, period_end, paylink_id, status, created_at) \
+        VALUES ($1,$2,$3,$4,$5,$6,$7,$8)",
+    )
+    .bind(cycle.id)
+    .bind(cycle.subscription_id)
+    .bind(cycle.cycle_index)
+    .bind(cycle.period_start)
+    .bind(cycle.period_end)
+    .bind(cycle.paylink_id)
+    .bind(cycle.status)
+    .bind(cycle.created_at)
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query_as::<_, Subscription>(
+        "UPDATE subscriptions SET next_cycle_index = $2 + 1, next_cycle_at = $3,\n           status = CASE WHEN status = 'trialing' THEN 'active' ELSE status END\n         WHERE id = $1 RETURNING *",
+    )
+    .bind(cycle.subscription_id)
+    .bind(cycle.cycle_index)
+    .bind(next_cycle_at)
+    .fetch_one(&mut **tx)
+    .await
+}
+
+pub async fn list_subscription_cycles(
+    pool: &PgPool,
+    subscription_id: Uuid,
+) -> Result<Vec<SubscriptionCycle>, sqlx::Error> {
+    sqlx::query_as::<_, SubscriptionCycle>(
+        "SELECT * FROM subscription_cycles WHERE subscription_id = $1 ORDER BY cycle_index ASC",
+    )
+    .bind(subscription_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Settles the cycle of a subscription paylink that just moved to `to`, and
+/// keeps the subscription `past_due` exactly while it has missed cycles.
+async fn settle_subscription_cycle(
+    tx: &mut Transaction<'_, Postgres>,
+    paylink_id: Uuid,
+    to: PaylinkStatus,
+) -> Result<(), sqlx::Error> {
+    let (status, from) = match to {
+        PaylinkStatus::Paid => (CycleStatus::Paid, vec!["open", "missed"]),
+        PaylinkStatus::Expired => (CycleStatus::Missed, vec!["open"]),
+        PaylinkStatus::Cancelled => (CycleStatus::Cancelled, vec!["open"]),
+        PaylinkStatus::Pending | PaylinkStatus::PartiallyPaid => return Ok(()),
+    };
+    let previous: Option<(CycleStatus,)> = sqlx::query_as(
+        "SELECT status FROM subscription_cycles WHERE paylink_id = $1 FOR UPDATE",
+    )
+    .bind(paylink_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+    let cycle = sqlx::query_as::<_, SubscriptionCycle>(
+        "UPDATE subscription_cycles SET status = $2, settled_at = now()\n         WHERE paylink_id = $1 AND status = ANY($3)\n         RETURNING *",
+    )
+    .bind(paylink_id)
+    .bind(status)
+    .bind(from)
+    .fetch_optional(&mut **tx)
+    .await?;
+    let cycle = match cycle {
+        Some(cycle) => cycle,
+        None => return Ok(()),
     };
 
-    let total: (i64,) = if let Some(merchant) = merchant {
-        sqlx::query_as(
-            "SELECT COUNT(*) FROM receipts r JOIN paylinks p ON r.paylink_id = p.id WHERE p.merchant_pubkey = $1",
-        )
-        .bind(merchant)
-        .fetch_one(pool)
-        .await?
-    } else {
-        sqlx::query_as("SELECT COUNT(*) FROM receipts")
-            .fetch_one(pool)
-            .await?
+    let event_type = match status {
+        CycleStatus::Paid => "SUBSCRIPTION_CYCLE_PAID",
+        CycleStatus::Missed => "SUBSCRIPTION_CYCLE_MISSED",
+        CycleStatus::Open | CycleStatus::Cancelled => return Ok(()),
     };
+    sqlx::query(
+        "UPDATE subscriptions SET status = CASE\n           WHEN EXISTS (SELECT 1 FROM subscription_cycles c WHERE c.subscription_id = $1 AND c.status = 'missed')\n           THEN 'past_due' ELSE 'active' END\n         WHERE id = $1 AND status IN ('active', 'past_due')",
+    )
+    .bind(cycle.subscription_id)
+    .execute(&mut **tx)
+    .await?;
+    insert_subscription_event_tx(
+        tx,
+        cycle.subscription_id,
+        event_type,
+        serde_json::json!({
+            "cycleIndex": cycle.cycle_index,
+            "periodStart": cycle.period_start,
+            "periodEnd": cycle.period_end,
+            "paylinkId": cycle.paylink_id,
+            "late": previous.is_some_and(|(s,)| s == CycleStatus::Missed),
+        }),
+    )
+    .await
+}
 
-    Ok((items, total.0))
+/// Cancels the subscription and any of its cycle paylinks that are still
+/// open. Returns `None` if it is already cancelled or belongs to another
+/// merchant.
+pub async fn cancel_subscription(
+    pool: &PgPool,
+    merchant_id: Uuid,
+    id: Uuid,
+    reason: Option<&str>,
+) -> Result<Option<Subscription>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let subscription = sqlx::query_as::<_, Subscription>(
+        "UPDATE subscriptions SET status = $3, cancelled_at = now()\n         WHERE id = $1 AND merchant_id = $2 AND status <> $3\n         RETURNING *",
+    )
+    .bind(id)
+    .bind(merchant_id)
+    .bind(SubscriptionStatus::Cancelled)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let subscription = match subscription {
+        Some(subscription) => subscription,
+        None => return Ok(None),
+    };
+    insert_subscription_event_tx(
+        &mut tx,
+        id,
+        "SUBSCRIPTION_CANCELLED",
+        serde_json::json!({"reason": reason}),
+    )
+    .await?;
+
+    let open: Vec<(Uuid,)> = sqlx::query_as(
+        "SELECT paylink_id FROM subscription_cycles WHERE subscription_id = $1 AND status = 'open'",
+    )
+    .bind(id)
+    .fetch_all(&mut *tx)
+    .await?;
+    for (paylink_id,) in open {
+        transition_paylink(
+            &mut tx,
+            paylink_id,
+            Some(merchant_id),
+            PaylinkStatus::Cancelled,
+            None,
+            serde_json::json!({"reason": "subscription cancelled"}),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(Some(subscription))
 }
 
-pub async fn insert_webhook_event(
+/// Records a subscription event and, like `insert_activity_event_tx`, queues
+/// the merchant webhook for the types in `MERCHANT_WEBHOOK_EVENTS`.
+pub async fn insert_subscription_event_tx(
+    tx: &mut Transaction<'_, Postgres>,
+    subscription_id: Uuid,
+    event_type: &str,
+    detail: serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO subscription_events (subscription_id, type, at, detail) VALUES ($1,$2,now(),$3)",
+    )
+    .bind(subscription_id)
+    .bind(event_type)
+    .bind(&detail)
+    .execute(&mut **tx)
+    .await?;
+    if MERCHANT_WEBHOOK_EVENTS.contains(&event_type) {
+        sqlx::query(
+            "INSERT INTO merchant_webhook_deliveries (id, merchant_id, subscription_id, event_type, url, payload)\n             SELECT $1, m.id, s.id, $3, m.webhook_url, jsonb_build_object(\n               'id', $1, 'type', $3, 'createdAt', now(), 'merchantId', m.id,\n               'data', jsonb_build_object(\n                 'subscriptionId', s.id, 'merchantPubkey', s.merchant_pubkey, 'amount', s.amount, 'mint', s.mint,\n                 'intervalUnit', s.interval_unit, 'intervalCount', s.interval_count, 'customerRef', s.customer_ref,\n                 'status', s.status, 'detail', $4::jsonb))\n             FROM subscriptions s JOIN merchants m ON m.id = s.merchant_id\n             WHERE s.id = $2 AND m.webhook_url IS NOT NULL",
+        )
+        .bind(Uuid::new_v4())
+        .bind(subscription_id)
+        .bind(event_type)
+        .bind(&detail)
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+pub async fn list_subscription_events(
+    pool: &PgPool,
+    merchant_id: Uuid,
+    subscription_id: Uuid,
+) -> Result<Vec<SubscriptionEvent>, sqlx::Error> {
+    sqlx::query_as::<_, SubscriptionEvent>(
+        "SELECT e.* FROM subscription_events e\n         JOIN subscriptions s ON e.subscription_id = s.id\n         WHERE e.subscription_id = $1 AND s.merchant_id = $2\n         ORDER BY e.at ASC, e.id ASC",
+    )
+    .bind(subscription_id)
+    .bind(merchant_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn insert_merchant(
+    tx: &mut Transaction<'_, Postgres>,
+    merchant: &Merchant,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO merchants (id, name, created_at) VALUES ($1,$2,$3)")
+        .bind(merchant.id)
+        .bind(&merchant.name)
+        .bind(merchant.created_at)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_merchant(pool: &PgPool, id: Uuid) -> Result<Option<Merchant>, sqlx::Error> {
+    sqlx::query_as::<_, Merchant>("SELECT * FROM merchants WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn insert_api_key(
+    tx: &mut Transaction<'_, Postgres>,
+    key: &ApiKey,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO api_keys (id, merchant_id, prefix, key_hash, scopes, created_at) VALUES ($1,$2,$3,$4,$5,$6)",
+    )
+    .bind(key.id)
+    .bind(key.merchant_id)
+    .bind(&key.prefix)
+    .bind(&key.key_hash)
+    .bind(&key.scopes)
+    .bind(key.created_at)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Looks up an active key by hash and stamps `last_used_at`.
+pub async fn use_api_key(pool: &PgPool, key_hash: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+    sqlx::query_as::<_, ApiKey>(
+        "UPDATE api_keys SET last_used_at = now() WHERE key_hash = $1 AND revoked_at IS NULL RETURNING *",
+    )
+    .bind(key_hash)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn list_api_keys(pool: &PgPool, merchant_id: Uuid) -> Result<Vec<ApiKey>, sqlx::Error> {
+    sqlx::query_as::<_, ApiKey>(
+        "SELECT * FROM api_keys WHERE merchant_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(merchant_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Revokes a key along with the wallets it linked and their sessions.
+/// Returns `None` if the merchant has no such active key, otherwise the
+/// unlinked wallets.
+pub async fn revoke_api_key(
+    tx: &mut Transaction<'_, Postgres>,
+    merchant_id: Uuid,
+    key_id: Uuid,
+) -> Result<Option<Vec<String>>, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE api_keys SET revoked_at = now() WHERE id = $1 AND merchant_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(key_id)
+    .bind(merchant_id)
+    .execute(&mut **tx)
+    .await?;
+    if res.rows_affected() == 0 {
+        return Ok(None);
+    }
+    unlink_merchant_wallets(tx, merchant_id, None, Some(key_id))
+        .await
+        .map(Some)
+}
+
+pub async fn insert_auth_nonce(pool: &PgPool, nonce: &AuthNonce) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO auth_nonces (nonce, pubkey, message, created_at, expires_at) VALUES ($1,$2,$3,$4,$5)",
+    )
+    .bind(&nonce.nonce)
+    .bind(&nonce.pubkey)
+    .bind(&nonce.message)
+    .bind(nonce.created_at)
+    .bind(nonce.expires_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Marks a nonce used and returns it, if it was issued to `pubkey`, is
+/// unexpired and has not been used before. Each nonce can succeed once.
+pub async fn consume_auth_nonce(
+    tx: &mut Transaction<'_, Postgres>,
+    nonce: &str,
+    pubkey: &str,
+) -> Result<Option<AuthNonce>, sqlx::Error> {
+    sqlx::query_as::<_, AuthNonce>(
+        "UPDATE auth_nonces SET used_at = now()\n         WHERE nonce = $1 AND pubkey = $2 AND used_at IS NULL AND expires_at > now()\n         RETURNING *",
+    )
+    .bind(nonce)
+    .bind(pubkey)
+    .fetch_optional(&mut **tx)
+    .await
+}
+
+pub async fn delete_expired_auth_nonces(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM auth_nonces WHERE expires_at < now() - interval '1 day'")
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+/// The merchant a wallet is linked to and the scopes it was granted.
+pub async fn get_wallet_merchant(
+    tx: &mut Transaction<'_, Postgres>,
+    pubkey: &str,
+) -> Result<Option<(Uuid, Vec<String>)>, sqlx::Error> {
+    sqlx::query_as("SELECT merchant_id, scopes FROM merchant_wallets WHERE pubkey = $1")
+        .bind(pubkey)
+        .fetch_optional(&mut **tx)
+        .await
+}
+
+/// Links a wallet to a merchant with the scopes of the API key `api_key_id`
+/// that vouched for it. Revoking that key removes the link.
+pub async fn link_merchant_wallet(
+    tx: &mut Transaction<'_, Postgres>,
+    merchant_id: Uuid,
+    pubkey: &str,
+    scopes: &[String],
+    api_key_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO merchant_wallets (pubkey, merchant_id, scopes, api_key_id) VALUES ($1,$2,$3,$4)")
+        .bind(pubkey)
+        .bind(merchant_id)
+        .bind(scopes)
+        .bind(api_key_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Removes a merchant's wallet links, either one wallet or those linked by
+/// one API key, and revokes their sessions. Returns the unlinked wallets.
+pub async fn unlink_merchant_wallets(
+    tx: &mut Transaction<'_, Postgres>,
+    merchant_id: Uuid,
+    pubkey: Option<&str>,
+    api_key_id: Option<Uuid>,
+) -> Result<Vec<String>, sqlx::Error> {
+    let unlinked: Vec<String> = sqlx::query_scalar(
+        "DELETE FROM merchant_wallets\n         WHERE merchant_id = $1 AND ($2::text IS NULL OR pubkey = $2) AND ($3::uuid IS NULL OR api_key_id = $3)\n         RETURNING pubkey",
+    )
+    .bind(merchant_id)
+    .bind(pubkey)
+    .bind(api_key_id)
+    .fetch_all(&mut **tx)
+    .await?;
+    sqlx::query(
+        "UPDATE wallet_sessions SET revoked_at = now()\n         WHERE merchant_id = $1 AND pubkey = ANY($2) AND revoked_at IS NULL",
+    )
+    .bind(merchant_id)
+    .bind(&unlinked)
+    .execute(&mut **tx)
+    .await?;
+    Ok(unlinked)
+}
+
+pub async fn merchant_has_wallet(
+    pool: &PgPool,
+    merchant_id: Uuid,
+    pubkey: &str,
+) -> Result<bool, sqlx::Error> {
+    let row: (bool,) = sqlx::query_as(
+        "SELECT EXISTS (SELECT 1 FROM merchant_wallets WHERE merchant_id = $1 AND pubkey = $2)",
+    )
+    .bind(merchant_id)
+    .bind(pubkey)
+    .fetch_one(pool)
+    .await?;
+    Ok(row.0)
+}
+
+pub async fn insert_wallet_session(
+    tx: &mut Transaction<'_, Postgres>,
+    session: &WalletSession,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO wallet_sessions (id, token_hash, pubkey, merchant_id, created_at, expires_at, scopes) VALUES ($1,$2,$3,$4,$5,$6,$7)",
+    )
+    .bind(session.id)
+    .bind(&session.token_hash)
+    .bind(&session.pubkey)
+    .bind(session.merchant_id)
+    .bind(session.created_at)
+    .bind(session.expires_at)
+    .bind(&session.scopes)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_active_wallet_session(
+    pool: &PgPool,
+    token_hash: &str,
+) -> Result<Option<WalletSession>, sqlx::Error> {
+    sqlx::query_as::<_, WalletSession>(
+        "SELECT * FROM wallet_sessions WHERE token_hash = $1 AND revoked_at IS NULL AND expires_at > now()",
+    )
+    .bind(token_hash)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn revoke_wallet_session(pool: &PgPool, token_hash: &str) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE wallet_sessions SET revoked_at = now() WHERE token_hash = $1 AND revoked_at IS NULL",
+    )
+    .bind(token_hash)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Hands paylinks created before merchant accounts existed to a merchant.
+pub async fn assign_unowned_paylinks(
     pool: &PgPool,
+    merchant_id: Uuid,
+    merchant_pubkey: &str,
+) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE paylinks SET merchant_id = $1 WHERE merchant_id IS NULL AND merchant_pubkey = $2",
+    )
+    .bind(merchant_id)
+    .bind(merchant_pubkey)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+pub async fn insert_webhook_event(
+    tx: &mut Transaction<'_, Postgres>,
     signature: &str,
     payload: &serde_json::Value,
+    authenticated: bool,
 ) -> Result<bool, sqlx::Error> {
     let res = sqlx::query(
-        "INSERT INTO webhook_events (signature, received_at, payload) VALUES ($1, now(), $2) ON CONFLICT (signature) DO NOTHING",
+        "INSERT INTO webhook_events (signature, received_at, payload, authenticated) VALUES ($1, now(), $2, $3) ON CONFLICT (signature) DO NOTHING",
     )
     .bind(signature)
     .bind(payload)
-    .execute(pool)
+    .bind(authenticated)
+    .execute(&mut **tx)
     .await?;
     Ok(res.rows_affected() > 0)
 }
 
-pub async fn mark_paylink_paid(
+pub async fn get_webhook_event(
+    pool: &PgPool,
+    signature: &str,
+) -> Result<Option<WebhookEvent>, sqlx::Error> {
+    sqlx::query_as::<_, WebhookEvent>("SELECT * FROM webhook_events WHERE signature = $1")
+        .bind(signature)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn list_webhook_events_for_replay(
+    pool: &PgPool,
+    signature: Option<String>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    unmatched_only: bool,
+    limit: i64,
+) -> Result<Vec<WebhookEvent>, sqlx::Error> {
+    sqlx::query_as::<_, WebhookEvent>(
+        "SELECT e.* FROM webhook_events e\n         WHERE ($1::text IS NULL OR e.signature = $1)\n           AND ($2::timestamptz IS NULL OR e.received_at >= $2)\n           AND ($3::timestamptz IS NULL OR e.received_at < $3)\n           AND (NOT $4 OR NOT EXISTS (\n             SELECT 1 FROM activity_events a\n             WHERE a.type = 'TX_VERIFIED_MATCH' AND a.detail->>'signature' = e.signature\n           ))\n         ORDER BY e.received_at ASC\n         LIMIT $5",
+    )
+    .bind(signature)
+    .bind(from)
+    .bind(to)
+    .bind(unmatched_only)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn enqueue_webhook_job(
     tx: &mut Transaction<'_, Postgres>,
-    paylink_id: Uuid,
     signature: &str,
-    slot: Option<i64>,
-) -> Result<Option<PayLink>, sqlx::Error> {
-    let paylink = sqlx::query_as::<_, PayLink>(
-        "UPDATE paylinks SET status='paid', paid_signature=$2, paid_slot=$3 WHERE id=$1 AND status != 'paid' RETURNING *",
+    max_attempts: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO webhook_jobs (signature, status, attempts, max_attempts, run_at, created_at, updated_at) \
+        VALUES ($1, 'queued', 0, $2, now(), now(), now()) ON CONFLICT (signature) DO NOTHING",
     )
-    .bind(paylink_id)
     .bind(signature)
-    .bind(slot)
-    .fetch_optional(&mut **tx)
+    .bind(max_attempts)
+    .execute(&mut **tx)
     .await?;
-    Ok(paylink)
+    Ok(())
 }
 
-pub async fn get_receipt_by_paylink(
+/// Puts a signature back on the queue with a fresh attempt budget. Jobs that
+/// are currently running are left alone. Reprocessing is idempotent: signature
+/// events and payments already recorded are not written again.
+pub async fn requeue_webhook_job(
     pool: &PgPool,
-    paylink_id: Uuid,
-) -> Result<Option<Receipt>, sqlx::Error> {
-    sqlx::query_as::<_, Receipt>(
-        "SELECT * FROM receipts WHERE paylink_id = $1 ORDER BY issued_at DESC LIMIT 1",
+    signature: &str,
+    max_attempts: i32,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "INSERT INTO webhook_jobs (signature, status, attempts, max_attempts, run_at, created_at, updated_at) \
+        VALUES ($1, 'queued', 0, $2, now(), now(), now()) \
+        ON CONFLICT (signature) DO UPDATE SET status='queued', attempts=0, max_attempts=EXCLUDED.max_attempts, \
+        run_at=now(), locked_at=NULL, last_error=NULL, updated_at=now() \
+        WHERE webhook_jobs.status != 'running'",
+    )
+    .bind(signature)
+    .bind(max_attempts)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn claim_webhook_job(pool: &PgPool) -> Result<Option<WebhookJob>, sqlx::Error> {
+    sqlx::query_as::<_, WebhookJob>(
+        "UPDATE webhook_jobs SET status='running', attempts=attempts+1, locked_at=now(), updated_at=now()\n         WHERE id = (\n           SELECT id FROM webhook_jobs\n           WHERE status='queued' AND run_at <= now()\n           ORDER BY run_at ASC\n           LIMIT 1\n           FOR UPDATE SKIP LOCKED\n         )\n         RETURNING *",
     )
-    .bind(paylink_id)
     .fetch_optional(pool)
     .await
 }
 
+pub async fn complete_webhook_job(pool: &PgPool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE webhook_jobs SET status='done', locked_at=NULL, last_error=NULL, updated_at=now() WHERE id = $1",
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Records a failed attempt. With `retry_at` the job goes back to the queue,
+/// otherwise it is parked as `failed` for manual inspection.
+pub async fn fail_webhook_job(
+    pool: &PgPool,
+    id: i64,
+    error: &str,
+    retry_at: Option<DateTime<Utc>>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE webhook_jobs SET status = CASE WHEN $3::timestamptz IS NULL THEN 'failed' ELSE 'queued' END,\n             run_at = COALESCE($3, run_at), locked_at=NULL, last_error=$2, updated_at=now()\n         WHERE id = $1",
+    )
+    .bind(id)
+    .bind(error)
+    .bind(retry_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Puts jobs whose worker died mid-run (crash, redeploy) back on the queue.
+pub async fn requeue_stale_webhook_jobs(
+    pool: &PgPool,
+    stale_after_secs: i64,
+) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE webhook_jobs SET status='queued', locked_at=NULL, run_at=now(), updated_at=now()\n         WHERE status='running' AND locked_at < now() - make_interval(secs => $1)",
+    )
+    .bind(stale_after_secs as f64)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+/// Finds the paylink whose Solana Pay reference appears among the given
+/// account keys.
+pub async fn find_paylink_by_reference(
+    pool: &PgPool,
+    account_keys: &[String],
+) -> Result<Option<PayLink>, sqlx::Error> {
+    if account_keys.is_empty() {
+        return Ok(None);
+    }
+    sqlx::query_as::<_, PayLink>("SELECT * FROM paylinks WHERE reference = ANY($1) LIMIT 1")
+        .bind(account_keys)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Finds an open paylink still owed `amount` (or charging it per use), give or
+/// take its amount tolerance. The closest amount wins, then the newest
+/// paylink. Open-amount links are never matched by amount.
 pub async fn find_pending_by_match(
     pool: &PgPool,
     merchant_pubkey: &str,
@@ -247,7 +1380,7 @@ pub async fn find_pending_by_match(
     amount: i64,
 ) -> Result<Option<PayLink>, sqlx::Error> {
     sqlx::query_as::<_, PayLink>(
-        "SELECT * FROM paylinks WHERE merchant_pubkey=$1 AND mint=$2 AND expected_amount=$3 AND status='pending' AND expires_at > now() ORDER BY created_at DESC LIMIT 1",
+        "SELECT * FROM paylinks\n         WHERE merchant_pubkey=$1 AND mint=$2 AND status IN ('pending', 'partially_paid') AND expires_at > now()\n           AND NOT open_amount\n           AND abs(CASE WHEN multi_use THEN expected_amount ELSE expected_amount - amount_received END - $3)\n             <= CASE amount_tolerance->>'kind'\n               WHEN 'bps' THEN expected_amount * (amount_tolerance->>'value')::bigint / 10000\n               ELSE (amount_tolerance->>'value')::bigint END\n         ORDER BY abs(CASE WHEN multi_use THEN expected_amount ELSE expected_amount - amount_received END - $3) ASC, created_at DESC\n         LIMIT 1",
     )
     .bind(merchant_pubkey)
     .bind(mint)
@@ -255,3 +1388,20 @@ pub async fn find_pending_by_match(
     .fetch_optional(pool)
     .await
 }
+
+/// The merchant's only open paylink in `mint`, if there is exactly one. Used
+/// to surface a transfer whose amount matched nothing.
+pub async fn find_only_open_paylink(
+    pool: &PgPool,
+    merchant_pubkey: &str,
+    mint: &str,
+) -> Result<Option<PayLink>, sqlx::Error> {
+    let mut open = sqlx::query_as::<_, PayLink>(
+        "SELECT * FROM paylinks WHERE merchant_pubkey=$1 AND mint=$2 AND status IN ('pending', 'partially_paid') AND expires_at > now() AND NOT open_amount LIMIT 2",
+    )
+    .bind(merchant_pubkey)
+    .bind(mint)
+    .fetch_all(pool)
+    .await?;
+    Ok(if open.len() == 1 { open.pop() } else { None })
+}
@@ -7,6 +7,7 @@ pub enum AppError {
     BadRequest(String),
     NotFound(String),
     Unauthorized(String),
+    Forbidden(String),
     Db(sqlx::Error),
     Http(reqwest::Error),
     Other(String),
@@ -24,6 +25,7 @@ impl IntoResponse for AppError {
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
             AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
             AppError::Db(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
             AppError::Http(err) => (StatusCode::BAD_GATEWAY, err.to_string()),
             AppError::Other(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
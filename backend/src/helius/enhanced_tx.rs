@@ -1,12 +1,27 @@
 use serde_json::Value;
 
-use crate::{config::Config, error::AppError};
+use crate::{config::Config, error::AppError, helius::webhook_payload, solana};
 
 #[derive(Debug, Clone)]
 pub struct TokenTransfer {
     pub mint: String,
     pub amount: i64,
+    /// Account that received the tokens; usually a token account.
     pub destination: String,
+    /// Wallet that owns `destination`, when known.
+    pub owner: Option<String>,
+}
+
+impl TokenTransfer {
+    /// The wallet credited by this transfer, falling back to the raw
+    /// destination when the owner could not be resolved.
+    pub fn recipient(&self) -> &str {
+        self.owner.as_deref().unwrap_or(&self.destination)
+    }
+
+    pub fn pays(&self, wallet: &str) -> bool {
+        self.destination == wallet || self.owner.as_deref() == Some(wallet)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -23,58 +38,57 @@ pub struct TxView {
     pub memo_strings: Vec<String>,
     pub token_transfers: Vec<TokenTransfer>,
     pub native_transfers: Vec<NativeTransfer>,
+    /// Every account the transaction touched, used for Solana Pay reference
+    /// matching.
+    pub account_keys: Vec<String>,
     pub raw: Value,
 }
 
+impl TxView {
+    /// Total of `mint` credited to `wallet`, summed over every transfer so a
+    /// payment split across instructions counts in full. Native lamports count
+    /// for SOL paylinks when no wrapped-SOL transfer is present.
+    pub fn amount_received(&self, wallet: &str, mint: &str) -> i64 {
+        let tokens: i64 = self
+            .token_transfers
+            .iter()
+            .filter(|t| t.pays(wallet) && t.mint == mint)
+            .map(|t| t.amount)
+            .sum();
+        if tokens > 0 || !solana::is_native_mint(mint) {
+            return tokens;
+        }
+        self.native_transfers
+            .iter()
+            .filter(|t| t.destination == wallet)
+            .map(|t| t.lamports)
+            .sum()
+    }
+}
+
+/// Fetches the parsed transaction from the Helius enhanced API. `None` when
+/// Helius has no usable parsed form, e.g. a token transfer whose decimals it
+/// did not report; the caller then falls back to `getTransaction`.
 pub async fn fetch_enhanced_tx(
     client: &reqwest::Client,
     config: &Config,
     signature: &str,
-) -> Result<TxView, AppError> {
+) -> Result<Option<TxView>, AppError> {
     let base = crate::helius::base_url(config);
     let url = format!("{}/v0/transactions?api-key={}", base, config.helius_api_key);
 
     let body_a = serde_json::json!({"transactions": [signature]});
     let resp_a = client.post(&url).json(&body_a).send().await?;
-    if resp_a.status().is_success() {
-        let value: Value = resp_a.json().await?;
-        return Ok(extract_tx_view(signature, value));
-    }
-
-    let body_b = serde_json::json!([signature]);
-    let resp_b = client.post(&url).json(&body_b).send().await?;
-    let value: Value = resp_b.json().await?;
-    Ok(extract_tx_view(signature, value))
-}
-
-pub fn extract_tx_view(signature: &str, raw: Value) -> TxView {
-    let slot = raw
-        .get(0)
-        .and_then(|v| v.get("slot"))
-        .and_then(|v| v.as_i64())
-        .or_else(|| raw.get("slot").and_then(|v| v.as_i64()));
-    let timestamp = raw
-        .get(0)
-        .and_then(|v| v.get("timestamp"))
-        .and_then(|v| v.as_i64())
-        .or_else(|| raw.get("timestamp").and_then(|v| v.as_i64()));
-
-    let memo_strings = collect_memos(&raw);
-    let token_transfers = collect_token_transfers(&raw);
-    let native_transfers = collect_native_transfers(&raw);
-
-    TxView {
-        signature: signature.to_string(),
-        slot,
-        timestamp,
-        memo_strings,
-        token_transfers,
-        native_transfers,
-        raw,
-    }
+    let value: Value = if resp_a.status().is_success() {
+        resp_a.json().await?
+    } else {
+        let body_b = serde_json::json!([signature]);
+        client.post(&url).json(&body_b).send().await?.json().await?
+    };
+    Ok(webhook_payload::tx_view_from_payload(signature, &value))
 }
 
-fn collect_memos(value: &Value) -> Vec<String> {
+pub fn collect_memos(value: &Value) -> Vec<String> {
     let mut memos = Vec::new();
     let predicate = |s: &str| s.contains("paylink:") || s.contains("paylink=");
     collect_strings(value, &mut memos, &predicate);
@@ -83,10 +97,8 @@ fn collect_memos(value: &Value) -> Vec<String> {
 
 fn collect_strings<F: Fn(&str) -> bool>(value: &Value, out: &mut Vec<String>, filter: &F) {
     match value {
-        Value::String(s) => {
-            if filter(s) {
-                out.push(s.to_string());
-            }
+        Value::String(s) if filter(s) => {
+            out.push(s.to_string());
         }
         Value::Array(arr) => {
             for v in arr {
@@ -101,90 +113,3 @@ fn collect_strings<F: Fn(&str) -> bool>(value: &Value, out: &mut Vec<String>, fi
         _ => {}
     }
 }
-
-fn collect_token_transfers(raw: &Value) -> Vec<TokenTransfer> {
-    let mut out = Vec::new();
-    let transfers = raw
-        .get(0)
-        .and_then(|v| v.get("tokenTransfers"))
-        .and_then(|v| v.as_array())
-        .cloned()
-        .unwrap_or_else(|| {
-            raw.get("tokenTransfers")
-                .and_then(|v| v.as_array())
-                .cloned()
-                .unwrap_or_default()
-        });
-
-    for transfer in transfers {
-        let mint = transfer
-            .get("mint")
-            .and_then(|v| v.as_str())
-            .unwrap_or_default()
-            .to_string();
-        let destination = transfer
-            .get("toUserAccount")
-            .or_else(|| transfer.get("destination"))
-            .and_then(|v| v.as_str())
-            .unwrap_or_default()
-            .to_string();
-        let amount = transfer
-            .get("tokenAmount")
-            .and_then(|v| {
-                if let Some(s) = v.as_str() {
-                    s.parse::<i64>().ok()
-                } else if let Some(n) = v.as_i64() {
-                    Some(n)
-                } else {
-                    None
-                }
-            })
-            .unwrap_or(0);
-
-        if !mint.is_empty() && !destination.is_empty() && amount > 0 {
-            out.push(TokenTransfer {
-                mint,
-                amount,
-                destination,
-            });
-        }
-    }
-
-    out
-}
-
-fn collect_native_transfers(raw: &Value) -> Vec<NativeTransfer> {
-    let mut out = Vec::new();
-    let transfers = raw
-        .get(0)
-        .and_then(|v| v.get("nativeTransfers"))
-        .and_then(|v| v.as_array())
-        .cloned()
-        .unwrap_or_else(|| {
-            raw.get("nativeTransfers")
-                .and_then(|v| v.as_array())
-                .cloned()
-                .unwrap_or_default()
-        });
-
-    for transfer in transfers {
-        let destination = transfer
-            .get("toUserAccount")
-            .or_else(|| transfer.get("destination"))
-            .and_then(|v| v.as_str())
-            .unwrap_or_default()
-            .to_string();
-        let lamports = transfer
-            .get("amount")
-            .and_then(|v| v.as_i64())
-            .unwrap_or(0);
-        if !destination.is_empty() && lamports > 0 {
-            out.push(NativeTransfer {
-                lamports,
-                destination,
-            });
-        }
-    }
-
-    out
-}
@@ -1,5 +1,6 @@
 pub mod enhanced_tx;
 pub mod priority_fee;
+pub mod webhook_payload;
 
 use crate::config::Config;
 
@@ -0,0 +1,382 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::chain::rpc_tx;
+use crate::helius::enhanced_tx::{self, NativeTransfer, TokenTransfer, TxView};
+use crate::solana;
+use crate::util::amount::ui_to_base_units;
+
+/// One transaction from a Helius webhook delivery. Enhanced webhooks send the
+/// parsed form; raw webhooks send the `getTransaction` shape.
+#[derive(Debug, Clone)]
+pub enum WebhookTransaction {
+    Enhanced(EnhancedTransaction),
+    Raw,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParsedTransaction {
+    pub signature: String,
+    pub failed: bool,
+    pub tx: WebhookTransaction,
+    pub raw: Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnhancedTransaction {
+    pub signature: String,
+    pub slot: Option<i64>,
+    pub timestamp: Option<i64>,
+    pub transaction_error: Option<Value>,
+    pub token_transfers: Option<Vec<EnhancedTokenTransfer>>,
+    pub native_transfers: Option<Vec<EnhancedNativeTransfer>>,
+    pub instructions: Option<Vec<EnhancedInstruction>>,
+    #[serde(default)]
+    pub account_data: Vec<EnhancedAccountData>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnhancedTokenTransfer {
+    pub to_user_account: Option<String>,
+    pub to_token_account: Option<String>,
+    pub mint: String,
+    pub token_amount: Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnhancedNativeTransfer {
+    pub to_user_account: Option<String>,
+    pub amount: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnhancedInstruction {
+    pub program_id: String,
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    #[serde(default)]
+    pub data: String,
+    #[serde(default)]
+    pub inner_instructions: Vec<EnhancedInstruction>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnhancedAccountData {
+    #[serde(default)]
+    pub account: String,
+    #[serde(default)]
+    pub token_balance_changes: Vec<EnhancedTokenBalanceChange>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnhancedTokenBalanceChange {
+    pub mint: String,
+    pub raw_token_amount: RawTokenAmount,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawTokenAmount {
+    pub decimals: u8,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawTransaction {
+    pub meta: Option<RawMeta>,
+    pub transaction: RawTransactionBody,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawMeta {
+    pub err: Option<Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawTransactionBody {
+    pub signatures: Vec<String>,
+}
+
+/// Parses a webhook body (a batch array or a single object) into the
+/// transactions it describes. Items that are neither shape are skipped.
+pub fn parse_payload(payload: &Value) -> Vec<ParsedTransaction> {
+    let items: Vec<&Value> = match payload {
+        Value::Array(arr) => arr.iter().collect(),
+        Value::Object(_) => vec![payload],
+        _ => Vec::new(),
+    };
+
+    let mut out: Vec<ParsedTransaction> = Vec::new();
+    for item in items {
+        match parse_item(item) {
+            Some(parsed) => {
+                if !out.iter().any(|p| p.signature == parsed.signature) {
+                    out.push(parsed);
+                }
+            }
+            None => eprintln!("skipping unrecognised webhook item"),
+        }
+    }
+    out
+}
+
+fn parse_item(item: &Value) -> Option<ParsedTransaction> {
+    if item.get("signature").is_some() {
+        let tx: EnhancedTransaction = serde_json::from_value(item.clone()).ok()?;
+        if !solana::is_signature(&tx.signature) {
+            return None;
+        }
+        return Some(ParsedTransaction {
+            signature: tx.signature.clone(),
+            failed: tx.transaction_error.as_ref().is_some_and(|e| !e.is_null()),
+            tx: WebhookTransaction::Enhanced(tx),
+            raw: item.clone(),
+        });
+    }
+
+    let tx: RawTransaction = serde_json::from_value(item.clone()).ok()?;
+    let signature = tx.transaction.signatures.first()?.clone();
+    if !solana::is_signature(&signature) {
+        return None;
+    }
+    Some(ParsedTransaction {
+        signature,
+        failed: tx
+            .meta
+            .as_ref()
+            .and_then(|m| m.err.as_ref())
+            .is_some_and(|e| !e.is_null()),
+        tx: WebhookTransaction::Raw,
+        raw: item.clone(),
+    })
+}
+
+/// Builds a `TxView` straight from a stored webhook payload when it already
+/// carries the transaction data (enhanced transfers or a raw transaction), so
+/// no second call to the chain provider is needed.
+pub fn tx_view_from_payload(signature: &str, payload: &Value) -> Option<TxView> {
+    parse_payload(payload)
+        .into_iter()
+        .find(|p| p.signature == signature)
+        .and_then(|p| match p.tx {
+            WebhookTransaction::Enhanced(tx) if tx.has_transfer_data() => tx.to_tx_view(p.raw),
+            WebhookTransaction::Enhanced(_) => None,
+            WebhookTransaction::Raw => Some(rpc_tx::parse_transaction(signature, p.raw)),
+        })
+}
+
+impl EnhancedTransaction {
+    pub fn has_transfer_data(&self) -> bool {
+        self.token_transfers.is_some()
+            || self.native_transfers.is_some()
+            || self.instructions.is_some()
+    }
+
+    /// `None` when a token transfer's decimals are unknown, as its UI amount
+    /// cannot be converted to base units; the chain provider has the raw one.
+    pub fn to_tx_view(&self, raw: Value) -> Option<TxView> {
+        if self
+            .token_transfers
+            .iter()
+            .flatten()
+            .any(|t| self.decimals_for(t).is_none())
+        {
+            return None;
+        }
+        let token_transfers = self
+            .token_transfers
+            .iter()
+            .flatten()
+            .filter_map(|t| self.token_transfer(t))
+            .collect();
+        let native_transfers = self
+            .native_transfers
+            .iter()
+            .flatten()
+            .filter_map(|t| {
+                let destination = t.to_user_account.clone().filter(|d| !d.is_empty())?;
+                (t.amount > 0).then_some(NativeTransfer {
+                    lamports: t.amount,
+                    destination,
+                })
+            })
+            .collect();
+
+        let mut memo_strings = Vec::new();
+        for ix in self.instructions.iter().flatten() {
+            collect_instruction_memos(ix, &mut memo_strings);
+        }
+        for memo in enhanced_tx::collect_memos(&raw) {
+            if !memo_strings.contains(&memo) {
+                memo_strings.push(memo);
+            }
+        }
+
+        Some(TxView {
+            signature: self.signature.clone(),
+            slot: self.slot,
+            timestamp: self.timestamp,
+            memo_strings,
+            token_transfers,
+            native_transfers,
+            account_keys: self.account_keys(),
+            raw,
+        })
+    }
+
+    /// `accountData` lists every account in the transaction; instruction
+    /// accounts are added in case a payload trims it.
+    fn account_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = Vec::new();
+        let mut push = |key: &str| {
+            if !key.is_empty() && !keys.iter().any(|k| k == key) {
+                keys.push(key.to_string());
+            }
+        };
+        for data in &self.account_data {
+            push(&data.account);
+        }
+        let mut stack: Vec<&EnhancedInstruction> = self.instructions.iter().flatten().collect();
+        while let Some(ix) = stack.pop() {
+            for account in &ix.accounts {
+                push(account);
+            }
+            stack.extend(ix.inner_instructions.iter());
+        }
+        keys
+    }
+
+    fn token_transfer(&self, transfer: &EnhancedTokenTransfer) -> Option<TokenTransfer> {
+        let owner = transfer.to_user_account.clone().filter(|d| !d.is_empty());
+        let destination = transfer
+            .to_token_account
+            .clone()
+            .filter(|d| !d.is_empty())
+            .or_else(|| owner.clone())?;
+        let amount = match &transfer.token_amount {
+            Value::String(s) => ui_to_base_units(s, self.decimals_for(transfer)?),
+            Value::Number(n) => ui_to_base_units(&n.to_string(), self.decimals_for(transfer)?),
+            _ => None,
+        }?;
+        if transfer.mint.is_empty() || amount <= 0 {
+            return None;
+        }
+        Some(TokenTransfer {
+            mint: transfer.mint.clone(),
+            amount,
+            destination,
+            owner,
+        })
+    }
+
+    /// `tokenAmount` in enhanced transfers is a UI amount; the decimals come
+    /// from the matching entry in `accountData[].tokenBalanceChanges`.
+    fn decimals_for(&self, transfer: &EnhancedTokenTransfer) -> Option<u8> {
+        self.account_data
+            .iter()
+            .flat_map(|a| a.token_balance_changes.iter())
+            .find(|c| c.mint == transfer.mint)
+            .map(|c| c.raw_token_amount.decimals)
+    }
+}
+
+fn collect_instruction_memos(ix: &EnhancedInstruction, out: &mut Vec<String>) {
+    if solana::is_memo_program(&ix.program_id) {
+        if let Some(memo) = bs58::decode(&ix.data)
+            .into_vec()
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+        {
+            if !out.contains(&memo) {
+                out.push(memo);
+            }
+        }
+    }
+    for inner in &ix.inner_instructions {
+        collect_instruction_memos(inner, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SIG_A: &str = "4Jgs8aGj66iQCt88mNPXyjoJpPaCpYwurgym6AtJ2dBf9HZBqwV8ioNXwUw9Sq4iCHjVFPCDthZ6FbPoeeWjXZwh";
+    const SIG_B: &str = "5dxAVh64ycF3iAtJ7wUxRuLT9bf5g5XrhfNsb5uiyP7FwKEBZge7ELxfAEeMkd8j215ymUtWrYvGzRRHWzKHLX7X";
+    const MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    fn usdc_transfer(amount: Value, with_decimals: bool) -> Value {
+        let account_data = if with_decimals {
+            json!([{"account": "dest", "tokenBalanceChanges": [
+                {"mint": MINT, "rawTokenAmount": {"tokenAmount": "1500000", "decimals": 6}}
+            ]}])
+        } else {
+            json!([])
+        };
+        json!({
+            "signature": SIG_A,
+            "slot": 10,
+            "tokenTransfers": [{"toUserAccount": "owner", "toTokenAccount": "dest", "mint": MINT, "tokenAmount": amount}],
+            "accountData": account_data,
+        })
+    }
+
+    #[test]
+    fn parses_enhanced_and_raw_items_once_each() {
+        let payload = json!([
+            {"signature": SIG_A, "transactionError": null},
+            {"signature": SIG_A},
+            {"meta": {"err": {"InstructionError": [0, "Custom"]}}, "transaction": {"signatures": [SIG_B]}},
+            {"signature": "not-a-signature"},
+            "garbage",
+        ]);
+        let parsed = parse_payload(&payload);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].signature, SIG_A);
+        assert!(!parsed[0].failed);
+        assert!(matches!(parsed[0].tx, WebhookTransaction::Enhanced(_)));
+        assert_eq!(parsed[1].signature, SIG_B);
+        assert!(parsed[1].failed);
+        assert!(matches!(parsed[1].tx, WebhookTransaction::Raw));
+    }
+
+    #[test]
+    fn parses_a_single_object() {
+        let parsed = parse_payload(&json!({"signature": SIG_A, "transactionError": "boom"}));
+        assert_eq!(parsed.len(), 1);
+        assert!(parsed[0].failed);
+        assert!(parse_payload(&json!(42)).is_empty());
+    }
+
+    #[test]
+    fn converts_ui_token_amounts_with_known_decimals() {
+        let view = tx_view_from_payload(SIG_A, &usdc_transfer(json!(1.5), true)).unwrap();
+        assert_eq!(view.token_transfers.len(), 1);
+        assert_eq!(view.token_transfers[0].amount, 1_500_000);
+        assert_eq!(view.token_transfers[0].destination, "dest");
+        assert_eq!(view.token_transfers[0].owner.as_deref(), Some("owner"));
+
+        let view = tx_view_from_payload(SIG_A, &usdc_transfer(json!("2"), true)).unwrap();
+        assert_eq!(view.token_transfers[0].amount, 2_000_000);
+    }
+
+    #[test]
+    fn leaves_unknown_decimals_to_the_chain_provider() {
+        assert!(tx_view_from_payload(SIG_A, &usdc_transfer(json!(1), false)).is_none());
+    }
+
+    #[test]
+    fn payload_without_transfer_data_has_no_view() {
+        assert!(tx_view_from_payload(SIG_A, &json!({"signature": SIG_A})).is_none());
+        assert!(tx_view_from_payload(SIG_B, &usdc_transfer(json!(1), true)).is_none());
+    }
+}
@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+use crate::{app::AppState, db::queries};
+
+pub fn spawn(state: AppState) {
+    tokio::spawn(run_sweeper(state));
+}
+
+/// Expires overdue paylinks in batches. Each `PAYLINK_EXPIRED` event also
+/// queues the merchant webhook, so there is nothing else to notify here.
+async fn run_sweeper(state: AppState) {
+    let interval = Duration::from_secs(state.config.expiry_sweep_interval_secs.max(1));
+    let batch_size = state.config.expiry_sweep_batch_size.max(1);
+    loop {
+        loop {
+            match queries::expire_due_paylinks(&state.db, batch_size).await {
+                Ok(expired) => {
+                    if !expired.is_empty() {
+                        println!("expired {} paylinks", expired.len());
+                    }
+                    if (expired.len() as i64) < batch_size {
+                        break;
+                    }
+                }
+                Err(err) => {
+                    eprintln!("expiry sweep failed: {:?}", err);
+                    break;
+                }
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
@@ -0,0 +1,288 @@
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::{
+    app::AppState,
+    db::{models::MerchantWebhookDelivery, queries},
+    util::{net, webhook_auth},
+};
+
+pub const EVENT_HEADER: &str = "x-receiptless-event";
+pub const DELIVERY_HEADER: &str = "x-receiptless-delivery";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const REAPER_INTERVAL: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const STALE_AFTER_SECS: i64 = 300;
+const BACKOFF_BASE_SECS: i64 = 10;
+const BACKOFF_MAX_SECS: i64 = 6 * 3600;
+
+pub fn spawn(state: AppState) {
+    tokio::spawn(run_reaper(state.clone()));
+    tokio::spawn(run_worker(state));
+}
+
+async fn run_reaper(state: AppState) {
+    loop {
+        match queries::requeue_stale_merchant_webhooks(&state.db, STALE_AFTER_SECS).await {
+            Ok(0) => {}
+            Ok(n) => println!("requeued {} stale merchant webhook deliveries", n),
+            Err(err) => eprintln!("merchant webhook reaper error: {:?}", err),
+        }
+        tokio::time::sleep(REAPER_INTERVAL).await;
+    }
+}
+
+async fn run_worker(state: AppState) {
+    loop {
+        match queries::claim_merchant_webhook(&state.db).await {
+            Ok(Some((delivery, secret))) => deliver(&state, delivery, secret).await,
+            Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+            Err(err) => {
+                eprintln!("merchant webhook worker failed to claim delivery: {:?}", err);
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+/// Makes one delivery attempt and records the outcome: delivered, retried
+/// after a backoff, or dead-lettered once `MERCHANT_WEBHOOK_MAX_ATTEMPTS` are
+/// used up. URLs that resolve to private addresses fail like any other error.
+pub async fn deliver(state: &AppState, delivery: MerchantWebhookDelivery, secret: Option<String>) {
+    let (status_code, error) = match client_for(state, &delivery.url).await {
+        Ok(http) => send(&http, &delivery, secret.as_deref()).await,
+        Err(err) => (None, Some(err)),
+    };
+
+    let retry_at = match &error {
+        Some(message) => {
+            let max_attempts = state.config.merchant_webhook_max_attempts;
+            eprintln!(
+                "merchant webhook {} ({}) attempt {}/{} failed: {}",
+                delivery.id, delivery.event_type, delivery.attempts, max_attempts, message
+            );
+            (delivery.attempts < max_attempts)
+                .then(|| Utc::now() + chrono::Duration::seconds(backoff_secs(delivery.attempts)))
+        }
+        None => None,
+    };
+    if error.is_some() && retry_at.is_none() {
+        eprintln!("merchant webhook {} dead-lettered", delivery.id);
+    }
+
+    if let Err(err) = queries::finish_merchant_webhook(
+        &state.db,
+        delivery.id,
+        status_code,
+        error.as_deref(),
+        retry_at,
+    )
+    .await
+    {
+        eprintln!("failed to record merchant webhook {} result: {:?}", delivery.id, err);
+    }
+}
+
+/// A client pinned to the addresses the URL was checked against, so a DNS
+/// answer that changes after the check cannot redirect the request to a
+/// private address. Redirects are not followed for the same reason.
+async fn client_for(state: &AppState, url: &str) -> Result<reqwest::Client, String> {
+    let url = reqwest::Url::parse(url).map_err(|e| format!("invalid webhook url: {}", e))?;
+    let addrs = net::resolve_public_url(&url, state.config.merchant_webhook_allow_private).await?;
+    let mut builder = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none());
+    if let Some(host) = url.host_str() {
+        builder = builder.resolve_to_addrs(host, &addrs);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// Posts the event, signed the same way inbound webhooks are verified in
+/// `hmac` mode: `x-webhook-signature: t=<unix>,v1=<hex hmac-sha256 of "<t>.<body>">`.
+async fn send(
+    http: &reqwest::Client,
+    delivery: &MerchantWebhookDelivery,
+    secret: Option<&str>,
+) -> (Option<i32>, Option<String>) {
+    let body = delivery.payload.to_string();
+    let mut request = http
+        .post(&delivery.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event_type)
+        .header(DELIVERY_HEADER, delivery.id.to_string());
+    if let Some(secret) = secret {
+        let timestamp = Utc::now().timestamp();
+        let signature = webhook_auth::sign(secret, timestamp, body.as_bytes());
+        request = request.header(
+            webhook_auth::SIGNATURE_HEADER,
+            format!("t={},v1={}", timestamp, signature),
+        );
+    }
+
+    match request.body(body).send().await {
+        Ok(res) if res.status().is_success() => (Some(res.status().as_u16() as i32), None),
+        Ok(res) => (
+            Some(res.status().as_u16() as i32),
+            Some(format!("endpoint returned {}", res.status())),
+        ),
+        Err(err) => (None, Some(err.to_string())),
+    }
+}
+
+fn backoff_secs(attempts: i32) -> i64 {
+    let exp = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    (BACKOFF_BASE_SECS * 2i64.pow(exp)).min(BACKOFF_MAX_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU16, Ordering},
+        Arc, Mutex,
+    };
+
+    use axum::{body::Bytes, extract::State, http::HeaderMap, http::StatusCode, routing::post, Router};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{db::models::Merchant, solana, test_support};
+
+    const SECRET: &str = "whsec_test";
+
+    /// Stands in for a merchant endpoint: records each request and answers
+    /// with the configured status.
+    #[derive(Clone, Default)]
+    struct Endpoint {
+        status: Arc<AtomicU16>,
+        requests: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+    }
+
+    async fn record(State(endpoint): State<Endpoint>, headers: HeaderMap, body: Bytes) -> StatusCode {
+        endpoint.requests.lock().unwrap().push((headers, body));
+        StatusCode::from_u16(endpoint.status.load(Ordering::SeqCst)).unwrap()
+    }
+
+    async fn serve(endpoint: Endpoint) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/hook", post(record)).with_state(endpoint);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}/hook", addr)
+    }
+
+    async fn next_delivery(state: &AppState, paylink_id: Uuid, event_type: &str) -> MerchantWebhookDelivery {
+        let mut tx = state.db.begin().await.unwrap();
+        queries::insert_activity_event_tx(&mut tx, paylink_id, event_type, serde_json::json!({}))
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+        let (delivery, secret) = queries::claim_merchant_webhook(&state.db)
+            .await
+            .unwrap()
+            .expect("delivery queued");
+        assert_eq!(secret.as_deref(), Some(SECRET));
+        deliver(state, delivery.clone(), secret).await;
+        sqlx::query_as("SELECT * FROM merchant_webhook_deliveries WHERE id = $1")
+            .bind(delivery.id)
+            .fetch_one(&state.db)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn delivers_signs_retries_and_dead_letters() {
+        let Some(mut app) = test_support::app().await else {
+            return;
+        };
+        app.state.config.merchant_webhook_allow_private = true;
+        app.state.config.merchant_webhook_max_attempts = 2;
+        let state = &app.state;
+
+        let endpoint = Endpoint::default();
+        endpoint.status.store(200, Ordering::SeqCst);
+        let url = serve(endpoint.clone()).await;
+
+        let merchant = Merchant {
+            id: Uuid::new_v4(),
+            name: "test".to_string(),
+            created_at: Utc::now(),
+            webhook_url: None,
+            webhook_secret: None,
+        };
+        let mut dbtx = state.db.begin().await.unwrap();
+        queries::insert_merchant(&mut dbtx, &merchant).await.unwrap();
+        dbtx.commit().await.unwrap();
+        queries::set_merchant_webhook(&state.db, merchant.id, Some(&url), Some(SECRET))
+            .await
+            .unwrap();
+        let mut paylink = test_support::paylink(test_support::FIXTURE_MERCHANT, solana::WRAPPED_SOL_MINT, 1);
+        paylink.merchant_id = Some(merchant.id);
+        let mut dbtx = state.db.begin().await.unwrap();
+        queries::insert_paylink(&mut dbtx, &paylink).await.unwrap();
+        dbtx.commit().await.unwrap();
+
+        // Delivered and signed.
+        let delivery = next_delivery(state, paylink.id, "PAYLINK_CREATED").await;
+        assert_eq!(delivery.status, "delivered");
+        assert_eq!(delivery.last_status_code, Some(200));
+        {
+            let requests = endpoint.requests.lock().unwrap();
+            assert_eq!(requests.len(), 1);
+            let (headers, body) = &requests[0];
+            assert_eq!(headers[EVENT_HEADER], "PAYLINK_CREATED");
+            assert_eq!(headers[DELIVERY_HEADER], delivery.id.to_string().as_str());
+            let signature = headers[webhook_auth::SIGNATURE_HEADER].to_str().unwrap();
+            let (t, v1) = signature.split_once(",v1=").unwrap();
+            let timestamp: i64 = t.strip_prefix("t=").unwrap().parse().unwrap();
+            assert_eq!(v1, webhook_auth::sign(SECRET, timestamp, body));
+            let payload: serde_json::Value = serde_json::from_slice(body).unwrap();
+            assert_eq!(payload["type"], "PAYLINK_CREATED");
+            assert_eq!(payload["data"]["paylinkId"], paylink.id.to_string());
+        }
+
+        // A failure is retried after the backoff, then dead-lettered.
+        endpoint.status.store(500, Ordering::SeqCst);
+        let delivery = next_delivery(state, paylink.id, "PAYLINK_CANCELLED").await;
+        assert_eq!(delivery.status, "pending");
+        assert_eq!(delivery.last_status_code, Some(500));
+        let wait = (delivery.run_at - Utc::now()).num_seconds();
+        assert!((BACKOFF_BASE_SECS - 2..=BACKOFF_BASE_SECS).contains(&wait), "backoff {}", wait);
+
+        sqlx::query("UPDATE merchant_webhook_deliveries SET run_at = now() WHERE id = $1")
+            .bind(delivery.id)
+            .execute(&state.db)
+            .await
+            .unwrap();
+        let (retry, secret) = queries::claim_merchant_webhook(&state.db).await.unwrap().unwrap();
+        assert_eq!(retry.id, delivery.id);
+        assert_eq!(retry.attempts, 2);
+        deliver(state, retry, secret).await;
+        let status: String = sqlx::query_scalar("SELECT status FROM merchant_webhook_deliveries WHERE id = $1")
+            .bind(delivery.id)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        assert_eq!(status, "dead");
+        assert_eq!(endpoint.requests.lock().unwrap().len(), 3);
+
+        // Private addresses are refused unless explicitly allowed.
+        app.state.config.merchant_webhook_allow_private = false;
+        let delivery = next_delivery(&app.state, paylink.id, "PAYLINK_EXPIRED").await;
+        assert_eq!(delivery.status, "pending");
+        assert!(delivery.last_error.unwrap().contains("non-public"));
+        assert_eq!(endpoint.requests.lock().unwrap().len(), 3);
+
+        app.cleanup().await;
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff_secs(1), BACKOFF_BASE_SECS);
+        assert_eq!(backoff_secs(2), BACKOFF_BASE_SECS * 2);
+        assert_eq!(backoff_secs(4), BACKOFF_BASE_SECS * 8);
+        assert_eq!(backoff_secs(30), BACKOFF_MAX_SECS);
+    }
+}
@@ -0,0 +1,13 @@
+pub mod expiry;
+pub mod merchant_webhook;
+pub mod subscriptions;
+pub mod webhook;
+
+use crate::app::AppState;
+
+pub fn spawn_all(state: &AppState) {
+    webhook::spawn(state.clone());
+    merchant_webhook::spawn(state.clone());
+    expiry::spawn(state.clone());
+    subscriptions::spawn(state.clone());
+}
@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use sqlx::Connection;
+use uuid::Uuid;
+
+use crate::{app::AppState, db::queries, error::AppError, routes::subscriptions};
+
+const BATCH_SIZE: i64 = 100;
+
+pub fn spawn(state: AppState) {
+    tokio::spawn(run_scheduler(state));
+}
+
+/// Issues the paylink for every subscription cycle that has started. A
+/// subscription that fell several cycles behind gets one per pass, so the
+/// loop runs until nothing is due. A subscription whose cycle cannot be
+/// issued is skipped until the next sweep.
+async fn run_scheduler(state: AppState) {
+    let interval = Duration::from_secs(state.config.subscription_sweep_interval_secs.max(1));
+    loop {
+        let mut failed = Vec::new();
+        loop {
+            match issue_due_cycles(&state, &mut failed).await {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(err) => {
+                    eprintln!("subscription scheduler failed: {:?}", err);
+                    break;
+                }
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Issues the next cycle of up to `BATCH_SIZE` due subscriptions, leaving out
+/// those in `failed`. Each cycle is issued under its own savepoint, so one
+/// that fails is rolled back, logged and added to `failed` without holding
+/// up the rest. Returns the number of subscriptions attempted.
+async fn issue_due_cycles(state: &AppState, failed: &mut Vec<Uuid>) -> Result<usize, AppError> {
+    let mut dbtx = state.db.begin().await?;
+    let due = queries::lock_due_subscriptions(&mut dbtx, BATCH_SIZE, failed).await?;
+    let mut issued = 0;
+    for subscription in &due {
+        let mut savepoint = dbtx.begin().await?;
+        match subscriptions::issue_cycle(state, &mut savepoint, subscription).await {
+            Ok(_) => {
+                savepoint.commit().await?;
+                issued += 1;
+            }
+            Err(err) => {
+                savepoint.rollback().await?;
+                eprintln!(
+                    "could not issue cycle {} of subscription {}: {:?}",
+                    subscription.next_cycle_index, subscription.id, err
+                );
+                failed.push(subscription.id);
+            }
+        }
+    }
+    dbtx.commit().await?;
+    if issued > 0 {
+        println!("issued {} subscription cycles", issued);
+    }
+    Ok(due.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+    use crate::{
+        db::models::{IntervalUnit, Merchant, Subscription, SubscriptionStatus},
+        solana, test_support,
+    };
+
+    #[tokio::test]
+    async fn a_failing_subscription_does_not_block_the_others() {
+        let Some(app) = test_support::app().await else {
+            return;
+        };
+        let state = &app.state;
+        let merchant = Merchant {
+            id: Uuid::new_v4(),
+            name: "test".to_string(),
+            created_at: Utc::now(),
+            webhook_url: None,
+            webhook_secret: None,
+        };
+        let mut dbtx = state.db.begin().await.unwrap();
+        queries::insert_merchant(&mut dbtx, &merchant).await.unwrap();
+        dbtx.commit().await.unwrap();
+
+        let template = test_support::paylink(test_support::FIXTURE_MERCHANT, solana::WRAPPED_SOL_MINT, 1_000);
+        let anchor_at = Utc::now() - Duration::hours(1);
+        let subscription = |interval_unit, interval_count| Subscription {
+            id: Uuid::new_v4(),
+            merchant_id: merchant.id,
+            merchant_pubkey: template.merchant_pubkey.clone(),
+            amount: template.expected_amount,
+            mint: template.mint.clone(),
+            interval_unit,
+            interval_count,
+            anchor_at,
+            trial_ends_at: None,
+            status: SubscriptionStatus::Active,
+            customer_ref: None,
+            memo_policy: template.memo_policy.clone(),
+            receipt_fields_policy: template.receipt_fields_policy.clone(),
+            amount_tolerance: template.amount_tolerance,
+            next_cycle_index: 0,
+            next_cycle_at: anchor_at,
+            created_at: anchor_at,
+            cancelled_at: None,
+        };
+        // The end of the first cycle of this one is past any representable
+        // date, so issuing it fails.
+        let overflowing = subscription(IntervalUnit::Year, i32::MAX);
+        let daily = subscription(IntervalUnit::Day, 1);
+        let mut dbtx = state.db.begin().await.unwrap();
+        queries::insert_subscription(&mut dbtx, &overflowing).await.unwrap();
+        queries::insert_subscription(&mut dbtx, &daily).await.unwrap();
+        dbtx.commit().await.unwrap();
+
+        let mut failed = Vec::new();
+        assert_eq!(issue_due_cycles(state, &mut failed).await.unwrap(), 2);
+        assert_eq!(failed, vec![overflowing.id]);
+        assert_eq!(issue_due_cycles(state, &mut failed).await.unwrap(), 0);
+
+        let next_index = |id| {
+            sqlx::query_scalar::<_, i32>("SELECT next_cycle_index FROM subscriptions WHERE id = $1")
+                .bind(id)
+                .fetch_one(&state.db)
+        };
+        assert_eq!(next_index(daily.id).await.unwrap(), 1);
+        assert_eq!(next_index(overflowing.id).await.unwrap(), 0);
+
+        app.cleanup().await;
+    }
+}
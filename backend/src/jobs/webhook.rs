@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::{app::AppState, db::{models::WebhookJob, queries}, routes::helius_webhook};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const REAPER_INTERVAL: Duration = Duration::from_secs(60);
+const STALE_AFTER_SECS: i64 = 300;
+const BACKOFF_BASE_SECS: i64 = 5;
+const BACKOFF_MAX_SECS: i64 = 3600;
+
+pub fn spawn(state: AppState) {
+    let workers = state.config.webhook_workers.max(1);
+    println!("⚙️  Starting {} webhook job workers", workers);
+
+    tokio::spawn(run_reaper(state.clone()));
+    for worker_id in 0..workers {
+        tokio::spawn(run_worker(state.clone(), worker_id));
+    }
+}
+
+async fn run_reaper(state: AppState) {
+    loop {
+        match queries::requeue_stale_webhook_jobs(&state.db, STALE_AFTER_SECS).await {
+            Ok(0) => {}
+            Ok(n) => println!("requeued {} stale webhook jobs", n),
+            Err(err) => eprintln!("webhook job reaper error: {:?}", err),
+        }
+        tokio::time::sleep(REAPER_INTERVAL).await;
+    }
+}
+
+async fn run_worker(state: AppState, worker_id: usize) {
+    loop {
+        match queries::claim_webhook_job(&state.db).await {
+            Ok(Some(job)) => run_job(&state, job).await,
+            Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+            Err(err) => {
+                eprintln!("webhook worker {} failed to claim job: {:?}", worker_id, err);
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+pub async fn run_job(state: &AppState, job: WebhookJob) {
+    let result = helius_webhook::process_signature(state, &job.signature).await;
+    let recorded = match result {
+        Ok(()) => queries::complete_webhook_job(&state.db, job.id).await,
+        Err(err) => {
+            let message = format!("{:?}", err);
+            let retry_at = if job.attempts < job.max_attempts {
+                Some(Utc::now() + chrono::Duration::seconds(backoff_secs(job.attempts)))
+            } else {
+                None
+            };
+            eprintln!(
+                "webhook job {} ({}) attempt {}/{} failed: {}",
+                job.id, job.signature, job.attempts, job.max_attempts, message
+            );
+            queries::fail_webhook_job(&state.db, job.id, &message, retry_at).await
+        }
+    };
+    if let Err(err) = recorded {
+        eprintln!("failed to record webhook job {} result: {:?}", job.id, err);
+    }
+}
+
+fn backoff_secs(attempts: i32) -> i64 {
+    let exp = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    (BACKOFF_BASE_SECS * 2i64.pow(exp)).min(BACKOFF_MAX_SECS)
+}
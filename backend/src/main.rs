@@ -1,12 +1,21 @@
 mod app;
+mod auth;
+mod chain;
 mod config;
 mod db;
 mod error;
 mod helius;
+mod jobs;
+mod pricing;
 mod privacy;
 mod routes;
+mod solana;
+#[cfg(test)]
+mod test_support;
 mod util;
 
+use std::sync::Arc;
+
 use app::AppState;
 use dotenvy::dotenv;
 
@@ -18,6 +27,7 @@ async fn main() -> Result<(), error::AppError> {
 
     let config = config::Config::from_env();
     println!("📋 Config loaded");
+    println!("   Environment: {}", config.environment.name());
     println!("   Port: {}", config.port);
     println!("   Cluster: {}", config.helius_cluster);
 
@@ -37,14 +47,32 @@ async fn main() -> Result<(), error::AppError> {
 
     let http = reqwest::Client::new();
     let rail = privacy::rail::RailSelector::new(&config.privacy_rail);
+    let chain = chain::from_config(&config, http.clone()).map_err(|e| {
+        eprintln!("❌ Chain provider failed: {:?}", e);
+        e
+    })?;
+    println!("   Chain provider: {}", chain.name());
+    let owners = Arc::new(chain::owners::OwnerResolver::new(chain.clone()));
+    let tokens = Arc::new(chain::tokens::TokenRegistry::load(
+        chain.clone(),
+        &config.helius_cluster,
+        &config.token_registry_path,
+    ));
+    let prices = pricing::from_config(&config, http);
+    println!("   Price source: {}", prices.name());
 
     let state = AppState {
         db,
-        http,
         config,
         rail,
+        chain,
+        owners,
+        tokens,
+        prices,
     };
 
+    jobs::spawn_all(&state);
+
     println!("🌐 Starting HTTP server...");
     app::run(state).await
 }
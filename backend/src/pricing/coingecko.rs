@@ -0,0 +1,51 @@
+use serde_json::Value;
+
+use crate::db::models::FiatCurrency;
+use crate::error::AppError;
+use crate::privacy::rail::BoxFuture;
+
+use super::{PriceSource, Rate};
+
+/// CoinGecko's `simple/price` endpoint. The API key is optional and sent as
+/// a demo key.
+pub struct CoinGeckoSource {
+    http: reqwest::Client,
+    api_url: String,
+    api_key: Option<String>,
+}
+
+impl CoinGeckoSource {
+    pub fn new(http: reqwest::Client, api_url: String, api_key: Option<String>) -> Self {
+        Self { http, api_url, api_key }
+    }
+}
+
+impl PriceSource for CoinGeckoSource {
+    fn name(&self) -> &'static str {
+        "coingecko"
+    }
+
+    fn price<'a>(&'a self, asset: &'a str, currency: FiatCurrency) -> BoxFuture<'a, Result<Rate, AppError>> {
+        Box::pin(async move {
+            let coin = match asset {
+                "SOL" => "solana",
+                "USDC" => "usd-coin",
+                _ => return Err(AppError::BadRequest(format!("coingecko has no price for {}", asset))),
+            };
+            let vs = currency.as_str().to_ascii_lowercase();
+            let mut request = self
+                .http
+                .get(format!("{}/simple/price", self.api_url.trim_end_matches('/')))
+                .query(&[("ids", coin), ("vs_currencies", vs.as_str()), ("precision", "full")]);
+            if let Some(key) = &self.api_key {
+                request = request.header("x-cg-demo-api-key", key);
+            }
+            let body: Value = request.send().await?.error_for_status()?.json().await?;
+            body.get(coin)
+                .and_then(|v| v.get(&vs))
+                .and_then(|v| v.as_number())
+                .and_then(|n| Rate::parse(&n.to_string()))
+                .ok_or_else(|| AppError::Other(format!("coingecko returned no price for {}", asset)))
+        })
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use crate::db::models::FiatCurrency;
+use crate::error::AppError;
+use crate::privacy::rail::BoxFuture;
+
+use super::{PriceSource, Rate};
+
+/// Fixed rates from `PRICE_STATIC_RATES` ("SOL/USD=150,USDC/EUR=0.92"), for
+/// local development and tests.
+pub struct FixedRates {
+    rates: HashMap<(String, FiatCurrency), Rate>,
+}
+
+impl FixedRates {
+    pub fn parse(spec: &str) -> Self {
+        let mut rates = HashMap::new();
+        for entry in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let parsed = entry.split_once('=').and_then(|(pair, rate)| {
+                let (asset, currency) = pair.split_once('/')?;
+                Some((
+                    asset.trim().to_ascii_uppercase(),
+                    FiatCurrency::parse(currency)?,
+                    Rate::parse(rate)?,
+                ))
+            });
+            match parsed {
+                Some((asset, currency, rate)) => {
+                    rates.insert((asset, currency), rate);
+                }
+                None => eprintln!("ignoring invalid static price {:?}", entry),
+            }
+        }
+        Self { rates }
+    }
+}
+
+impl PriceSource for FixedRates {
+    fn name(&self) -> &'static str {
+        "static"
+    }
+
+    fn price<'a>(&'a self, asset: &'a str, currency: FiatCurrency) -> BoxFuture<'a, Result<Rate, AppError>> {
+        Box::pin(async move {
+            self.rates
+                .get(&(asset.to_string(), currency))
+                .copied()
+                .ok_or_else(|| AppError::BadRequest(format!("no static {} price for {}", currency, asset)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn parses_rates_and_skips_invalid_entries() {
+        let rates = FixedRates::parse(" SOL/USD=150, usdc/eur=0.92,,bad,SOL/GBP=1,BONK/USD=abc,JUP/USD=0 ");
+        assert_eq!(rates.rates.len(), 2);
+        assert_eq!(rates.price("SOL", FiatCurrency::Usd).await.unwrap(), Rate::parse("150").unwrap());
+        assert_eq!(rates.price("USDC", FiatCurrency::Eur).await.unwrap(), Rate::parse("0.92").unwrap());
+        assert!(matches!(
+            rates.price("SOL", FiatCurrency::Eur).await,
+            Err(AppError::BadRequest(_))
+        ));
+        assert!(FixedRates::parse("").rates.is_empty());
+    }
+}
@@ -0,0 +1,56 @@
+use serde_json::Value;
+
+use crate::db::models::FiatCurrency;
+use crate::error::AppError;
+use crate::privacy::rail::BoxFuture;
+use crate::solana;
+
+use super::{PriceSource, Rate};
+
+/// Jupiter's price API, which quotes mainnet mints against USDC. Only USD
+/// prices are offered; devnet tokens are priced as their mainnet twins.
+pub struct JupiterSource {
+    http: reqwest::Client,
+    price_url: String,
+}
+
+impl JupiterSource {
+    pub fn new(http: reqwest::Client, price_url: String) -> Self {
+        Self { http, price_url }
+    }
+}
+
+impl PriceSource for JupiterSource {
+    fn name(&self) -> &'static str {
+        "jupiter"
+    }
+
+    fn price<'a>(&'a self, asset: &'a str, currency: FiatCurrency) -> BoxFuture<'a, Result<Rate, AppError>> {
+        Box::pin(async move {
+            if currency != FiatCurrency::Usd {
+                return Err(AppError::BadRequest(format!("jupiter has no {} prices", currency)));
+            }
+            let mint = match asset {
+                "SOL" => solana::WRAPPED_SOL_MINT,
+                "USDC" => solana::USDC_MAINNET_MINT,
+                _ => return Err(AppError::BadRequest(format!("jupiter has no price for {}", asset))),
+            };
+            let body: Value = self
+                .http
+                .get(&self.price_url)
+                .query(&[("ids", mint)])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            let price = body.pointer(&format!("/data/{}/price", mint));
+            let rate = match price {
+                Some(Value::String(s)) => Rate::parse(s),
+                Some(Value::Number(n)) => Rate::parse(&n.to_string()),
+                _ => None,
+            };
+            rate.ok_or_else(|| AppError::Other(format!("jupiter returned no price for {}", asset)))
+        })
+    }
+}
@@ -0,0 +1,258 @@
+pub mod coingecko;
+pub mod fixed;
+pub mod jupiter;
+pub mod pyth;
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::chain::tokens::TokenInfo;
+use crate::config::Config;
+use crate::db::models::{FiatCurrency, PriceQuote};
+use crate::error::AppError;
+use crate::privacy::rail::BoxFuture;
+use crate::util::amount::base_units_to_ui;
+
+/// Digits kept from a quoted price; more precision than any source reports.
+const MAX_RATE_DIGITS: usize = 18;
+
+/// Source of token prices in fiat. Pyth is the default; Jupiter, CoinGecko or
+/// a fixed table of rates can be swapped in via `PRICE_SOURCE`.
+pub trait PriceSource: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// Price of one whole `asset` token ("SOL", "USDC") in `currency`.
+    fn price<'a>(&'a self, asset: &'a str, currency: FiatCurrency) -> BoxFuture<'a, Result<Rate, AppError>>;
+}
+
+pub fn from_config(config: &Config, http: reqwest::Client) -> Arc<dyn PriceSource> {
+    match config.price_source.as_str() {
+        "jupiter" => Arc::new(jupiter::JupiterSource::new(http, config.jupiter_price_url.clone())),
+        "coingecko" => Arc::new(coingecko::CoinGeckoSource::new(
+            http,
+            config.coingecko_api_url.clone(),
+            config.coingecko_api_key.clone(),
+        )),
+        "static" => Arc::new(fixed::FixedRates::parse(&config.price_static_rates)),
+        _ => Arc::new(pyth::PythSource::new(http, config.pyth_hermes_url.clone())),
+    }
+}
+
+/// Converts `fiat_amount` (in cents) into base units of `mint` at the
+/// current price. Mints are priced by their registry symbol. The quote
+/// expires after `ttl_secs` when given.
+pub async fn quote(
+    prices: &dyn PriceSource,
+    mint: &TokenInfo,
+    currency: FiatCurrency,
+    fiat_amount: i64,
+    ttl_secs: Option<i32>,
+) -> Result<PriceQuote, AppError> {
+    let asset = mint
+        .symbol
+        .as_deref()
+        .ok_or_else(|| AppError::BadRequest(format!("no fiat price for mint {}", mint.address)))?;
+    let rate = prices.price(asset, currency).await?;
+    let amount = rate
+        .token_amount(fiat_amount, currency.decimals(), mint.decimals)
+        .ok_or_else(|| AppError::BadRequest("fiat amount is out of range".to_string()))?;
+    if amount <= 0 {
+        return Err(AppError::BadRequest(format!(
+            "{} {} is less than one base unit of {}",
+            base_units_to_ui(fiat_amount, currency.decimals()),
+            currency,
+            asset
+        )));
+    }
+    let quoted_at = Utc::now();
+    Ok(PriceQuote {
+        source: prices.name().to_string(),
+        asset: asset.to_string(),
+        currency,
+        fiat_amount,
+        rate: rate.to_string(),
+        amount,
+        quoted_at,
+        expires_at: ttl_secs.map(|ttl| quoted_at + Duration::seconds(ttl as i64)),
+    })
+}
+
+/// A positive decimal price, `mantissa × 10^expo`, kept exact so the token
+/// amount does not depend on float rounding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    mantissa: i64,
+    expo: i32,
+}
+
+impl Rate {
+    pub fn new(mantissa: i64, expo: i32) -> Option<Self> {
+        (mantissa > 0 && (-40..=40).contains(&expo)).then_some(Self { mantissa, expo })
+    }
+
+    /// Parses a decimal or exponent-notation price ("151.23", "1.5e-5").
+    /// Digits past the 18th significant one are dropped.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (mantissa, exponent) = match value.find(['e', 'E']) {
+            Some(idx) => (&value[..idx], value[idx + 1..].parse::<i32>().ok()?),
+            None => (value, 0),
+        };
+        let mantissa = mantissa.trim_start_matches('+');
+        let (whole, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if !whole.chars().chain(frac.chars()).all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let digits = format!("{}{}", whole, frac);
+        let digits = digits.trim_start_matches('0');
+        let mut expo = exponent.checked_sub(frac.len() as i32)?;
+        let digits = if digits.len() > MAX_RATE_DIGITS {
+            expo = expo.checked_add((digits.len() - MAX_RATE_DIGITS) as i32)?;
+            &digits[..MAX_RATE_DIGITS]
+        } else {
+            digits
+        };
+        Self::new(digits.parse().ok()?, expo)
+    }
+
+    /// `self / other`, for crossing two prices quoted in a common currency.
+    pub fn checked_div(&self, other: &Rate) -> Option<Rate> {
+        const SCALE: u32 = 18;
+        let mut quotient = (self.mantissa as i128)
+            .checked_mul(10i128.pow(SCALE))?
+            .checked_div(other.mantissa as i128)?;
+        let mut expo = self.expo - other.expo - SCALE as i32;
+        while quotient > i64::MAX as i128 || (quotient != 0 && quotient % 10 == 0) {
+            quotient /= 10;
+            expo += 1;
+        }
+        Self::new(quotient as i64, expo)
+    }
+
+    /// Base units of a token with `token_decimals` worth `fiat_amount` minor
+    /// units of a currency with `fiat_decimals`, rounded half up.
+    pub fn token_amount(&self, fiat_amount: i64, fiat_decimals: u8, token_decimals: u8) -> Option<i64> {
+        let mut numerator = (fiat_amount as i128).checked_mul(pow10(token_decimals as u32)?)?;
+        let mut denominator = (self.mantissa as i128).checked_mul(pow10(fiat_decimals as u32)?)?;
+        if self.expo < 0 {
+            numerator = numerator.checked_mul(pow10(self.expo.unsigned_abs())?)?;
+        } else {
+            denominator = denominator.checked_mul(pow10(self.expo as u32)?)?;
+        }
+        let rounded = numerator
+            .checked_mul(2)?
+            .checked_add(denominator)?
+            .checked_div(denominator.checked_mul(2)?)?;
+        i64::try_from(rounded).ok()
+    }
+}
+
+impl std::fmt::Display for Rate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.expo >= 0 {
+            write!(f, "{}{}", self.mantissa, "0".repeat(self.expo as usize))
+        } else {
+            f.write_str(&base_units_to_ui(self.mantissa, self.expo.unsigned_abs() as u8))
+        }
+    }
+}
+
+fn pow10(exp: u32) -> Option<i128> {
+    10i128.checked_pow(exp)
+}
+
+/// Rejects prices older than `max_age_secs`, for sources that report when
+/// the price was published.
+pub fn check_fresh(published_at: DateTime<Utc>, max_age_secs: i64, source: &str) -> Result<(), AppError> {
+    if Utc::now() - published_at > Duration::seconds(max_age_secs) {
+        return Err(AppError::Other(format!(
+            "{} price is stale (published {})",
+            source, published_at
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(value: &str) -> Rate {
+        Rate::parse(value).unwrap()
+    }
+
+    #[test]
+    fn parses_decimal_and_exponent_prices() {
+        assert_eq!(rate("151.23"), Rate { mantissa: 15_123, expo: -2 });
+        assert_eq!(rate(" +0.000123 "), Rate { mantissa: 123, expo: -6 });
+        assert_eq!(rate("1.5e-5"), Rate { mantissa: 15, expo: -6 });
+        assert_eq!(rate("2E3"), Rate { mantissa: 2, expo: 3 });
+        assert_eq!(rate("150"), Rate { mantissa: 150, expo: 0 });
+    }
+
+    #[test]
+    fn keeps_eighteen_significant_digits() {
+        let long = rate("1.23456789012345678999");
+        assert_eq!(long, Rate { mantissa: 123_456_789_012_345_678, expo: -17 });
+        assert_eq!(long.to_string(), "1.23456789012345678");
+        assert_eq!(rate("1234567890123456789012"), Rate { mantissa: 123_456_789_012_345_678, expo: 4 });
+    }
+
+    #[test]
+    fn refuses_prices_out_of_range() {
+        for bad in ["", "0", "0.000", "-1", "abc", "1.2.3", "1e", "1e41", "1e-41", "1e2147483647"] {
+            assert_eq!(Rate::parse(bad), None, "{:?}", bad);
+        }
+        assert_eq!(Rate::new(0, 0), None);
+        assert_eq!(Rate::new(-5, 0), None);
+        assert_eq!(Rate::new(1, 41), None);
+        assert_eq!(Rate::new(1, -40), Some(Rate { mantissa: 1, expo: -40 }));
+    }
+
+    #[test]
+    fn displays_exact_decimals() {
+        assert_eq!(rate("151.23").to_string(), "151.23");
+        assert_eq!(rate("1.5e-5").to_string(), "0.000015");
+        assert_eq!(rate("2e3").to_string(), "2000");
+    }
+
+    #[test]
+    fn converts_fiat_to_token_amounts() {
+        // $10.00 of SOL at $150: 0.0666666666… SOL, rounded to 66666667 lamports.
+        assert_eq!(rate("150").token_amount(1_000, 2, 9), Some(66_666_667));
+        // Positive and negative exponents give the same amounts as plain decimals.
+        assert_eq!(rate("1.5e2").token_amount(1_000, 2, 9), Some(66_666_667));
+        assert_eq!(Rate::new(15, 1).unwrap().token_amount(1_000, 2, 9), Some(66_666_667));
+        assert_eq!(rate("0.5").token_amount(100, 2, 6), Some(2_000_000));
+        assert_eq!(rate("5e-1").token_amount(100, 2, 6), Some(2_000_000));
+    }
+
+    #[test]
+    fn rounds_token_amounts_half_up() {
+        // 1 cent at $2 with 2 decimals is exactly 0.5 base units.
+        assert_eq!(rate("2").token_amount(1, 2, 2), Some(1));
+        assert_eq!(rate("3").token_amount(1, 2, 2), Some(0));
+        // 2.5 and 1.5 both round up.
+        assert_eq!(rate("4").token_amount(10, 2, 2), Some(3));
+        assert_eq!(rate("4").token_amount(6, 2, 2), Some(2));
+    }
+
+    #[test]
+    fn token_amounts_out_of_range_are_none() {
+        assert_eq!(Rate::new(1, -40).unwrap().token_amount(i64::MAX, 2, 18), None);
+        assert_eq!(rate("1e-18").token_amount(i64::MAX, 2, 9), None);
+        assert_eq!(Rate::new(1, 40).unwrap().token_amount(100, 2, 255), None);
+    }
+
+    #[test]
+    fn crosses_a_eur_rate_through_usd() {
+        // SOL/USD 150 and EUR/USD 1.08 give SOL/EUR 138.888…
+        let sol_eur = rate("150").checked_div(&rate("1.08")).unwrap();
+        assert_eq!(sol_eur, Rate { mantissa: 1_388_888_888_888_888_888, expo: -16 });
+        assert_eq!(sol_eur.to_string(), "138.8888888888888888");
+        assert_eq!(sol_eur.token_amount(1_000, 2, 9), Some(72_000_000));
+        // Exact quotients drop their trailing zeros.
+        assert_eq!(rate("150").checked_div(&rate("1.5")), Some(Rate { mantissa: 1, expo: 2 }));
+        assert_eq!(Rate::new(1, 40).unwrap().checked_div(&Rate::new(1, -40).unwrap()), None);
+    }
+}
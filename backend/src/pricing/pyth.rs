@@ -0,0 +1,109 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use crate::db::models::FiatCurrency;
+use crate::error::AppError;
+use crate::privacy::rail::BoxFuture;
+
+use super::{check_fresh, PriceSource, Rate};
+
+/// Pyth publishes roughly every 400ms; anything older means the feed is
+/// halted or Hermes is lagging.
+const MAX_AGE_SECS: i64 = 60;
+
+const SOL_USD_FEED: &str = "ef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d";
+const USDC_USD_FEED: &str = "eaa020c61cc479712813461ce153894a96a6c00b21ed0cfc2798d1f9a9e9c94a";
+const EUR_USD_FEED: &str = "a995d00bb36a63cef7fd2c287dc105fc8f3d93779f062f09551b0af3e81ec30b";
+
+/// Pyth price feeds read through a Hermes endpoint. Feeds are quoted in USD;
+/// EUR prices are crossed with the EUR/USD feed.
+pub struct PythSource {
+    http: reqwest::Client,
+    hermes_url: String,
+}
+
+impl PythSource {
+    pub fn new(http: reqwest::Client, hermes_url: String) -> Self {
+        Self { http, hermes_url }
+    }
+
+    async fn latest(&self, feeds: &[&str]) -> Result<Vec<Rate>, AppError> {
+        let url = format!(
+            "{}/v2/updates/price/latest",
+            self.hermes_url.trim_end_matches('/')
+        );
+        let query: Vec<(&str, &str)> = feeds
+            .iter()
+            .map(|feed| ("ids[]", *feed))
+            .chain([("parsed", "true"), ("encoding", "hex")])
+            .collect();
+        let body: Value = self
+            .http
+            .get(url)
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let parsed = body
+            .get("parsed")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| AppError::Other("pyth response has no parsed prices".to_string()))?;
+        feeds
+            .iter()
+            .map(|feed| {
+                let entry = parsed
+                    .iter()
+                    .find(|p| p.get("id").and_then(|v| v.as_str()) == Some(*feed))
+                    .and_then(|p| p.get("price"))
+                    .ok_or_else(|| AppError::Other(format!("pyth returned no price for feed {}", feed)))?;
+                parse_price(entry)
+            })
+            .collect()
+    }
+}
+
+fn parse_price(entry: &Value) -> Result<Rate, AppError> {
+    let mantissa = entry
+        .get("price")
+        .and_then(|v| v.as_str())
+        .and_then(|s| s.parse::<i64>().ok());
+    let expo = entry.get("expo").and_then(|v| v.as_i64());
+    let rate = mantissa
+        .zip(expo)
+        .and_then(|(m, e)| Rate::new(m, e as i32))
+        .ok_or_else(|| AppError::Other(format!("invalid pyth price {}", entry)))?;
+    let published_at = entry
+        .get("publish_time")
+        .and_then(|v| v.as_i64())
+        .and_then(|t| DateTime::<Utc>::from_timestamp(t, 0))
+        .ok_or_else(|| AppError::Other("pyth price has no publish time".to_string()))?;
+    check_fresh(published_at, MAX_AGE_SECS, "pyth")?;
+    Ok(rate)
+}
+
+impl PriceSource for PythSource {
+    fn name(&self) -> &'static str {
+        "pyth"
+    }
+
+    fn price<'a>(&'a self, asset: &'a str, currency: FiatCurrency) -> BoxFuture<'a, Result<Rate, AppError>> {
+        Box::pin(async move {
+            let feed = match asset {
+                "SOL" => SOL_USD_FEED,
+                "USDC" => USDC_USD_FEED,
+                _ => return Err(AppError::BadRequest(format!("pyth has no feed for {}", asset))),
+            };
+            match currency {
+                FiatCurrency::Usd => Ok(self.latest(&[feed]).await?[0]),
+                FiatCurrency::Eur => {
+                    let rates = self.latest(&[feed, EUR_USD_FEED]).await?;
+                    rates[0]
+                        .checked_div(&rates[1])
+                        .ok_or_else(|| AppError::Other("pyth EUR cross rate out of range".to_string()))
+                }
+            }
+        })
+    }
+}
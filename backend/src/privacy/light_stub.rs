@@ -4,7 +4,9 @@ use crate::db::{models::PayLink, queries, Db};
 use crate::helius::enhanced_tx::TxView;
 use crate::util::json_scan::extract_paylink_id_from_memo;
 
-use super::rail::{BoxFuture, PrivacyRail};
+use super::rail::{
+    amount_identifies, expected_memo_found, unmatched_reason, BoxFuture, PrivacyRail,
+};
 use super::types::PaymentMatchResult;
 
 /// Light Protocol ZK Compression Privacy Rail
@@ -36,6 +38,14 @@ impl LightRail {
         None
     }
 
+    /// Solana Pay reference key carried as an extra account in the transfer
+    fn reference_match(&self, paylink: &PayLink, tx: &TxView) -> bool {
+        paylink
+            .reference
+            .as_ref()
+            .is_some_and(|r| tx.account_keys.iter().any(|k| k == r))
+    }
+
     /// Check if transaction contains Light Protocol compressed token instructions
     /// Light Protocol uses specific program IDs and instruction discriminators
     fn is_compressed_token_transfer(&self, _tx: &TxView) -> bool {
@@ -64,7 +74,12 @@ impl PrivacyRail for LightRail {
 
     fn match_paylink<'a>(&'a self, tx: &'a TxView, db: &'a Db) -> BoxFuture<'a, Option<Uuid>> {
         Box::pin(async move {
-            // First, try memo-based matching (works for both compressed and regular)
+            // A Solana Pay reference key identifies exactly one PayLink
+            if let Ok(Some(paylink)) = queries::find_paylink_by_reference(db, &tx.account_keys).await {
+                return Some(paylink.id);
+            }
+
+            // Next, try memo-based matching (works for both compressed and regular)
             if let Some(id) = self.memo_match(tx) {
                 return Some(id);
             }
@@ -85,15 +100,22 @@ impl PrivacyRail for LightRail {
                 eprintln!("Light Protocol: Detected compressed token transfer, but full parsing not yet implemented");
             }
 
-            // Fallback: Check regular token transfers (for hybrid support)
+            // Fallback: Check regular token transfers (for hybrid support),
+            // totalled per recipient so split transfers still match
+            for transfer in &tx.token_transfers {
+                let amount = tx.amount_received(transfer.recipient(), &transfer.mint);
+                if let Ok(Some(paylink)) =
+                    queries::find_pending_by_match(db, transfer.recipient(), &transfer.mint, amount)
+                        .await
+                {
+                    return Some(paylink.id);
+                }
+            }
+
+            // Surface an amount that fits no PayLink when only one could be meant
             for transfer in &tx.token_transfers {
-                if let Ok(Some(paylink)) = queries::find_pending_by_match(
-                    db,
-                    &transfer.destination,
-                    &transfer.mint,
-                    transfer.amount,
-                )
-                .await
+                if let Ok(Some(paylink)) =
+                    queries::find_only_open_paylink(db, transfer.recipient(), &transfer.mint).await
                 {
                     return Some(paylink.id);
                 }
@@ -117,6 +139,16 @@ impl PrivacyRail for LightRail {
                 matched_fields.push("memo".to_string());
             }
 
+            let memo_required = expected_memo_found(paylink, tx);
+            if memo_required == Some(true) && !memo_match {
+                matched_fields.push("memo".to_string());
+            }
+
+            let reference_match = self.reference_match(paylink, tx);
+            if reference_match {
+                matched_fields.push("reference".to_string());
+            }
+
             // For Light Protocol compressed payments:
             // - Verify ZK proof validity (done on-chain by Light Protocol)
             // - Check compressed account state matches PayLink requirements
@@ -132,28 +164,25 @@ impl PrivacyRail for LightRail {
                 // 2. Extract compressed token transfer details
                 // 3. Match against PayLink (amount, mint, recipient)
 
-                if memo_match {
+                if memo_match && memo_required != Some(false) {
                     return PaymentMatchResult {
                         matched: true,
                         reason: "Matched via Light Protocol compressed payment with memo".to_string(),
                         matched_fields,
+                        amount: None,
                     };
                 }
             }
 
             // Fallback to regular token transfer verification
-            let mut transfer_match = false;
-            for transfer in &tx.token_transfers {
-                if transfer.destination == paylink.merchant_pubkey
-                    && transfer.mint == paylink.mint
-                    && transfer.amount == paylink.expected_amount
-                {
-                    transfer_match = true;
+            let received = tx.amount_received(&paylink.merchant_pubkey, &paylink.mint);
+            let transfer_match = amount_identifies(paylink, received);
+            if received > 0 {
+                if transfer_match {
                     matched_fields.push("amount".to_string());
-                    matched_fields.push("mint".to_string());
-                    matched_fields.push("merchantPubkey".to_string());
-                    break;
                 }
+                matched_fields.push("mint".to_string());
+                matched_fields.push("merchantPubkey".to_string());
             }
 
             if let Some(slot) = tx.slot {
@@ -162,21 +191,37 @@ impl PrivacyRail for LightRail {
                 }
             }
 
-            let matched = memo_match || transfer_match;
+            if memo_required == Some(false) {
+                return PaymentMatchResult {
+                    matched: false,
+                    reason: "Required memo not found".to_string(),
+                    matched_fields,
+                    amount: Some(received),
+                };
+            }
+
+            let identified = reference_match || memo_match || transfer_match;
+            let amount_check = paylink.check_payment_amount(received);
+            let matched = identified && received > 0 && amount_check.is_ok();
             let reason = if matched {
                 if is_compressed {
                     "Matched Light Protocol compressed payment".to_string()
                 } else {
                     "Matched payment".to_string()
                 }
+            } else if identified && received > 0 {
+                amount_check.err().unwrap_or_default()
+            } else if identified {
+                "No transfer of the paylink mint to the merchant".to_string()
             } else {
-                "No match found".to_string()
+                unmatched_reason(paylink, received, "No match found")
             };
 
             PaymentMatchResult {
                 matched,
                 reason,
                 matched_fields,
+                amount: Some(received),
             }
         })
     }
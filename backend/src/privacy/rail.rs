@@ -13,6 +13,7 @@ pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
 
 pub trait PrivacyRail: Send + Sync {
     fn name(&self) -> &'static str;
+    #[allow(dead_code)]
     fn receipt_rail(&self) -> &'static str;
     fn match_paylink<'a>(&'a self, tx: &'a TxView, db: &'a Db) -> BoxFuture<'a, Option<Uuid>>;
     fn verify_payment<'a>(
@@ -22,6 +23,38 @@ pub trait PrivacyRail: Send + Sync {
     ) -> BoxFuture<'a, PaymentMatchResult>;
 }
 
+/// Whether the memo rendered from the paylink's memo policy is present.
+/// `None` when the policy does not require a memo.
+pub fn expected_memo_found(paylink: &PayLink, tx: &TxView) -> Option<bool> {
+    let expected = paylink.expected_memo.as_deref()?;
+    Some(tx.memo_strings.iter().any(|memo| memo.contains(expected)))
+}
+
+/// Whether a transfer amount alone is enough to tie a transaction without a
+/// memo or reference to the paylink: it pays either the whole amount or what
+/// is still owed, within the paylink's amount tolerance. Never true for
+/// open-amount links.
+pub fn amount_identifies(paylink: &PayLink, received: i64) -> bool {
+    let allowance = paylink.amount_tolerance.allowance(paylink.expected_amount);
+    received > 0
+        && !paylink.open_amount
+        && ((received - paylink.expected_amount).abs() <= allowance
+            || (received - paylink.remaining_amount()).abs() <= allowance)
+}
+
+/// Mismatch reason for a transaction that could not be tied to the paylink.
+pub fn unmatched_reason(paylink: &PayLink, received: i64, fallback: &str) -> String {
+    if received > 0 {
+        format!(
+            "Received {} but {} is owed, outside the amount tolerance",
+            received,
+            paylink.remaining_amount()
+        )
+    } else {
+        fallback.to_string()
+    }
+}
+
 #[derive(Clone)]
 pub struct RailSelector {
     rail_name: String,
@@ -41,3 +74,32 @@ impl RailSelector {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::{AmountTolerance, ToleranceKind};
+    use crate::test_support::{paylink, FIXTURE_MERCHANT};
+
+    #[test]
+    fn amount_identifies_full_or_remaining_amounts_within_tolerance() {
+        let mut link = paylink(FIXTURE_MERCHANT, "SOL", 1_000);
+        link.amount_tolerance = sqlx::types::Json(AmountTolerance {
+            kind: ToleranceKind::Absolute,
+            value: 5,
+        });
+        assert!(amount_identifies(&link, 1_000));
+        assert!(amount_identifies(&link, 995));
+        assert!(amount_identifies(&link, 1_005));
+        assert!(!amount_identifies(&link, 994));
+        assert!(!amount_identifies(&link, 0));
+
+        link.amount_received = 600;
+        assert!(amount_identifies(&link, 400));
+        assert!(amount_identifies(&link, 1_000));
+        assert!(!amount_identifies(&link, 300));
+
+        link.open_amount = true;
+        assert!(!amount_identifies(&link, 1_000));
+    }
+}
@@ -2,9 +2,12 @@ use uuid::Uuid;
 
 use crate::db::{models::PayLink, queries, Db};
 use crate::helius::enhanced_tx::TxView;
+use crate::solana;
 use crate::util::json_scan::extract_paylink_id_from_memo;
 
-use super::rail::{BoxFuture, PrivacyRail};
+use super::rail::{
+    amount_identifies, expected_memo_found, unmatched_reason, BoxFuture, PrivacyRail,
+};
 use super::types::PaymentMatchResult;
 
 #[derive(Clone)]
@@ -23,6 +26,13 @@ impl TransparentRail {
         }
         None
     }
+
+    fn reference_match(&self, paylink: &PayLink, tx: &TxView) -> bool {
+        paylink
+            .reference
+            .as_ref()
+            .is_some_and(|r| tx.account_keys.iter().any(|k| k == r))
+    }
 }
 
 impl PrivacyRail for TransparentRail {
@@ -36,42 +46,45 @@ impl PrivacyRail for TransparentRail {
 
     fn match_paylink<'a>(&'a self, tx: &'a TxView, db: &'a Db) -> BoxFuture<'a, Option<Uuid>> {
         Box::pin(async move {
+            if let Ok(Some(paylink)) = queries::find_paylink_by_reference(db, &tx.account_keys).await {
+                return Some(paylink.id);
+            }
+
             if let Some(id) = self.memo_match(tx) {
                 return Some(id);
             }
 
-            for transfer in &tx.token_transfers {
-                if let Ok(Some(paylink)) = queries::find_pending_by_match(
-                    db,
-                    &transfer.destination,
-                    &transfer.mint,
-                    transfer.amount,
-                )
-                .await
-                {
-                    return Some(paylink.id);
+            // Amounts are totalled per recipient and mint so a transfer split
+            // across instructions still matches.
+            let mut candidates: Vec<(&str, &str)> = Vec::new();
+            let token_candidates = tx
+                .token_transfers
+                .iter()
+                .map(|t| (t.recipient(), t.mint.as_str()));
+            // Paylinks store the mint address, wrapped SOL for native SOL.
+            let native_candidates = tx
+                .native_transfers
+                .iter()
+                .map(|t| (t.destination.as_str(), solana::WRAPPED_SOL_MINT));
+            for candidate in token_candidates.chain(native_candidates) {
+                if !candidates.contains(&candidate) {
+                    candidates.push(candidate);
                 }
             }
 
-            for transfer in &tx.native_transfers {
-                if let Ok(Some(paylink)) = queries::find_pending_by_match(
-                    db,
-                    &transfer.destination,
-                    "SOL",
-                    transfer.lamports,
-                )
-                .await
+            for (recipient, mint) in &candidates {
+                let amount = tx.amount_received(recipient, mint);
+                if let Ok(Some(paylink)) =
+                    queries::find_pending_by_match(db, recipient, mint, amount).await
                 {
                     return Some(paylink.id);
                 }
-                if let Ok(Some(paylink)) = queries::find_pending_by_match(
-                    db,
-                    &transfer.destination,
-                    "So11111111111111111111111111111111111111112",
-                    transfer.lamports,
-                )
-                .await
-                {
+            }
+
+            // An amount that fits no paylink is still shown to the merchant
+            // when only one paylink could have been meant.
+            for (recipient, mint) in &candidates {
+                if let Ok(Some(paylink)) = queries::find_only_open_paylink(db, recipient, mint).await {
                     return Some(paylink.id);
                 }
             }
@@ -93,35 +106,24 @@ impl PrivacyRail for TransparentRail {
                 matched_fields.push("memo".to_string());
             }
 
-            let mut transfer_match = false;
-            for transfer in &tx.token_transfers {
-                if transfer.destination == paylink.merchant_pubkey
-                    && transfer.mint == paylink.mint
-                    && transfer.amount == paylink.expected_amount
-                {
-                    transfer_match = true;
-                    matched_fields.push("amount".to_string());
-                    matched_fields.push("mint".to_string());
-                    matched_fields.push("merchantPubkey".to_string());
-                    break;
-                }
+            let memo_required = expected_memo_found(paylink, tx);
+            if memo_required == Some(true) && !memo_match {
+                matched_fields.push("memo".to_string());
             }
 
-            if !transfer_match
-                && (paylink.mint == "SOL"
-                    || paylink.mint == "So11111111111111111111111111111111111111112")
-            {
-                for transfer in &tx.native_transfers {
-                    if transfer.destination == paylink.merchant_pubkey
-                        && transfer.lamports == paylink.expected_amount
-                    {
-                        transfer_match = true;
-                        matched_fields.push("amount".to_string());
-                        matched_fields.push("mint".to_string());
-                        matched_fields.push("merchantPubkey".to_string());
-                        break;
-                    }
+            let reference_match = self.reference_match(paylink, tx);
+            if reference_match {
+                matched_fields.push("reference".to_string());
+            }
+
+            let received = tx.amount_received(&paylink.merchant_pubkey, &paylink.mint);
+            let transfer_match = amount_identifies(paylink, received);
+            if received > 0 {
+                if transfer_match {
+                    matched_fields.push("amount".to_string());
                 }
+                matched_fields.push("mint".to_string());
+                matched_fields.push("merchantPubkey".to_string());
             }
 
             if let Some(slot) = tx.slot {
@@ -130,17 +132,33 @@ impl PrivacyRail for TransparentRail {
                 }
             }
 
-            let matched = memo_match || transfer_match;
+            if memo_required == Some(false) {
+                return PaymentMatchResult {
+                    matched: false,
+                    reason: "Required memo not found".to_string(),
+                    matched_fields,
+                    amount: Some(received),
+                };
+            }
+
+            let identified = reference_match || memo_match || transfer_match;
+            let amount_check = paylink.check_payment_amount(received);
+            let matched = identified && received > 0 && amount_check.is_ok();
             let reason = if matched {
                 "Matched payment".to_string()
+            } else if identified && received > 0 {
+                amount_check.err().unwrap_or_default()
+            } else if identified {
+                "No transfer of the paylink mint to the merchant".to_string()
             } else {
-                "No confident match".to_string()
+                unmatched_reason(paylink, received, "No confident match")
             };
 
             PaymentMatchResult {
                 matched,
                 reason,
                 matched_fields,
+                amount: Some(received),
             }
         })
     }
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ReceiptFacts {
@@ -21,6 +22,10 @@ pub struct CommitmentPayload {
     pub slot: Option<i64>,
     pub invoice_ref: Option<String>,
     pub nonce: String,
+    /// `Invoice::breakdown` for invoice paylinks. Left out of the serialized
+    /// form when absent so other receipts keep their commitments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invoice: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,4 +34,8 @@ pub struct PaymentMatchResult {
     pub matched: bool,
     pub reason: String,
     pub matched_fields: Vec<String>,
+    /// Base units of the paylink mint the merchant received in this
+    /// transaction. `None` when the rail cannot see the amount (compressed
+    /// transfers), in which case the payment settles the remaining balance.
+    pub amount: Option<i64>,
 }
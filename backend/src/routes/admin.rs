@@ -0,0 +1,366 @@
+use axum::{extract::{Path, State}, http::HeaderMap, response::IntoResponse, routing::{delete, post}, Json, Router};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app::AppState,
+    auth::{self, api_key},
+    db::{models::{ApiKey, Merchant, PaylinkStatus}, queries},
+    error::AppError,
+    routes::helius_webhook,
+    solana,
+    util::crypto,
+};
+
+const DEFAULT_REPLAY_LIMIT: i64 = 500;
+const MAX_REPLAY_LIMIT: i64 = 5000;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayRequest {
+    pub signature: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub unmatched_only: bool,
+    #[serde(default)]
+    pub dry_run: bool,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayResponse {
+    pub dry_run: bool,
+    pub count: usize,
+    pub items: Vec<ReplayItem>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayItem {
+    pub signature: String,
+    pub received_at: DateTime<Utc>,
+    pub queued: Option<bool>,
+    pub paylink_id: Option<Uuid>,
+    pub paylink_status: Option<PaylinkStatus>,
+    pub matched: Option<bool>,
+    pub reason: Option<String>,
+    pub matched_fields: Vec<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RescanRequest {
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RescanResponse {
+    pub address: String,
+    pub found: usize,
+    pub queued: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMerchantRequest {
+    pub name: String,
+    pub scopes: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKeyRequest {
+    pub scopes: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimPaylinksRequest {
+    pub merchant_pubkey: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssuedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    /// Plaintext key; only ever returned here.
+    pub key: String,
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/webhooks/replay", post(replay_webhooks))
+        .route("/addresses/:address/rescan", post(rescan_address))
+        .route("/merchants", post(create_merchant))
+        .route("/merchants/:id/api-keys", post(create_api_key).get(list_api_keys))
+        .route("/merchants/:id/api-keys/:key_id/revoke", post(revoke_api_key))
+        .route("/merchants/:id/wallets/:pubkey", delete(unlink_wallet))
+        .route("/merchants/:id/claim-paylinks", post(claim_paylinks))
+}
+
+pub fn require_admin(state: &AppState, headers: &HeaderMap) -> Result<(), AppError> {
+    let expected = state
+        .config
+        .admin_token
+        .as_deref()
+        .ok_or_else(|| AppError::Unauthorized("admin API disabled".to_string()))?;
+    let provided = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let valid = provided
+        .map(|p| crypto::constant_time_eq(p.as_bytes(), expected.as_bytes()))
+        .unwrap_or(false);
+    if !valid {
+        return Err(AppError::Unauthorized("invalid admin token".to_string()));
+    }
+    Ok(())
+}
+
+async fn replay_webhooks(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<ReplayRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    require_admin(&state, &headers)?;
+
+    if payload.signature.is_none()
+        && payload.from.is_none()
+        && payload.to.is_none()
+        && !payload.unmatched_only
+    {
+        return Err(AppError::BadRequest(
+            "provide a signature, a from/to range or unmatchedOnly".to_string(),
+        ));
+    }
+
+    let limit = payload
+        .limit
+        .unwrap_or(DEFAULT_REPLAY_LIMIT)
+        .clamp(1, MAX_REPLAY_LIMIT);
+    let events = queries::list_webhook_events_for_replay(
+        &state.db,
+        payload.signature.clone(),
+        payload.from,
+        payload.to,
+        payload.unmatched_only,
+        limit,
+    )
+    .await?;
+
+    if events.is_empty() && payload.signature.is_some() {
+        return Err(AppError::NotFound("webhook event not found".to_string()));
+    }
+
+    let mut items = Vec::with_capacity(events.len());
+    for event in events {
+        let mut item = ReplayItem {
+            signature: event.signature.clone(),
+            received_at: event.received_at,
+            queued: None,
+            paylink_id: None,
+            paylink_status: None,
+            matched: None,
+            reason: None,
+            matched_fields: Vec::new(),
+            error: None,
+        };
+
+        if payload.dry_run {
+            match helius_webhook::evaluate_signature(&state, &event.signature).await {
+                Ok(eval) => {
+                    item.paylink_id = eval.paylink_id;
+                    item.paylink_status = eval.paylink.map(|p| p.status);
+                    if let Some(verify) = eval.verify {
+                        item.matched = Some(verify.matched);
+                        item.reason = Some(verify.reason);
+                        item.matched_fields = verify.matched_fields;
+                    }
+                }
+                Err(err) => item.error = Some(format!("{:?}", err)),
+            }
+        } else {
+            let queued = queries::requeue_webhook_job(
+                &state.db,
+                &event.signature,
+                state.config.webhook_job_max_attempts,
+            )
+            .await?;
+            item.queued = Some(queued);
+        }
+
+        items.push(item);
+    }
+
+    Ok(Json(ReplayResponse {
+        dry_run: payload.dry_run,
+        count: items.len(),
+        items,
+    }))
+}
+
+/// Pulls recent signatures for an address from the chain provider and queues
+/// any we have never seen, to recover payments whose webhook never arrived.
+async fn rescan_address(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(address): Path<String>,
+    Json(payload): Json<RescanRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    require_admin(&state, &headers)?;
+    if !solana::is_pubkey(&address) {
+        return Err(AppError::BadRequest("invalid address".to_string()));
+    }
+
+    let limit = payload.limit.unwrap_or(100).clamp(1, 1000);
+    let signatures = state.chain.signatures_for_address(&address, limit).await?;
+
+    let mut queued = Vec::new();
+    for signature in &signatures {
+        let mut dbtx = state.db.begin().await?;
+        let event = serde_json::json!({"signature": signature, "source": "rescan"});
+        if queries::insert_webhook_event(&mut dbtx, signature, &event, false).await? {
+            queries::enqueue_webhook_job(&mut dbtx, signature, state.config.webhook_job_max_attempts)
+                .await?;
+            queued.push(signature.clone());
+        }
+        dbtx.commit().await?;
+    }
+
+    Ok(Json(RescanResponse {
+        address,
+        found: signatures.len(),
+        queued,
+    }))
+}
+
+async fn create_merchant(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateMerchantRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    require_admin(&state, &headers)?;
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest("name is required".to_string()));
+    }
+
+    let merchant = Merchant {
+        id: Uuid::new_v4(),
+        name: name.to_string(),
+        created_at: Utc::now(),
+        webhook_url: None,
+        webhook_secret: None,
+    };
+    let api_key = new_api_key(merchant.id, payload.scopes)?;
+    let mut dbtx = state.db.begin().await?;
+    queries::insert_merchant(&mut dbtx, &merchant).await?;
+    queries::insert_api_key(&mut dbtx, &api_key.api_key).await?;
+    dbtx.commit().await?;
+
+    Ok((
+        axum::http::StatusCode::CREATED,
+        Json(serde_json::json!({ "merchant": merchant, "apiKey": api_key })),
+    ))
+}
+
+async fn create_api_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    require_admin(&state, &headers)?;
+    queries::get_merchant(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("merchant not found".to_string()))?;
+    let api_key = new_api_key(id, payload.scopes)?;
+    let mut dbtx = state.db.begin().await?;
+    queries::insert_api_key(&mut dbtx, &api_key.api_key).await?;
+    dbtx.commit().await?;
+    Ok((axum::http::StatusCode::CREATED, Json(api_key)))
+}
+
+async fn list_api_keys(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    require_admin(&state, &headers)?;
+    let items = queries::list_api_keys(&state.db, id).await?;
+    Ok(Json(serde_json::json!({ "items": items })))
+}
+
+async fn revoke_api_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((id, key_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, AppError> {
+    require_admin(&state, &headers)?;
+    let mut dbtx = state.db.begin().await?;
+    let unlinked = queries::revoke_api_key(&mut dbtx, id, key_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("active API key not found".to_string()))?;
+    dbtx.commit().await?;
+    Ok(Json(serde_json::json!({ "revoked": true, "unlinkedWallets": unlinked })))
+}
+
+/// Unlinks a wallet from a merchant and revokes its sessions, for links made
+/// with a key that is still needed or before linking keys were recorded.
+async fn unlink_wallet(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((id, pubkey)): Path<(Uuid, String)>,
+) -> Result<impl IntoResponse, AppError> {
+    require_admin(&state, &headers)?;
+    let mut dbtx = state.db.begin().await?;
+    let unlinked = queries::unlink_merchant_wallets(&mut dbtx, id, Some(&pubkey), None).await?;
+    if unlinked.is_empty() {
+        return Err(AppError::NotFound("wallet is not linked to this merchant".to_string()));
+    }
+    dbtx.commit().await?;
+    Ok(Json(serde_json::json!({ "unlinked": true })))
+}
+
+async fn claim_paylinks(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<ClaimPaylinksRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    require_admin(&state, &headers)?;
+    queries::get_merchant(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("merchant not found".to_string()))?;
+    let claimed = queries::assign_unowned_paylinks(&state.db, id, &payload.merchant_pubkey).await?;
+    Ok(Json(serde_json::json!({ "claimed": claimed })))
+}
+
+/// Generates a key with the given scopes (all of them by default), after
+/// checking they are known. The caller stores `api_key`.
+fn new_api_key(merchant_id: Uuid, scopes: Option<Vec<String>>) -> Result<IssuedApiKey, AppError> {
+    let scopes = scopes.unwrap_or_else(|| auth::ALL_SCOPES.iter().map(|s| s.to_string()).collect());
+    auth::validate_scopes(&scopes)?;
+
+    let generated = api_key::generate();
+    let api_key = ApiKey {
+        id: Uuid::new_v4(),
+        merchant_id,
+        prefix: generated.prefix,
+        key_hash: generated.hash,
+        scopes,
+        created_at: Utc::now(),
+        last_used_at: None,
+        revoked_at: None,
+    };
+    Ok(IssuedApiKey {
+        api_key,
+        key: generated.key,
+    })
+}
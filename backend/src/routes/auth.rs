@@ -0,0 +1,266 @@
+use axum::{extract::State, http::HeaderMap, response::IntoResponse, routing::post, Json, Router};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app::AppState,
+    auth::{self, api_key, siws, MerchantAuth},
+    db::{models::{AuthNonce, WalletSession}, queries},
+    error::AppError,
+    solana,
+};
+
+const NONCE_TTL_SECS: i64 = 300;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NonceRequest {
+    pub pubkey: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NonceResponse {
+    pub nonce: String,
+    pub message: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyRequest {
+    pub pubkey: String,
+    pub nonce: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionResponse {
+    pub token: String,
+    pub pubkey: String,
+    pub merchant_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+    pub scopes: Vec<String>,
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/siws/nonce", post(create_nonce))
+        .route("/siws/verify", post(verify))
+        .route("/logout", post(logout))
+}
+
+async fn create_nonce(
+    State(state): State<AppState>,
+    Json(payload): Json<NonceRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if !solana::is_pubkey(&payload.pubkey) {
+        return Err(AppError::BadRequest("invalid pubkey".to_string()));
+    }
+    let now = Utc::now();
+    let expires_at = now + Duration::seconds(NONCE_TTL_SECS);
+    let nonce = siws::new_nonce();
+    let message = siws::message(&state.config, &payload.pubkey, &nonce, now, expires_at);
+    queries::insert_auth_nonce(
+        &state.db,
+        &AuthNonce {
+            nonce: nonce.clone(),
+            pubkey: payload.pubkey,
+            message: message.clone(),
+            created_at: now,
+            expires_at,
+            used_at: None,
+        },
+    )
+    .await?;
+    if let Err(err) = queries::delete_expired_auth_nonces(&state.db).await {
+        eprintln!("failed to prune auth nonces: {:?}", err);
+    }
+
+    Ok(Json(NonceResponse {
+        nonce,
+        message,
+        expires_at,
+    }))
+}
+
+/// Exchanges a signed SIWS message for a session bound to the wallet. The
+/// first sign-in for a wallet must also carry a merchant API key, which links
+/// the wallet to that merchant with the key's scopes; later sign-ins only need
+/// the signature, and sessions get the scopes the wallet was linked with.
+async fn verify(
+    State(state): State<AppState>,
+    api_key_auth: Option<MerchantAuth>,
+    Json(payload): Json<VerifyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut dbtx = state.db.begin().await?;
+    let nonce = queries::consume_auth_nonce(&mut dbtx, &payload.nonce, &payload.pubkey)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("unknown, used or expired nonce".to_string()))?;
+    if !siws::verify_signature(&payload.pubkey, &nonce.message, &payload.signature) {
+        // The nonce stays consumed, so a failed attempt cannot be retried.
+        dbtx.commit().await?;
+        return Err(AppError::Unauthorized("invalid signature".to_string()));
+    }
+
+    let linked = queries::get_wallet_merchant(&mut dbtx, &payload.pubkey).await?;
+    let (merchant_id, scopes) = match (linked, api_key_auth) {
+        (Some(linked), _) => linked,
+        (
+            None,
+            Some(MerchantAuth {
+                merchant_id,
+                scopes,
+                api_key_id: Some(api_key_id),
+                ..
+            }),
+        ) => {
+            queries::link_merchant_wallet(&mut dbtx, merchant_id, &payload.pubkey, &scopes, api_key_id)
+                .await?;
+            (merchant_id, scopes)
+        }
+        (None, _) => {
+            return Err(AppError::Forbidden(
+                "wallet is not linked to a merchant; sign in once with an API key".to_string(),
+            ))
+        }
+    };
+
+    let token = siws::new_session_token();
+    let session = WalletSession {
+        id: Uuid::new_v4(),
+        token_hash: api_key::hash(&token),
+        pubkey: payload.pubkey,
+        merchant_id,
+        created_at: Utc::now(),
+        expires_at: Utc::now() + Duration::seconds(state.config.session_ttl_secs),
+        revoked_at: None,
+        scopes,
+    };
+    queries::insert_wallet_session(&mut dbtx, &session).await?;
+    dbtx.commit().await?;
+
+    Ok(Json(SessionResponse {
+        token,
+        pubkey: session.pubkey,
+        merchant_id,
+        expires_at: session.expires_at,
+        scopes: session.scopes,
+    }))
+}
+
+async fn logout(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let token = auth::bearer_token(&headers)
+        .filter(|t| t.starts_with(siws::SESSION_PREFIX))
+        .ok_or_else(|| AppError::Unauthorized("missing session token".to_string()))?;
+    let revoked = queries::revoke_wallet_session(&state.db, &api_key::hash(token)).await?;
+    Ok(Json(serde_json::json!({ "revoked": revoked })))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::response::Response;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    use super::*;
+    use crate::{
+        db::models::{ApiKey, Merchant},
+        test_support,
+    };
+
+    async fn body(response: Response) -> serde_json::Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    async fn nonce(state: &AppState, pubkey: &str) -> (String, String) {
+        let response = create_nonce(State(state.clone()), Json(NonceRequest { pubkey: pubkey.to_string() }))
+            .await
+            .unwrap()
+            .into_response();
+        let json = body(response).await;
+        (json["nonce"].as_str().unwrap().to_string(), json["message"].as_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn revoking_the_linking_key_unlinks_the_wallet() {
+        let Some(app) = test_support::app().await else {
+            return;
+        };
+        let state = &app.state;
+        let merchant = Merchant {
+            id: Uuid::new_v4(),
+            name: "test".to_string(),
+            created_at: Utc::now(),
+            webhook_url: None,
+            webhook_secret: None,
+        };
+        let key = ApiKey {
+            id: Uuid::new_v4(),
+            merchant_id: merchant.id,
+            prefix: "rk_test".to_string(),
+            key_hash: api_key::hash("rk_test"),
+            scopes: vec![auth::SCOPE_PAYLINKS_READ.to_string()],
+            created_at: Utc::now(),
+            last_used_at: None,
+            revoked_at: None,
+        };
+        let mut dbtx = state.db.begin().await.unwrap();
+        queries::insert_merchant(&mut dbtx, &merchant).await.unwrap();
+        queries::insert_api_key(&mut dbtx, &key).await.unwrap();
+        dbtx.commit().await.unwrap();
+
+        let wallet = SigningKey::from_bytes(&[7; 32]);
+        let pubkey = bs58::encode(wallet.verifying_key().as_bytes()).into_string();
+        let key_auth = || MerchantAuth {
+            merchant_id: merchant.id,
+            scopes: key.scopes.clone(),
+            wallet: None,
+            api_key_id: Some(key.id),
+        };
+        let sign = |message: &str| bs58::encode(wallet.sign(message.as_bytes()).to_bytes()).into_string();
+
+        // A bad signature still uses up the nonce.
+        let (nonce_value, message) = nonce(state, &pubkey).await;
+        let request = |signature: String| VerifyRequest {
+            pubkey: pubkey.clone(),
+            nonce: nonce_value.clone(),
+            signature,
+        };
+        let bad = verify(State(state.clone()), Some(key_auth()), Json(request(sign("other")))).await;
+        assert!(matches!(bad, Err(AppError::Unauthorized(_))));
+        let retried = verify(State(state.clone()), Some(key_auth()), Json(request(sign(&message)))).await;
+        assert!(matches!(retried, Err(AppError::Unauthorized(_))));
+
+        let (nonce_value, message) = nonce(state, &pubkey).await;
+        let response = verify(
+            State(state.clone()),
+            Some(key_auth()),
+            Json(VerifyRequest {
+                pubkey: pubkey.clone(),
+                nonce: nonce_value,
+                signature: sign(&message),
+            }),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        let session = body(response).await;
+        let token_hash = api_key::hash(session["token"].as_str().unwrap());
+        assert!(queries::get_active_wallet_session(&state.db, &token_hash).await.unwrap().is_some());
+
+        let mut dbtx = state.db.begin().await.unwrap();
+        let unlinked = queries::revoke_api_key(&mut dbtx, merchant.id, key.id).await.unwrap();
+        dbtx.commit().await.unwrap();
+        assert_eq!(unlinked, Some(vec![pubkey.clone()]));
+        assert!(queries::get_active_wallet_session(&state.db, &token_hash).await.unwrap().is_none());
+        assert!(!queries::merchant_has_wallet(&state.db, merchant.id, &pubkey).await.unwrap());
+
+        app.cleanup().await;
+    }
+}
@@ -1,160 +1,528 @@
-use axum::{extract::State, http::HeaderMap, Json};
+use axum::{body::Bytes, extract::State, http::HeaderMap, Json};
 use serde_json::Value;
+use sqlx::{Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
     app::AppState,
-    db::{models::Receipt, queries},
+    db::{models::{PayLink, PaylinkStatus, Payment, Receipt}, queries},
     error::AppError,
-    helius::enhanced_tx,
-    privacy::types::CommitmentPayload,
-    util::{crypto, json_scan},
+    helius::{enhanced_tx, webhook_payload},
+    privacy::types::{CommitmentPayload, PaymentMatchResult},
+    util::{crypto, idempotency, webhook_auth},
 };
 
 pub async fn handle(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(payload): Json<Value>,
+    body: Bytes,
 ) -> Result<Json<Value>, AppError> {
-    if let Some(secret) = &state.config.webhook_secret {
-        let provided = headers
-            .get("x-webhook-secret")
-            .and_then(|v| v.to_str().ok());
-        if provided != Some(secret.as_str()) {
-            return Err(AppError::Unauthorized("invalid webhook secret".to_string()));
-        }
-    }
+    let authenticated = webhook_auth::verify(&state.config, &headers, &body)?;
+    let payload: Value = serde_json::from_slice(&body)
+        .map_err(|e| AppError::BadRequest(format!("invalid webhook payload: {}", e)))?;
 
-    let signatures = json_scan::scan_base58_strings(&payload);
-    if signatures.is_empty() {
-        return Ok(Json(serde_json::json!({"ok": true})));
+    let transactions = webhook_payload::parse_payload(&payload);
+    if transactions.is_empty() {
+        return Ok(Json(idempotency::ok_response()));
     }
 
-    for signature in signatures {
-        let payload_clone = payload.clone();
-        let state_clone = state.clone();
-        tokio::spawn(async move {
-            if let Err(err) = process_signature(state_clone, signature, payload_clone).await {
-                eprintln!("webhook processing error: {:?}", err);
-            }
-        });
+    for parsed in transactions {
+        if parsed.failed {
+            continue;
+        }
+        let mut dbtx = state.db.begin().await?;
+        let inserted = queries::insert_webhook_event(&mut dbtx, &parsed.signature, &parsed.raw, authenticated)
+            .await?;
+        if inserted {
+            queries::enqueue_webhook_job(
+                &mut dbtx,
+                &parsed.signature,
+                state.config.webhook_job_max_attempts,
+            )
+            .await?;
+        }
+        dbtx.commit().await?;
     }
 
-    Ok(Json(serde_json::json!({"ok": true})))
+    Ok(Json(idempotency::ok_response()))
 }
 
-async fn process_signature(
-    state: AppState,
-    signature: String,
-    payload: Value,
-) -> Result<(), AppError> {
-    let inserted = queries::insert_webhook_event(&state.db, &signature, &payload).await?;
-    if !inserted {
-        return Ok(());
-    }
+/// Outcome of matching a signature against paylinks, without any writes.
+pub struct SignatureEvaluation {
+    pub tx: enhanced_tx::TxView,
+    pub paylink_id: Option<Uuid>,
+    pub paylink: Option<PayLink>,
+    pub verify: Option<PaymentMatchResult>,
+}
 
-    let tx = enhanced_tx::fetch_enhanced_tx(&state.http, &state.config, &signature).await?;
+pub async fn evaluate_signature(
+    state: &AppState,
+    signature: &str,
+) -> Result<SignatureEvaluation, AppError> {
+    let mut tx = load_tx_view(state, signature).await?;
+    state.owners.resolve(&mut tx).await?;
     let rail = state.rail.active();
 
-    let paylink_id = match rail.match_paylink(&tx, &state.db).await {
+    let paylink_id = rail.match_paylink(&tx, &state.db).await;
+    let paylink = match paylink_id {
+        Some(id) => queries::get_paylink(&state.db, id).await?,
+        None => None,
+    };
+    let verify = match &paylink {
+        Some(p) => Some(rail.verify_payment(p, &tx).await),
+        None => None,
+    };
+
+    Ok(SignatureEvaluation {
+        tx,
+        paylink_id,
+        paylink,
+        verify,
+    })
+}
+
+/// Prefers the transaction data stored with an authenticated webhook event and
+/// otherwise fetches the transaction from the chain provider, so a forged
+/// delivery cannot describe a payment that never happened.
+pub async fn load_tx_view(state: &AppState, signature: &str) -> Result<enhanced_tx::TxView, AppError> {
+    if let Some(event) = queries::get_webhook_event(&state.db, signature).await? {
+        if event.authenticated {
+            if let Some(view) = webhook_payload::tx_view_from_payload(signature, &event.payload) {
+                return Ok(view);
+            }
+        }
+    }
+    state.chain.fetch_transaction(signature).await
+}
+
+/// Runs matching, verification and receipt issuance for one signature.
+/// Called by the webhook job workers; safe to re-run for the same signature,
+/// as events are recorded once per signature and payments once per paylink.
+pub async fn process_signature(state: &AppState, signature: &str) -> Result<(), AppError> {
+    let SignatureEvaluation {
+        tx,
+        paylink_id,
+        paylink,
+        verify,
+    } = evaluate_signature(state, signature).await?;
+
+    let paylink_id = match paylink_id {
         Some(id) => id,
         None => return Ok(()),
     };
 
-    queries::insert_activity_event(
+    // A payment to a cancelled link is logged for the merchant to refund, but
+    // never verified or receipted.
+    if paylink.as_ref().is_some_and(|p| p.status == PaylinkStatus::Cancelled) {
+        queries::insert_signature_event(
+            &state.db,
+            paylink_id,
+            "PAYMENT_IGNORED",
+            &tx.signature,
+            serde_json::json!({"signature": tx.signature, "reason": "paylink cancelled"}),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    queries::insert_signature_event(
         &state.db,
         paylink_id,
         "WEBHOOK_RECEIVED",
-        serde_json::json!({"signature": signature, "raw": tx.raw}),
+        &tx.signature,
+        serde_json::json!({"signature": tx.signature, "raw": tx.raw}),
     )
     .await?;
 
-    let paylink = match queries::get_paylink(&state.db, paylink_id).await? {
+    let verify = match verify {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+
+    let paylink = match paylink {
         Some(p) => p,
         None => return Ok(()),
     };
+    let amount = verify.amount.unwrap_or_else(|| paylink.remaining_amount());
 
-    let verify = rail.verify_payment(&paylink, &tx).await;
     if verify.matched {
-        queries::insert_activity_event(
+        queries::insert_signature_event(
             &state.db,
             paylink_id,
             "TX_VERIFIED_MATCH",
-            serde_json::json!({"signature": signature, "matchedFields": verify.matched_fields}),
+            signature,
+            serde_json::json!({
+                "signature": signature,
+                "matchedFields": verify.matched_fields,
+                "amount": amount,
+                "amountDelta": amount - paylink.remaining_amount(),
+            }),
         )
         .await?;
     } else {
-        queries::insert_activity_event(
+        // The amount lets the merchant follow up on a transfer that was not
+        // applied, e.g. by accepting it through `/paylinks/:id/payments`.
+        queries::insert_signature_event(
             &state.db,
             paylink_id,
             "TX_VERIFIED_MISMATCH",
-            serde_json::json!({"signature": signature, "reason": verify.reason}),
+            signature,
+            serde_json::json!({"signature": signature, "reason": verify.reason, "amount": verify.amount}),
         )
         .await?;
         return Ok(());
     }
 
+    record_payment(
+        state,
+        &paylink,
+        NewPayment {
+            signature,
+            amount,
+            slot: tx.slot,
+            block_time: tx.timestamp,
+            simulated: false,
+            events: Vec::new(),
+        },
+    )
+    .await
+}
+
+/// A verified transfer to a paylink, before it is recorded.
+pub struct NewPayment<'a> {
+    pub signature: &'a str,
+    pub amount: i64,
+    pub slot: Option<i64>,
+    /// Unix seconds.
+    pub block_time: Option<i64>,
+    pub simulated: bool,
+    /// Activity events recorded just before `PAYMENT_RECEIVED`, in the same
+    /// transaction, and only if the signature is new to the paylink.
+    pub events: Vec<(&'static str, serde_json::Value)>,
+}
+
+/// Adds a payment to the paylink's received total and, once the expected
+/// amount is reached within its tolerance, marks it paid and issues a receipt
+/// listing every contributing signature. Any excess is flagged for refund. A
+/// signature already recorded is a no-op.
+pub async fn record_payment(
+    state: &AppState,
+    paylink: &PayLink,
+    payment: NewPayment<'_>,
+) -> Result<(), AppError> {
+    let paylink_id = paylink.id;
+    let row = Payment {
+        id: Uuid::new_v4(),
+        paylink_id,
+        signature: payment.signature.to_string(),
+        amount: payment.amount,
+        mint: paylink.mint.clone(),
+        slot: payment.slot,
+        block_time: payment
+            .block_time
+            .and_then(|t| chrono::DateTime::from_timestamp(t, 0)),
+        created_at: chrono::Utc::now(),
+        refund_due: 0,
+    };
     let mut dbtx = state.db.begin().await?;
-    let updated = queries::mark_paylink_paid(&mut dbtx, paylink_id, &signature, tx.slot).await?;
-    let paylink = match updated {
-        Some(p) => p,
+    let (row, paylink) = match queries::insert_payment(&mut dbtx, &row).await? {
+        Some(recorded) => recorded,
         None => {
             dbtx.commit().await?;
             return Ok(());
         }
     };
 
-    let existing_receipt = queries::get_receipt_by_paylink(&state.db, paylink_id).await?;
-    if existing_receipt.is_none() {
-        let nonce = crypto::random_nonce_hex();
-        let slot = tx.slot.unwrap_or(0);
-        let facts = serde_json::json!({
-            "merchantPubkey": paylink.merchant_pubkey,
-            "amount": paylink.expected_amount,
-            "mint": paylink.mint,
-            "slot": slot,
-            "invoiceRef": paylink.invoice_ref,
-            "nonce": nonce,
-        });
-        let commitment_payload = CommitmentPayload {
-            paylink_id: Some(paylink.id),
-            merchant_pubkey: facts.get("merchantPubkey").and_then(|v| v.as_str()).map(|s| s.to_string()),
-            amount: facts.get("amount").and_then(|v| v.as_i64()),
-            mint: facts.get("mint").and_then(|v| v.as_str()).map(|s| s.to_string()),
-            slot: facts.get("slot").and_then(|v| v.as_i64()),
-            invoice_ref: facts.get("invoiceRef").and_then(|v| v.as_str()).map(|s| s.to_string()),
-            nonce: nonce.clone(),
-        };
-        let bytes = serde_json::to_vec(&commitment_payload)
-            .map_err(|e| AppError::Other(format!("commitment serialize failed: {}", e)))?;
-        let commitment = crypto::sha256_hex(&bytes);
-
-        let receipt = Receipt {
-            id: Uuid::new_v4(),
-            paylink_id: paylink.id,
-            commitment,
-            issued_at: chrono::Utc::now(),
-            facts,
-            rail: paylink.privacy_rail,
-        };
-        queries::insert_receipt(&state.db, &receipt).await?;
-        queries::insert_activity_event(
-            &state.db,
+    for (event_type, detail) in payment.events {
+        queries::insert_activity_event_tx(&mut dbtx, paylink_id, event_type, detail).await?;
+    }
+    queries::insert_activity_event_tx(
+        &mut dbtx,
+        paylink_id,
+        "PAYMENT_RECEIVED",
+        serde_json::json!({
+            "signature": payment.signature,
+            "amount": payment.amount,
+            "totalReceived": paylink.amount_received,
+            "remaining": paylink.remaining_amount(),
+        }),
+    )
+    .await?;
+
+    if row.refund_due > 0 {
+        queries::insert_activity_event_tx(
+            &mut dbtx,
             paylink_id,
-            "RECEIPT_ISSUED",
-            serde_json::json!({"receiptId": receipt.id, "commitment": receipt.commitment}),
+            "PAYMENT_OVERPAID",
+            serde_json::json!({
+                "signature": payment.signature,
+                "paymentId": row.id,
+                "refundDue": row.refund_due,
+                "totalReceived": paylink.amount_received,
+            }),
         )
         .await?;
     }
 
-    queries::insert_activity_event(
-        &state.db,
+    let detail = serde_json::json!({
+        "signature": payment.signature,
+        "slot": payment.slot,
+        "blockTime": payment.block_time,
+        "totalReceived": paylink.amount_received,
+        "amountDelta": paylink.amount_delta(),
+    });
+    if paylink.per_payment() {
+        let accepted = row.amount - row.refund_due;
+        if accepted > 0 {
+            issue_receipt(
+                &mut dbtx,
+                &paylink,
+                Some(row.id),
+                accepted,
+                payment.slot,
+                vec![row.signature.clone()],
+                payment.simulated,
+            )
+            .await?;
+        }
+        if paylink.is_settled() {
+            queries::transition_paylink(
+                &mut dbtx,
+                paylink_id,
+                None,
+                PaylinkStatus::Paid,
+                Some((payment.signature, payment.slot)),
+                detail,
+            )
+            .await?;
+        }
+        dbtx.commit().await?;
+        return Ok(());
+    }
+
+    if !paylink.is_settled() {
+        if paylink.status == PaylinkStatus::Pending {
+            queries::transition_paylink(
+                &mut dbtx,
+                paylink_id,
+                None,
+                PaylinkStatus::PartiallyPaid,
+                None,
+                detail,
+            )
+            .await?;
+        }
+        dbtx.commit().await?;
+        return Ok(());
+    }
+
+    let updated = queries::transition_paylink(
+        &mut dbtx,
         paylink_id,
-        "PAYLINK_MARKED_PAID",
-        serde_json::json!({"signature": signature, "slot": tx.slot}),
+        None,
+        PaylinkStatus::Paid,
+        Some((payment.signature, payment.slot)),
+        detail,
+    )
+    .await?;
+    let paylink = match updated {
+        Some(p) => p,
+        None => {
+            dbtx.commit().await?;
+            return Ok(());
+        }
+    };
+
+    let signatures: Vec<String> = queries::list_payments(&mut dbtx, paylink_id)
+        .await?
+        .into_iter()
+        .map(|p| p.signature)
+        .collect();
+    issue_receipt(
+        &mut dbtx,
+        &paylink,
+        None,
+        paylink.amount_received,
+        payment.slot,
+        signatures,
+        payment.simulated,
     )
     .await?;
 
     dbtx.commit().await?;
     Ok(())
 }
+
+/// Writes a receipt for `amount` paid by `signatures` and records
+/// `RECEIPT_ISSUED`. `payment_id` is set for per-payment receipts.
+async fn issue_receipt(
+    dbtx: &mut Transaction<'_, Postgres>,
+    paylink: &PayLink,
+    payment_id: Option<Uuid>,
+    amount: i64,
+    slot: Option<i64>,
+    signatures: Vec<String>,
+    simulated: bool,
+) -> Result<(), AppError> {
+    let nonce = crypto::random_nonce_hex();
+    let invoice = match (paylink.has_invoice, payment_id) {
+        (true, None) => queries::get_invoice_by_paylink_tx(dbtx, paylink.id)
+            .await?
+            .map(|invoice| invoice.breakdown()),
+        _ => None,
+    };
+    let mut facts = serde_json::json!({
+        "merchantPubkey": paylink.merchant_pubkey,
+        "amount": amount,
+        "mint": paylink.mint,
+        "slot": slot.unwrap_or(0),
+        "invoiceRef": paylink.invoice_ref,
+        "signatures": signatures,
+        "nonce": nonce,
+    });
+    if let (Some(invoice), Some(obj)) = (&invoice, facts.as_object_mut()) {
+        obj.insert("invoice".to_string(), invoice.clone());
+    }
+    if let (Some(quote), Some(obj)) = (&paylink.price_quote, facts.as_object_mut()) {
+        obj.insert("quote".to_string(), serde_json::json!(quote));
+    }
+    let commitment_payload = CommitmentPayload {
+        paylink_id: Some(paylink.id),
+        merchant_pubkey: facts.get("merchantPubkey").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        amount: facts.get("amount").and_then(|v| v.as_i64()),
+        mint: facts.get("mint").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        slot: facts.get("slot").and_then(|v| v.as_i64()),
+        invoice_ref: facts.get("invoiceRef").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        nonce: nonce.clone(),
+        invoice,
+    };
+    let bytes = serde_json::to_vec(&commitment_payload)
+        .map_err(|e| AppError::Other(format!("commitment serialize failed: {}", e)))?;
+    let commitment = crypto::sha256_hex(&bytes);
+
+    let receipt = Receipt {
+        id: Uuid::new_v4(),
+        paylink_id: paylink.id,
+        commitment,
+        issued_at: chrono::Utc::now(),
+        facts,
+        rail: paylink.privacy_rail.clone(),
+        simulated,
+        payment_id,
+        subscription_id: paylink.subscription_id,
+    };
+    queries::insert_receipt(dbtx, &receipt).await?;
+    queries::insert_activity_event_tx(
+        dbtx,
+        paylink.id,
+        "RECEIPT_ISSUED",
+        serde_json::json!({"receiptId": receipt.id, "commitment": receipt.commitment, "paymentId": payment_id}),
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{jobs, solana, test_support};
+
+    #[tokio::test]
+    async fn fixture_webhook_is_paid_and_receipted() {
+        let Some(app) = test_support::app().await else {
+            return;
+        };
+        let state = &app.state;
+        let paylink = test_support::paylink(
+            test_support::FIXTURE_MERCHANT,
+            solana::WRAPPED_SOL_MINT,
+            test_support::FIXTURE_NATIVE_LAMPORTS,
+        );
+        let mut dbtx = state.db.begin().await.unwrap();
+        queries::insert_paylink(&mut dbtx, &paylink).await.unwrap();
+        dbtx.commit().await.unwrap();
+
+        // The delivery is unauthenticated, so its understated inline transfer
+        // is ignored in favour of the fixture transaction.
+        let body = serde_json::json!([{
+            "signature": test_support::FIXTURE_NATIVE_SIGNATURE,
+            "nativeTransfers": [{"toUserAccount": test_support::FIXTURE_MERCHANT, "amount": 1}],
+        }]);
+        let Json(response) = handle(State(state.clone()), HeaderMap::new(), Bytes::from(body.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(response, idempotency::ok_response());
+
+        let job = queries::claim_webhook_job(&state.db)
+            .await
+            .unwrap()
+            .expect("webhook job queued");
+        jobs::webhook::run_job(state, job).await;
+
+        let paid = queries::get_paylink(&state.db, paylink.id).await.unwrap().unwrap();
+        assert_eq!(paid.status, PaylinkStatus::Paid);
+        assert_eq!(paid.amount_received, test_support::FIXTURE_NATIVE_LAMPORTS);
+        assert_eq!(
+            paid.paid_signature.as_deref(),
+            Some(test_support::FIXTURE_NATIVE_SIGNATURE)
+        );
+
+        let receipts: Vec<Receipt> = sqlx::query_as("SELECT * FROM receipts WHERE paylink_id = $1")
+            .bind(paylink.id)
+            .fetch_all(&state.db)
+            .await
+            .unwrap();
+        assert_eq!(receipts.len(), 1);
+        assert!(!receipts[0].simulated);
+
+        let job_status: String = sqlx::query_scalar("SELECT status FROM webhook_jobs WHERE signature = $1")
+            .bind(test_support::FIXTURE_NATIVE_SIGNATURE)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        assert_eq!(job_status, "done");
+
+        app.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn a_signature_pays_one_paylink() {
+        let Some(app) = test_support::app().await else {
+            return;
+        };
+        let state = &app.state;
+        let first = test_support::paylink(test_support::FIXTURE_MERCHANT, solana::WRAPPED_SOL_MINT, 1_000);
+        let second = test_support::paylink(test_support::FIXTURE_MERCHANT, solana::WRAPPED_SOL_MINT, 1_000);
+        let mut dbtx = state.db.begin().await.unwrap();
+        queries::insert_paylink(&mut dbtx, &first).await.unwrap();
+        queries::insert_paylink(&mut dbtx, &second).await.unwrap();
+        dbtx.commit().await.unwrap();
+
+        for paylink in [&first, &second] {
+            let payment = NewPayment {
+                signature: test_support::FIXTURE_NATIVE_SIGNATURE,
+                amount: 1_000,
+                slot: None,
+                block_time: None,
+                simulated: false,
+                events: vec![("PAYMENT_ACCEPTED", serde_json::json!({}))],
+            };
+            record_payment(state, paylink, payment).await.unwrap();
+        }
+
+        let first = queries::get_paylink(&state.db, first.id).await.unwrap().unwrap();
+        let second = queries::get_paylink(&state.db, second.id).await.unwrap().unwrap();
+        assert_eq!(first.status, PaylinkStatus::Paid);
+        assert_eq!(second.status, PaylinkStatus::Pending);
+        assert_eq!(second.amount_received, 0);
+        let accepted: i64 = sqlx::query_scalar(
+            "SELECT count(*) FROM activity_events WHERE type = 'PAYMENT_ACCEPTED'",
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(accepted, 1);
+
+        app.cleanup().await;
+    }
+}
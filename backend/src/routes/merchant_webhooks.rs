@@ -0,0 +1,133 @@
+use axum::{extract::{Path, Query, State}, response::IntoResponse, routing::{get, post}, Json, Router};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app::AppState,
+    auth::{MerchantAuth, SCOPE_WEBHOOKS_WRITE},
+    db::{models::{Merchant, MERCHANT_WEBHOOK_EVENTS}, queries},
+    error::AppError,
+    routes::paylinks::ListResponse,
+    util::{crypto, net},
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetWebhookRequest {
+    pub url: String,
+    #[serde(default)]
+    pub rotate_secret: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookConfigResponse {
+    pub url: Option<String>,
+    /// HMAC secret for `x-webhook-signature`; `None` until a URL is set.
+    pub secret: Option<String>,
+    pub events: &'static [&'static str],
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveryQuery {
+    pub status: Option<String>,
+    pub page: Option<i64>,
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_webhook).put(set_webhook).delete(delete_webhook))
+        .route("/deliveries", get(list_deliveries))
+        .route("/deliveries/:id/redeliver", post(redeliver))
+}
+
+fn config_response(merchant: Merchant) -> WebhookConfigResponse {
+    WebhookConfigResponse {
+        url: merchant.webhook_url,
+        secret: merchant.webhook_secret,
+        events: MERCHANT_WEBHOOK_EVENTS,
+    }
+}
+
+async fn get_webhook(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_WEBHOOKS_WRITE)?;
+    let merchant = queries::get_merchant(&state.db, auth.merchant_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("merchant not found".to_string()))?;
+    Ok(Json(config_response(merchant)))
+}
+
+async fn set_webhook(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
+    Json(payload): Json<SetWebhookRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_WEBHOOKS_WRITE)?;
+    let url = payload.url.trim();
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|_| AppError::BadRequest("invalid webhook url".to_string()))?;
+    net::resolve_public_url(&parsed, state.config.merchant_webhook_allow_private)
+        .await
+        .map_err(AppError::BadRequest)?;
+
+    let merchant = queries::get_merchant(&state.db, auth.merchant_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("merchant not found".to_string()))?;
+    let secret = (payload.rotate_secret || merchant.webhook_secret.is_none())
+        .then(|| format!("whsec_{}", crypto::random_nonce_hex()));
+    let merchant = queries::set_merchant_webhook(&state.db, auth.merchant_id, Some(url), secret.as_deref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("merchant not found".to_string()))?;
+    Ok(Json(config_response(merchant)))
+}
+
+/// Stops new deliveries. Queued ones are still attempted.
+async fn delete_webhook(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_WEBHOOKS_WRITE)?;
+    let merchant = queries::set_merchant_webhook(&state.db, auth.merchant_id, None, None)
+        .await?
+        .ok_or_else(|| AppError::NotFound("merchant not found".to_string()))?;
+    Ok(Json(config_response(merchant)))
+}
+
+async fn list_deliveries(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
+    Query(query): Query<DeliveryQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_WEBHOOKS_WRITE)?;
+    let page = query.page.unwrap_or(1).max(1);
+    let page_size = 20;
+    let (items, total) = queries::list_merchant_webhook_deliveries(
+        &state.db,
+        auth.merchant_id,
+        query.status,
+        page,
+        page_size,
+    )
+    .await?;
+    Ok(Json(ListResponse {
+        items,
+        page,
+        page_size,
+        total,
+    }))
+}
+
+async fn redeliver(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_WEBHOOKS_WRITE)?;
+    let delivery = queries::redeliver_merchant_webhook(&state.db, auth.merchant_id, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("delivery not found or in flight".to_string()))?;
+    Ok(Json(delivery))
+}
@@ -2,3 +2,9 @@ pub mod paylinks;
 pub mod receipts;
 pub mod fees;
 pub mod helius_webhook;
+pub mod merchant_webhooks;
+pub mod admin;
+pub mod auth;
+pub mod public;
+pub mod transaction_request;
+pub mod subscriptions;
@@ -3,36 +3,158 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::{app::AppState, db::{models::PayLink, queries}, error::AppError};
-
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct MemoPolicy {
-    pub enabled: bool,
-    pub template: String,
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ReceiptFieldsPolicy {
-    pub merchant: bool,
-    pub amount: bool,
-    pub token: bool,
-    pub time_window: bool,
-    pub invoice_ref: bool,
-    pub paylink_id: bool,
-}
+use crate::{
+    app::AppState,
+    auth::{MerchantAuth, SCOPE_PAYLINKS_READ, SCOPE_PAYLINKS_WRITE, SCOPE_RECEIPTS_READ},
+    chain::tokens::TokenInfo,
+    db::{
+        models::{
+            AmountTolerance, Customer, Discount, FiatCurrency, Invoice, LineItem, MemoPolicy,
+            PayLink, PaylinkStatus, PriceQuote, ReceiptFieldsPolicy, TaxLine, TokenAmounts,
+        },
+        queries,
+    },
+    error::AppError,
+    pricing,
+    routes::{
+        helius_webhook::{self, NewPayment},
+        transaction_request,
+    },
+    solana::pay::TransferRequest,
+    util::amount::ui_to_base_units,
+};
+
+/// Prefix of the fake signatures recorded by `/paylinks/:id/simulate`.
+pub const SIMULATED_SIGNATURE_PREFIX: &str = "simulated-";
+
+/// Bounds on how long a quote taken on open holds.
+const MIN_QUOTE_TTL_SECS: i32 = 10;
+const MAX_QUOTE_TTL_SECS: i32 = 3600;
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreatePaylinkRequest {
     pub merchant_pubkey: String,
-    pub expected_amount: i64,
+    /// Required unless `open_amount`, `invoice` or `fiat` is set.
+    pub expected_amount: Option<i64>,
     pub mint: String,
     pub expires_at: DateTime<Utc>,
     pub invoice_ref: Option<String>,
     pub memo_policy: MemoPolicy,
     pub receipt_fields_policy: ReceiptFieldsPolicy,
+    #[serde(default)]
+    pub amount_tolerance: AmountTolerance,
+    #[serde(default)]
+    pub multi_use: bool,
+    pub max_uses: Option<i32>,
+    pub max_total: Option<i64>,
+    #[serde(default)]
+    pub open_amount: bool,
+    pub min_amount: Option<i64>,
+    pub max_amount: Option<i64>,
+    /// The invoice whose total becomes the expected amount.
+    pub invoice: Option<InvoiceRequest>,
+    /// Fiat price that the expected amount is converted from.
+    pub fiat: Option<FiatPriceRequest>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FiatPriceRequest {
+    pub currency: FiatCurrency,
+    /// Decimal amount in `currency`, e.g. "49.99".
+    pub amount: String,
+    #[serde(default)]
+    pub lock: QuoteLock,
+    /// How long a quote taken on open holds; defaults to
+    /// `PRICE_QUOTE_TTL_SECS`.
+    pub quote_ttl_secs: Option<i32>,
+}
+
+/// When the token amount is fixed: once at creation, or each time a payer
+/// opens the link after the previous quote lapsed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteLock {
+    #[default]
+    Creation,
+    Open,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvoiceRequest {
+    pub customer: Customer,
+    pub line_items: Vec<LineItem>,
+    #[serde(default)]
+    pub discounts: Vec<Discount>,
+    #[serde(default)]
+    pub taxes: Vec<TaxLine>,
+    pub due_date: Option<DateTime<Utc>>,
+    pub notes: Option<String>,
+}
+
+impl CreatePaylinkRequest {
+    /// Checks the amount and reuse options and returns the expected amount
+    /// to store (0 for open-amount links, the total for invoices, the quoted
+    /// amount for fiat prices).
+    fn validate_amounts(&self, invoice_total: Option<i64>, quoted: Option<i64>) -> Result<i64, AppError> {
+        let bad = |msg: &str| Err(AppError::BadRequest(msg.to_string()));
+        let expected_amount = if let Some(amount) = quoted {
+            if invoice_total.is_some() {
+                return bad("an invoice cannot be combined with a fiat price");
+            }
+            if self.open_amount {
+                return bad("fiat prices only apply to fixed-amount paylinks");
+            }
+            if self.expected_amount.is_some() {
+                return bad("expectedAmount must be omitted when a fiat price is given");
+            }
+            if self.min_amount.is_some() || self.max_amount.is_some() {
+                return bad("minAmount and maxAmount only apply to open-amount paylinks");
+            }
+            amount
+        } else if let Some(total) = invoice_total {
+            if self.open_amount || self.multi_use {
+                return bad("invoices only apply to single-use fixed-amount paylinks");
+            }
+            if self.expected_amount.is_some() {
+                return bad("expectedAmount must be omitted when an invoice is given");
+            }
+            if self.min_amount.is_some() || self.max_amount.is_some() {
+                return bad("minAmount and maxAmount only apply to open-amount paylinks");
+            }
+            total
+        } else if self.open_amount {
+            if self.expected_amount.is_some() {
+                return bad("expectedAmount must be omitted for open-amount paylinks");
+            }
+            if self.min_amount.is_some_and(|v| v <= 0) || self.max_amount.is_some_and(|v| v <= 0) {
+                return bad("minAmount and maxAmount must be positive");
+            }
+            if let (Some(min), Some(max)) = (self.min_amount, self.max_amount) {
+                if min > max {
+                    return bad("minAmount must not exceed maxAmount");
+                }
+            }
+            0
+        } else {
+            if self.min_amount.is_some() || self.max_amount.is_some() {
+                return bad("minAmount and maxAmount only apply to open-amount paylinks");
+            }
+            match self.expected_amount {
+                Some(amount) if amount > 0 => amount,
+                _ => return bad("expectedAmount must be positive"),
+            }
+        };
+        if !self.multi_use && (self.max_uses.is_some() || self.max_total.is_some()) {
+            return bad("maxUses and maxTotal only apply to multi-use paylinks");
+        }
+        if self.max_uses.is_some_and(|v| v <= 0) || self.max_total.is_some_and(|v| v <= 0) {
+            return bad("maxUses and maxTotal must be positive");
+        }
+        Ok(expected_amount)
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -40,7 +162,42 @@ pub struct CreatePaylinkRequest {
 pub struct CreatePaylinkResponse {
     pub paylink: PayLink,
     pub pay_url: String,
+    pub solana_pay_url: String,
     pub privacy_rail: String,
+    pub invoice: Option<Invoice>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdatePaylinkRequest {
+    pub expires_at: Option<DateTime<Utc>>,
+    /// `Some(None)` (JSON `null`) clears the invoice reference.
+    #[serde(default, deserialize_with = "double_option")]
+    pub invoice_ref: Option<Option<String>>,
+}
+
+fn double_option<'de, D>(deserializer: D) -> Result<Option<Option<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcceptPaymentRequest {
+    pub signature: String,
+}
+
+/// Omitting `amount` pays whatever is still owed; open-amount paylinks
+/// require it.
+#[derive(Debug, Deserialize)]
+pub struct SimulatePaymentRequest {
+    pub amount: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelPaylinkRequest {
+    pub reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -83,77 +240,314 @@ pub struct ActivityEventItem {
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/", post(create_paylink).get(list_paylinks))
-        .route("/:id", get(get_paylink))
+        .route("/:id", get(get_paylink).patch(update_paylink))
+        .route("/:id/cancel", post(cancel_paylink))
         .route("/:id/activity", get(get_activity))
+        .route("/:id/invoice", get(get_invoice))
+        .route("/:id/payments", get(list_payments).post(accept_payment))
         .route("/:id/receipts", get(get_paylink_receipts))
         .route("/:id/simulate", post(simulate_paylink))
+        .route(
+            "/:id/tx",
+            get(transaction_request::metadata).post(transaction_request::build),
+        )
+}
+
+/// Paylinks pay out to `merchant_pubkey`, so the merchant must have proven
+/// control of it: a wallet session must be for that wallet, and an API key's
+/// merchant must have linked it through a signed sign-in.
+pub async fn require_wallet_signature(
+    state: &AppState,
+    auth: &MerchantAuth,
+    merchant_pubkey: &str,
+) -> Result<(), AppError> {
+    let proven = match &auth.wallet {
+        Some(wallet) => wallet == merchant_pubkey,
+        None => queries::merchant_has_wallet(&state.db, auth.merchant_id, merchant_pubkey).await?,
+    };
+    if !proven {
+        return Err(AppError::Forbidden(
+            "merchantPubkey has not been verified by a wallet signature".to_string(),
+        ));
+    }
+    Ok(())
 }
 
 async fn create_paylink(
     State(state): State<AppState>,
-    Json(payload): Json<CreatePaylinkRequest>,
+    auth: MerchantAuth,
+    Json(mut payload): Json<CreatePaylinkRequest>,
 ) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_PAYLINKS_WRITE)?;
+    require_wallet_signature(&state, &auth, &payload.merchant_pubkey).await?;
+    payload
+        .amount_tolerance
+        .validate()
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let token = state.tokens.resolve(&payload.mint).await?;
     let id = Uuid::new_v4();
     let now = Utc::now();
+    let invoice = payload
+        .invoice
+        .take()
+        .map(|req| {
+            if req.customer.name.trim().is_empty() {
+                return Err(AppError::BadRequest("invoice customer name is required".to_string()));
+            }
+            let mut invoice = Invoice {
+                id: Uuid::new_v4(),
+                paylink_id: id,
+                customer: sqlx::types::Json(req.customer),
+                line_items: sqlx::types::Json(req.line_items),
+                discounts: sqlx::types::Json(req.discounts),
+                taxes: sqlx::types::Json(req.taxes),
+                subtotal: 0,
+                discount_total: 0,
+                tax_total: 0,
+                total: 0,
+                due_date: req.due_date,
+                notes: req.notes,
+                created_at: now,
+            };
+            invoice
+                .compute()
+                .map_err(|e| AppError::BadRequest(e.to_string()))?;
+            Ok(invoice)
+        })
+        .transpose()?;
+    let quote = match payload.fiat.take() {
+        Some(fiat) => Some(fiat_quote(&state, &token, fiat).await?),
+        None => None,
+    };
+    let expected_amount = payload.validate_amounts(
+        invoice.as_ref().map(|i| i.total),
+        quote.as_ref().map(|q| q.amount),
+    )?;
     let privacy = state.rail.active();
-    let paylink = PayLink {
+    let mut paylink = PayLink {
         id,
         merchant_pubkey: payload.merchant_pubkey,
-        expected_amount: payload.expected_amount,
-        mint: payload.mint,
+        expected_amount,
+        mint: token.address.clone(),
         expires_at: payload.expires_at,
         invoice_ref: payload.invoice_ref,
-        status: "pending".to_string(),
+        status: PaylinkStatus::Pending,
+        amount_received: 0,
         created_at: now,
         paid_signature: None,
         paid_slot: None,
         privacy_rail: privacy.name().to_string(),
+        reference: Some(crate::solana::new_reference_key()),
+        memo_policy: sqlx::types::Json(payload.memo_policy),
+        receipt_fields_policy: sqlx::types::Json(payload.receipt_fields_policy),
+        amount_tolerance: sqlx::types::Json(payload.amount_tolerance),
+        expected_memo: None,
+        merchant_id: Some(auth.merchant_id),
+        multi_use: payload.multi_use,
+        max_uses: payload.max_uses,
+        max_total: payload.max_total,
+        open_amount: payload.open_amount,
+        min_amount: payload.min_amount,
+        max_amount: payload.max_amount,
+        use_count: 0,
+        subscription_id: None,
+        has_invoice: invoice.is_some(),
+        fiat_currency: quote.as_ref().map(|q| q.currency),
+        fiat_amount: quote.as_ref().map(|q| q.fiat_amount),
+        quote_ttl_secs: quote
+            .as_ref()
+            .and_then(|q| q.expires_at.map(|at| (at - q.quoted_at).num_seconds() as i32)),
+        price_quote: quote.map(sqlx::types::Json),
+        token: None,
     };
+    paylink.expected_memo = paylink.memo_policy.render(&paylink);
 
-    queries::insert_paylink(&state.db, &paylink).await?;
-    queries::insert_activity_event(
-        &state.db,
+    let mut dbtx = state.db.begin().await?;
+    queries::insert_paylink(&mut dbtx, &paylink).await?;
+    if let Some(invoice) = &invoice {
+        queries::insert_invoice(&mut dbtx, invoice).await?;
+    }
+    queries::insert_activity_event_tx(
+        &mut dbtx,
         paylink.id,
         "PAYLINK_CREATED",
-        serde_json::json!({"memoPolicy": payload.memo_policy, "receiptFieldsPolicy": payload.receipt_fields_policy}),
+        serde_json::json!({
+            "memoPolicy": paylink.memo_policy,
+            "receiptFieldsPolicy": paylink.receipt_fields_policy,
+            "amountTolerance": paylink.amount_tolerance,
+            "priceQuote": paylink.price_quote,
+        }),
     )
     .await?;
-    queries::insert_activity_event(
-        &state.db,
+    queries::insert_activity_event_tx(
+        &mut dbtx,
         paylink.id,
         "RAIL_SELECTED",
         serde_json::json!({"rail": privacy.name()}),
     )
     .await?;
+    dbtx.commit().await?;
 
-    let pay_url = format!("{}/pay/{}", state.config.base_pay_url.trim_end_matches('/'), paylink.id);
+    let solana_pay_url = transfer_request(&state, &paylink, &token).to_uri();
+    let pay_url = pay_url(&state, &paylink);
+    paylink.token = Some(token_amounts(&token, &paylink));
 
     Ok((
         axum::http::StatusCode::CREATED,
         Json(CreatePaylinkResponse {
             paylink,
             pay_url,
+            solana_pay_url,
             privacy_rail: privacy.name().to_string(),
+            invoice,
         }),
     ))
 }
 
+/// Converts the requested fiat price at the current rate; the quote expires
+/// when it is to be taken again on open.
+async fn fiat_quote(
+    state: &AppState,
+    mint: &TokenInfo,
+    fiat: FiatPriceRequest,
+) -> Result<PriceQuote, AppError> {
+    let fiat_amount = ui_to_base_units(&fiat.amount, fiat.currency.decimals())
+        .filter(|amount| *amount > 0)
+        .ok_or_else(|| {
+            AppError::BadRequest(format!("fiat amount must be a positive {} amount", fiat.currency))
+        })?;
+    let ttl = match fiat.lock {
+        QuoteLock::Creation => {
+            if fiat.quote_ttl_secs.is_some() {
+                return Err(AppError::BadRequest(
+                    "quoteTtlSecs only applies to quotes locked on open".to_string(),
+                ));
+            }
+            None
+        }
+        QuoteLock::Open => {
+            let ttl = fiat.quote_ttl_secs.unwrap_or(state.config.price_quote_ttl_secs);
+            if !(MIN_QUOTE_TTL_SECS..=MAX_QUOTE_TTL_SECS).contains(&ttl) {
+                return Err(AppError::BadRequest(format!(
+                    "quoteTtlSecs must be between {} and {}",
+                    MIN_QUOTE_TTL_SECS, MAX_QUOTE_TTL_SECS
+                )));
+            }
+            Some(ttl)
+        }
+    };
+    pricing::quote(state.prices.as_ref(), mint, fiat.currency, fiat_amount, ttl).await
+}
+
+/// Takes a new quote for a fiat paylink whose quote lapsed before anything
+/// was paid against it, so the payer is shown a current amount. Other
+/// paylinks are returned as they are.
+pub async fn refresh_quote(state: &AppState, paylink: PayLink) -> Result<PayLink, AppError> {
+    if !paylink.needs_requote(Utc::now()) {
+        return Ok(paylink);
+    }
+    let (Some(currency), Some(fiat_amount)) = (paylink.fiat_currency, paylink.fiat_amount) else {
+        return Ok(paylink);
+    };
+    let mint = state.tokens.resolve(&paylink.mint).await?;
+    let quote = pricing::quote(
+        state.prices.as_ref(),
+        &mint,
+        currency,
+        fiat_amount,
+        paylink.quote_ttl_secs,
+    )
+    .await?;
+    match queries::requote_paylink(&state.db, paylink.id, &quote).await? {
+        Some(updated) => Ok(updated),
+        // Paid or settled meanwhile; show it as it now stands.
+        None => Ok(queries::get_paylink(&state.db, paylink.id).await?.unwrap_or(paylink)),
+    }
+}
+
+/// Fills in `paylink.token`. Left unset if the mint cannot be resolved,
+/// which only older paylinks allow.
+pub async fn with_token_amounts(state: &AppState, mut paylink: PayLink) -> PayLink {
+    match state.tokens.resolve(&paylink.mint).await {
+        Ok(token) => paylink.token = Some(token_amounts(&token, &paylink)),
+        Err(err) => eprintln!("no token details for paylink {}: {:?}", paylink.id, err),
+    }
+    paylink
+}
+
+fn token_amounts(token: &TokenInfo, paylink: &PayLink) -> TokenAmounts {
+    TokenAmounts {
+        symbol: token.symbol.clone(),
+        decimals: token.decimals,
+        token_program: token.token_program.clone(),
+        expected_amount: token.ui(paylink.expected_amount),
+        amount_received: token.ui(paylink.amount_received),
+        min_amount: paylink.min_amount.map(|v| token.ui(v)),
+        max_amount: paylink.max_amount.map(|v| token.ui(v)),
+        max_total: paylink.max_total.map(|v| token.ui(v)),
+    }
+}
+
+pub fn pay_url(state: &AppState, paylink: &PayLink) -> String {
+    format!(
+        "{}/pay/{}?reference={}",
+        state.config.base_pay_url.trim_end_matches('/'),
+        paylink.id,
+        paylink.reference.as_deref().unwrap_or_default()
+    )
+}
+
+fn transfer_request(
+    state: &AppState,
+    paylink: &PayLink,
+    mint: &TokenInfo,
+) -> TransferRequest {
+    let message = match &paylink.invoice_ref {
+        Some(invoice_ref) => format!("Invoice {}", invoice_ref),
+        None => format!("PayLink {}", paylink.id),
+    };
+    TransferRequest {
+        recipient: paylink.merchant_pubkey.clone(),
+        // Wallets ask the payer for the amount when it is left out.
+        amount: (!paylink.open_amount).then(|| mint.ui(paylink.expected_amount)),
+        spl_token: (!mint.native).then(|| mint.address.clone()),
+        references: paylink.reference.iter().cloned().collect(),
+        label: Some(state.config.pay_label.clone()),
+        message: Some(message),
+        memo: paylink.expected_memo.clone(),
+    }
+}
+
 async fn list_paylinks(
     State(state): State<AppState>,
+    auth: MerchantAuth,
     Query(query): Query<ListQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    queries::expire_paylinks(&state.db).await?;
+    auth.require(SCOPE_PAYLINKS_READ)?;
     let page = query.page.unwrap_or(1).max(1);
     let page_size = 20;
-    let (items, total) = queries::list_paylinks(
+    // Paylinks store the mint address, so a symbol filter is resolved first.
+    let mint = match query.token {
+        Some(token) => Some(match state.tokens.resolve(&token).await {
+            Ok(info) => info.address,
+            Err(_) => token,
+        }),
+        None => None,
+    };
+    let (paylinks, total) = queries::list_paylinks(
         &state.db,
+        auth.merchant_id,
         query.status,
-        query.token,
+        mint,
         query.q,
         page,
         page_size,
     )
     .await?;
+    let mut items = Vec::with_capacity(paylinks.len());
+    for paylink in paylinks {
+        items.push(with_token_amounts(&state, paylink).await);
+    }
 
     Ok(Json(ListResponse {
         items,
@@ -165,20 +559,103 @@ async fn list_paylinks(
 
 async fn get_paylink(
     State(state): State<AppState>,
+    auth: MerchantAuth,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_PAYLINKS_READ)?;
+    let paylink = queries::get_merchant_paylink(&state.db, auth.merchant_id, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("paylink not found".to_string()))?;
+    Ok(Json(PaylinkResponse {
+        paylink: with_token_amounts(&state, paylink).await,
+    }))
+}
+
+/// Edits a pending paylink: `expiresAt` may only move later, and
+/// `invoiceRef` may change or be cleared. The expected memo is kept as
+/// issued, since payers may already hold a Solana Pay URL carrying it.
+async fn update_paylink(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<UpdatePaylinkRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_PAYLINKS_WRITE)?;
+    let mut paylink = queries::get_merchant_paylink(&state.db, auth.merchant_id, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("paylink not found".to_string()))?;
+    if paylink.status != PaylinkStatus::Pending {
+        return Err(AppError::BadRequest(format!("paylink is {}", paylink.status)));
+    }
+
+    let mut changes = serde_json::Map::new();
+    if let Some(expires_at) = payload.expires_at {
+        if expires_at <= paylink.expires_at || expires_at <= Utc::now() {
+            return Err(AppError::BadRequest(
+                "expiresAt can only be extended to a later time".to_string(),
+            ));
+        }
+        changes.insert(
+            "expiresAt".to_string(),
+            serde_json::json!({"from": paylink.expires_at, "to": expires_at}),
+        );
+        paylink.expires_at = expires_at;
+    }
+    if let Some(invoice_ref) = payload.invoice_ref {
+        if invoice_ref != paylink.invoice_ref {
+            changes.insert(
+                "invoiceRef".to_string(),
+                serde_json::json!({"from": paylink.invoice_ref, "to": invoice_ref}),
+            );
+            paylink.invoice_ref = invoice_ref;
+        }
+    }
+    if changes.is_empty() {
+        return Ok(Json(PaylinkResponse {
+            paylink: with_token_amounts(&state, paylink).await,
+        }));
+    }
+
+    let paylink = queries::update_pending_paylink(
+        &state.db,
+        &paylink,
+        serde_json::json!({ "changes": changes }),
+    )
+    .await?
+    .ok_or_else(|| AppError::BadRequest("paylink is no longer pending".to_string()))?;
+    Ok(Json(PaylinkResponse {
+        paylink: with_token_amounts(&state, paylink).await,
+    }))
+}
+
+async fn cancel_paylink(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
     Path(id): Path<Uuid>,
+    payload: Option<Json<CancelPaylinkRequest>>,
 ) -> Result<impl IntoResponse, AppError> {
-    queries::expire_paylink_by_id(&state.db, id).await?;
-    let paylink = queries::get_paylink(&state.db, id)
+    auth.require(SCOPE_PAYLINKS_WRITE)?;
+    let reason = payload.and_then(|Json(p)| p.reason);
+    if let Some(paylink) =
+        queries::cancel_paylink(&state.db, auth.merchant_id, id, reason.as_deref()).await?
+    {
+        return Ok(Json(PaylinkResponse {
+            paylink: with_token_amounts(&state, paylink).await,
+        }));
+    }
+    let paylink = queries::get_merchant_paylink(&state.db, auth.merchant_id, id)
         .await?
         .ok_or_else(|| AppError::NotFound("paylink not found".to_string()))?;
-    Ok(Json(PaylinkResponse { paylink }))
+    Err(AppError::BadRequest(format!("paylink is {}", paylink.status)))
 }
 
 async fn get_activity(
     State(state): State<AppState>,
+    auth: MerchantAuth,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
-    let events = queries::list_activity_events(&state.db, id).await?;
+    auth.require(SCOPE_PAYLINKS_READ)?;
+    let events = queries::list_activity_events(&state.db, auth.merchant_id, id).await?;
     let items = events
         .into_iter()
         .map(|ev| ActivityEventItem {
@@ -191,11 +668,100 @@ async fn get_activity(
     Ok(Json(ActivityEventResponse { events: items }))
 }
 
+async fn get_invoice(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_PAYLINKS_READ)?;
+    queries::get_merchant_paylink(&state.db, auth.merchant_id, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("paylink not found".to_string()))?;
+    let invoice = queries::get_invoice_by_paylink(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("paylink has no invoice".to_string()))?;
+    Ok(Json(serde_json::json!({ "invoice": invoice })))
+}
+
+async fn list_payments(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_PAYLINKS_READ)?;
+    let items = queries::list_merchant_payments(&state.db, auth.merchant_id, id).await?;
+    Ok(Json(serde_json::json!({"items": items})))
+}
+
+/// Applies a transfer the matcher did not, typically one surfaced as
+/// `TX_VERIFIED_MISMATCH` because its amount was outside the tolerance. The
+/// merchant vouches for the transaction; the amount still comes from chain.
+/// A transaction already recorded for another paylink is refused.
+async fn accept_payment(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<AcceptPaymentRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_PAYLINKS_WRITE)?;
+    let paylink = queries::get_merchant_paylink(&state.db, auth.merchant_id, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("paylink not found".to_string()))?;
+    if paylink.status == PaylinkStatus::Cancelled {
+        return Err(AppError::BadRequest(format!("paylink is {}", paylink.status)));
+    }
+    if !crate::solana::is_signature(&payload.signature) {
+        return Err(AppError::BadRequest("invalid signature".to_string()));
+    }
+    if let Some(payment) = queries::get_payment_by_signature(&state.db, &payload.signature).await? {
+        if payment.paylink_id != paylink.id {
+            return Err(AppError::BadRequest(
+                "transaction is already recorded as a payment for another paylink".to_string(),
+            ));
+        }
+    }
+
+    let mut tx = helius_webhook::load_tx_view(&state, &payload.signature).await?;
+    state.owners.resolve(&mut tx).await?;
+    let amount = tx.amount_received(&paylink.merchant_pubkey, &paylink.mint);
+    if amount <= 0 {
+        return Err(AppError::BadRequest(
+            "transaction sends none of the paylink mint to the merchant".to_string(),
+        ));
+    }
+
+    helius_webhook::record_payment(
+        &state,
+        &paylink,
+        NewPayment {
+            signature: &payload.signature,
+            amount,
+            slot: tx.slot,
+            block_time: tx.timestamp,
+            simulated: false,
+            events: vec![(
+                "PAYMENT_ACCEPTED",
+                serde_json::json!({"signature": payload.signature, "amount": amount}),
+            )],
+        },
+    )
+    .await?;
+
+    let paylink = queries::get_paylink(&state.db, paylink.id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("paylink not found".to_string()))?;
+    Ok(Json(PaylinkResponse {
+        paylink: with_token_amounts(&state, paylink).await,
+    }))
+}
+
 async fn get_paylink_receipts(
     State(state): State<AppState>,
+    auth: MerchantAuth,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
-    let receipts = queries::list_receipts_by_paylink(&state.db, id).await?;
+    auth.require(SCOPE_RECEIPTS_READ)?;
+    let receipts = queries::list_receipts_by_paylink(&state.db, auth.merchant_id, id).await?;
     let items: Vec<serde_json::Value> = receipts
         .into_iter()
         .map(|receipt| serde_json::json!({
@@ -203,6 +769,9 @@ async fn get_paylink_receipts(
             "paylinkId": receipt.paylink_id,
             "commitment": receipt.commitment,
             "issuedAt": receipt.issued_at,
+            "simulated": receipt.simulated,
+            "paymentId": receipt.payment_id,
+            "subscriptionId": receipt.subscription_id,
         }))
         .collect();
 
@@ -211,96 +780,64 @@ async fn get_paylink_receipts(
 
 async fn simulate_paylink(
     State(state): State<AppState>,
+    auth: MerchantAuth,
     Path(id): Path<Uuid>,
+    payload: Option<Json<SimulatePaymentRequest>>,
 ) -> Result<impl IntoResponse, AppError> {
-    let paylink = queries::get_paylink(&state.db, id)
+    auth.require(SCOPE_PAYLINKS_WRITE)?;
+    if !state.config.environment.allows_simulation() {
+        return Err(AppError::Forbidden(
+            "payment simulation is disabled in production".to_string(),
+        ));
+    }
+    let paylink = queries::get_merchant_paylink(&state.db, auth.merchant_id, id)
         .await?
         .ok_or_else(|| AppError::NotFound("paylink not found".to_string()))?;
 
-    if paylink.status == "paid" {
+    if paylink.status == PaylinkStatus::Paid {
         return Ok(Json(serde_json::json!({ "signature": paylink.paid_signature })));
     }
-
-    let signature = format!("simulated-{}", Uuid::new_v4());
-    let slot = 0i64;
-
-    queries::insert_activity_event(
-        &state.db,
-        paylink.id,
-        "WEBHOOK_RECEIVED",
-        serde_json::json!({ "signature": signature }),
-    )
-    .await?;
-
-    queries::insert_activity_event(
-        &state.db,
-        paylink.id,
-        "TX_VERIFIED_MATCH",
-        serde_json::json!({ "signature": signature, "matchedFields": ["memo", "amount", "mint", "merchantPubkey"] }),
-    )
-    .await?;
-
-    let mut dbtx = state.db.begin().await?;
-    let updated = queries::mark_paylink_paid(&mut dbtx, paylink.id, &signature, Some(slot)).await?;
-    let paylink = match updated {
-        Some(p) => p,
-        None => {
-            dbtx.commit().await?;
-            return Ok(Json(serde_json::json!({ "signature": signature })));
+    if !paylink.status.can_transition_to(PaylinkStatus::Paid) {
+        return Err(AppError::BadRequest(format!("paylink is {}", paylink.status)));
+    }
+    let amount = match payload.and_then(|Json(p)| p.amount) {
+        Some(amount) => amount,
+        None if paylink.open_amount => {
+            return Err(AppError::BadRequest(
+                "amount is required for open-amount paylinks".to_string(),
+            ))
         }
+        None => paylink.remaining_amount(),
     };
-
-    let existing_receipt = queries::get_receipt_by_paylink(&state.db, paylink.id).await?;
-    if existing_receipt.is_none() {
-        let nonce = crate::util::crypto::random_nonce_hex();
-        let facts = serde_json::json!({
-            "merchantPubkey": paylink.merchant_pubkey,
-            "amount": paylink.expected_amount,
-            "mint": paylink.mint,
-            "slot": slot,
-            "invoiceRef": paylink.invoice_ref,
-            "nonce": nonce,
-        });
-        let commitment_payload = crate::privacy::types::CommitmentPayload {
-            paylink_id: Some(paylink.id),
-            merchant_pubkey: facts.get("merchantPubkey").and_then(|v| v.as_str()).map(|s| s.to_string()),
-            amount: facts.get("amount").and_then(|v| v.as_i64()),
-            mint: facts.get("mint").and_then(|v| v.as_str()).map(|s| s.to_string()),
-            slot: facts.get("slot").and_then(|v| v.as_i64()),
-            invoice_ref: facts.get("invoiceRef").and_then(|v| v.as_str()).map(|s| s.to_string()),
-            nonce: nonce.clone(),
-        };
-        let bytes = serde_json::to_vec(&commitment_payload)
-            .map_err(|e| AppError::Other(format!("commitment serialize failed: {}", e)))?;
-        let commitment = crate::util::crypto::sha256_hex(&bytes);
-
-        let receipt = crate::db::models::Receipt {
-            id: Uuid::new_v4(),
-            paylink_id: paylink.id,
-            commitment,
-            issued_at: chrono::Utc::now(),
-            facts,
-            rail: paylink.privacy_rail,
-        };
-        queries::insert_receipt(&state.db, &receipt).await?;
-        queries::insert_activity_event(
-            &state.db,
-            paylink.id,
-            "RECEIPT_ISSUED",
-            serde_json::json!({ "receiptId": receipt.id, "commitment": receipt.commitment }),
-        )
-        .await?;
+    if amount <= 0 {
+        return Err(AppError::BadRequest("amount must be positive".to_string()));
     }
+    paylink
+        .check_payment_amount(amount)
+        .map_err(AppError::BadRequest)?;
 
-    queries::insert_activity_event(
-        &state.db,
-        paylink.id,
-        "PAYLINK_MARKED_PAID",
-        serde_json::json!({ "signature": signature, "slot": slot }),
+    let signature = format!("{}{}", SIMULATED_SIGNATURE_PREFIX, Uuid::new_v4());
+    let slot = 0i64;
+
+    helius_webhook::record_payment(
+        &state,
+        &paylink,
+        NewPayment {
+            signature: &signature,
+            amount,
+            slot: Some(slot),
+            block_time: None,
+            simulated: true,
+            events: vec![
+                ("WEBHOOK_RECEIVED", serde_json::json!({ "signature": signature })),
+                (
+                    "TX_VERIFIED_MATCH",
+                    serde_json::json!({ "signature": signature, "matchedFields": ["memo", "amount", "mint", "merchantPubkey"] }),
+                ),
+            ],
+        },
     )
     .await?;
 
-    dbtx.commit().await?;
-
     Ok(Json(serde_json::json!({ "signature": signature })))
 }
@@ -0,0 +1,67 @@
+use axum::{extract::{Path, State}, response::IntoResponse, routing::{get, post}, Json, Router};
+use uuid::Uuid;
+
+use crate::{
+    app::AppState,
+    db::queries,
+    error::AppError,
+    routes::{paylinks::{self, PaylinkResponse}, receipts::{self, ProofRequest}},
+};
+
+/// Payer-facing reads. These are keyed by the unguessable paylink or receipt
+/// id that the payer was given, so they need no API key; everything that
+/// lists or mutates lives behind merchant auth.
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/paylinks/:id", get(get_paylink))
+        .route("/paylinks/:id/invoice", get(get_invoice))
+        .route("/receipts/:id", get(get_receipt))
+        .route("/receipts/:id/proof", post(get_receipt_proof))
+}
+
+async fn get_paylink(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let paylink = queries::get_paylink(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("paylink not found".to_string()))?;
+    let paylink = paylinks::refresh_quote(&state, paylink).await?;
+    Ok(Json(PaylinkResponse {
+        paylink: paylinks::with_token_amounts(&state, paylink).await,
+    }))
+}
+
+/// The invoice as a payer may see it: the breakdown receipts record, without
+/// the customer details or notes. Paylink ids are not secret (they can appear
+/// in on-chain memos), so those stay behind merchant auth.
+async fn get_invoice(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let invoice = queries::get_invoice_by_paylink(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("invoice not found".to_string()))?;
+    Ok(Json(serde_json::json!({ "invoice": invoice.breakdown() })))
+}
+
+async fn get_receipt(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let receipt = queries::get_receipt(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("receipt not found".to_string()))?;
+    Ok(Json(receipts::receipt_json(&state, receipt).await))
+}
+
+async fn get_receipt_proof(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<ProofRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let receipt = queries::get_receipt(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("receipt not found".to_string()))?;
+    Ok(Json(receipts::build_proof(&state, receipt, &payload.disclosed).await?))
+}
@@ -2,7 +2,15 @@ use axum::{extract::{Path, Query, State}, response::IntoResponse, routing::{get,
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::{app::AppState, db::queries, error::AppError, helius::enhanced_tx, privacy::types::CommitmentPayload, util::crypto};
+use crate::{
+    app::AppState,
+    auth::{MerchantAuth, SCOPE_RECEIPTS_READ},
+    db::{models::{Receipt, ReceiptFieldsPolicy}, queries},
+    error::AppError,
+    privacy::types::CommitmentPayload,
+    routes::paylinks::SIMULATED_SIGNATURE_PREFIX,
+    util::crypto,
+};
 
 #[derive(Debug, Deserialize)]
 pub struct ListQuery {
@@ -42,6 +50,7 @@ pub struct RevealedFields {
     pub mint: Option<String>,
     pub slot: Option<i64>,
     pub invoice_ref: Option<String>,
+    pub invoice: Option<serde_json::Value>,
 }
 
 impl RevealedFields {
@@ -65,6 +74,9 @@ impl RevealedFields {
         if self.invoice_ref.is_some() {
             out.push("invoiceRef".to_string());
         }
+        if self.invoice.is_some() {
+            out.push("invoice".to_string());
+        }
         out
     }
 }
@@ -87,17 +99,7 @@ pub struct VerifyDetails {
     pub slot: Option<i64>,
     pub paid_signature: Option<String>,
     pub matched_fields: Vec<String>,
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ReceiptFieldPolicy {
-    pub merchant: bool,
-    pub amount: bool,
-    pub token: bool,
-    pub time_window: bool,
-    pub invoice_ref: bool,
-    pub paylink_id: bool,
+    pub simulated: bool,
 }
 
 pub fn router() -> Router<AppState> {
@@ -110,12 +112,15 @@ pub fn router() -> Router<AppState> {
 
 async fn list_receipts(
     State(state): State<AppState>,
+    auth: MerchantAuth,
     Query(query): Query<ListQuery>,
 ) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_RECEIPTS_READ)?;
     let page = query.page.unwrap_or(1).max(1);
     let page_size = 20;
     let (items, total) = queries::list_receipts_by_merchant(
         &state.db,
+        auth.merchant_id,
         query.merchant,
         page,
         page_size,
@@ -132,18 +137,36 @@ async fn list_receipts(
 
 async fn get_receipt(
     State(state): State<AppState>,
+    auth: MerchantAuth,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
-    let receipt = queries::get_receipt(&state.db, id)
+    auth.require(SCOPE_RECEIPTS_READ)?;
+    let receipt = queries::get_merchant_receipt(&state.db, auth.merchant_id, id)
         .await?
         .ok_or_else(|| AppError::NotFound("receipt not found".to_string()))?;
+    Ok(Json(receipt_json(&state, receipt).await))
+}
 
+/// Receipt as shown to merchants and payers; the commitment nonce stays
+/// private until a proof is requested. `token` gives the paid amount in
+/// whole tokens when the mint resolves.
+pub async fn receipt_json(state: &AppState, receipt: Receipt) -> serde_json::Value {
     let mut facts = receipt.facts.clone();
     if let Some(obj) = facts.as_object_mut() {
         obj.remove("nonce");
     }
+    let mint = facts.get("mint").and_then(|v| v.as_str()).unwrap_or_default();
+    let token = match (state.tokens.resolve(mint).await, facts.get("amount").and_then(|v| v.as_i64())) {
+        (Ok(token), Some(amount)) => serde_json::json!({
+            "symbol": token.symbol,
+            "decimals": token.decimals,
+            "tokenProgram": token.token_program,
+            "amount": token.ui(amount),
+        }),
+        _ => serde_json::Value::Null,
+    };
 
-    Ok(Json(serde_json::json!({
+    serde_json::json!({
         "receipt": {
             "id": receipt.id,
             "paylinkId": receipt.paylink_id,
@@ -151,8 +174,24 @@ async fn get_receipt(
             "issuedAt": receipt.issued_at,
             "facts": facts,
             "rail": receipt.rail,
+            "simulated": receipt.simulated,
+            "paymentId": receipt.payment_id,
+            "subscriptionId": receipt.subscription_id,
+            "token": token,
         }
-    })))
+    })
+}
+
+const SIMULATED_REASON: &str = "receipt was issued for a simulated payment";
+
+/// A stored receipt matched the proof. Simulated receipts have a valid
+/// commitment but no on-chain payment behind them, so they never verify.
+fn receipt_found(receipt: &Receipt) -> (bool, String) {
+    if receipt.simulated {
+        (false, SIMULATED_REASON.to_string())
+    } else {
+        (true, "receipt found".to_string())
+    }
 }
 
 async fn verify_receipt(
@@ -167,6 +206,7 @@ async fn verify_receipt(
         slot: payload.proof.revealed.slot,
         invoice_ref: payload.proof.revealed.invoice_ref.clone(),
         nonce: payload.proof.nonce.clone(),
+        invoice: payload.proof.revealed.invoice.clone(),
     };
     let bytes = serde_json::to_vec(&commitment_payload)
         .map_err(|e| AppError::BadRequest(format!("invalid proof: {}", e)))?;
@@ -181,9 +221,10 @@ async fn verify_receipt(
                 .map(|s| s.to_string());
             if stored_nonce == Some(payload.proof.nonce.clone()) {
                 let matched_fields = payload.proof.revealed.as_matched_fields();
+                let (verified, reason) = receipt_found(&receipt);
                 return Ok(Json(VerifyReceiptResponse {
-                    verified: true,
-                    reason: "receipt found".to_string(),
+                    verified,
+                    reason,
                     details: Some(VerifyDetails {
                         paylink_id: Some(receipt.paylink_id),
                         merchant_pubkey: receipt
@@ -200,6 +241,7 @@ async fn verify_receipt(
                         slot: receipt.facts.get("slot").and_then(|v| v.as_i64()),
                         paid_signature: None,
                         matched_fields,
+                        simulated: receipt.simulated,
                     }),
                 }));
             }
@@ -215,9 +257,10 @@ async fn verify_receipt(
     if let Some(receipt) = queries::get_receipt_by_commitment(&state.db, &commitment).await? {
         let paylink = queries::get_paylink(&state.db, receipt.paylink_id).await?;
         let paid_signature = paylink.and_then(|p| p.paid_signature);
+        let (verified, reason) = receipt_found(&receipt);
         return Ok(Json(VerifyReceiptResponse {
-            verified: true,
-            reason: "receipt found".to_string(),
+            verified,
+            reason,
             details: Some(VerifyDetails {
                 paylink_id: Some(receipt.paylink_id),
                 merchant_pubkey: receipt
@@ -234,6 +277,7 @@ async fn verify_receipt(
                 slot: receipt.facts.get("slot").and_then(|v| v.as_i64()),
                 paid_signature,
                 matched_fields: vec!["commitment".to_string()],
+                simulated: receipt.simulated,
             }),
         }));
     }
@@ -241,7 +285,24 @@ async fn verify_receipt(
     if let Some(paylink_id) = payload.proof.revealed.paylink_id {
         if let Some(paylink) = queries::get_paylink(&state.db, paylink_id).await? {
             if let Some(signature) = paylink.paid_signature.clone() {
-                let tx = enhanced_tx::fetch_enhanced_tx(&state.http, &state.config, &signature).await?;
+                if signature.starts_with(SIMULATED_SIGNATURE_PREFIX) {
+                    return Ok(Json(VerifyReceiptResponse {
+                        verified: false,
+                        reason: SIMULATED_REASON.to_string(),
+                        details: Some(VerifyDetails {
+                            paylink_id: Some(paylink.id),
+                            merchant_pubkey: Some(paylink.merchant_pubkey),
+                            amount: Some(paylink.expected_amount),
+                            mint: Some(paylink.mint),
+                            slot: paylink.paid_slot,
+                            paid_signature: Some(signature),
+                            matched_fields: Vec::new(),
+                            simulated: true,
+                        }),
+                    }));
+                }
+                let mut tx = state.chain.fetch_transaction(&signature).await?;
+                state.owners.resolve(&mut tx).await?;
                 let rail = state.rail.active();
                 let result = rail.verify_payment(&paylink, &tx).await;
                 return Ok(Json(VerifyReceiptResponse {
@@ -255,6 +316,7 @@ async fn verify_receipt(
                         slot: paylink.paid_slot,
                         paid_signature: Some(signature),
                         matched_fields: result.matched_fields,
+                        simulated: false,
                     }),
                 }));
             }
@@ -271,7 +333,7 @@ async fn verify_receipt(
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ProofRequest {
-    pub disclosed: ReceiptFieldPolicy,
+    pub disclosed: ReceiptFieldsPolicy,
 }
 
 #[derive(Debug, Serialize)]
@@ -282,12 +344,34 @@ pub struct ProofResponse {
 
 async fn get_receipt_proof(
     State(state): State<AppState>,
+    auth: MerchantAuth,
     Path(id): Path<Uuid>,
     Json(payload): Json<ProofRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    let receipt = queries::get_receipt(&state.db, id)
+    auth.require(SCOPE_RECEIPTS_READ)?;
+    let receipt = queries::get_merchant_receipt(&state.db, auth.merchant_id, id)
         .await?
         .ok_or_else(|| AppError::NotFound("receipt not found".to_string()))?;
+    Ok(Json(build_proof(&state, receipt, &payload.disclosed).await?))
+}
+
+/// Builds a selective-disclosure proof, refusing fields the paylink's
+/// receipt policy does not allow.
+pub async fn build_proof(
+    state: &AppState,
+    receipt: Receipt,
+    disclosed: &ReceiptFieldsPolicy,
+) -> Result<ProofResponse, AppError> {
+    let paylink = queries::get_paylink(&state.db, receipt.paylink_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("paylink not found".to_string()))?;
+    let disallowed = paylink.receipt_fields_policy.disallowed(disclosed);
+    if !disallowed.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "merchant does not allow disclosing: {}",
+            disallowed.join(", ")
+        )));
+    }
 
     let nonce = receipt
         .facts
@@ -298,19 +382,20 @@ async fn get_receipt_proof(
 
     let facts = &receipt.facts;
     let revealed = serde_json::json!({
-        "paylinkId": if payload.disclosed.paylink_id { Some(receipt.paylink_id) } else { None },
-        "merchantPubkey": if payload.disclosed.merchant { facts.get("merchantPubkey").and_then(|v| v.as_str()) } else { None },
-        "amount": if payload.disclosed.amount { facts.get("amount").and_then(|v| v.as_i64()) } else { None },
-        "mint": if payload.disclosed.token { facts.get("mint").and_then(|v| v.as_str()) } else { None },
-        "slot": if payload.disclosed.time_window { facts.get("slot").and_then(|v| v.as_i64()) } else { None },
-        "invoiceRef": if payload.disclosed.invoice_ref { facts.get("invoiceRef").and_then(|v| v.as_str()) } else { None },
+        "paylinkId": if disclosed.paylink_id { Some(receipt.paylink_id) } else { None },
+        "merchantPubkey": if disclosed.merchant { facts.get("merchantPubkey").and_then(|v| v.as_str()) } else { None },
+        "amount": if disclosed.amount { facts.get("amount").and_then(|v| v.as_i64()) } else { None },
+        "mint": if disclosed.token { facts.get("mint").and_then(|v| v.as_str()) } else { None },
+        "slot": if disclosed.time_window { facts.get("slot").and_then(|v| v.as_i64()) } else { None },
+        "invoiceRef": if disclosed.invoice_ref { facts.get("invoiceRef").and_then(|v| v.as_str()) } else { None },
+        "invoice": if disclosed.invoice { facts.get("invoice") } else { None },
     });
 
-    Ok(Json(ProofResponse {
+    Ok(ProofResponse {
         proof: serde_json::json!({
             "commitment": receipt.commitment,
             "nonce": nonce,
             "revealed": revealed
         }),
-    }))
+    })
 }
@@ -0,0 +1,380 @@
+use axum::{extract::{Path, Query, State}, response::IntoResponse, routing::{get, post}, Json, Router};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    app::AppState,
+    auth::{MerchantAuth, SCOPE_PAYLINKS_READ, SCOPE_PAYLINKS_WRITE, SCOPE_RECEIPTS_READ},
+    db::{
+        models::{
+            AmountTolerance, CycleStatus, IntervalUnit, MemoPolicy, PayLink, PaylinkStatus,
+            ReceiptFieldsPolicy, Subscription, SubscriptionCycle, SubscriptionStatus,
+        },
+        queries,
+    },
+    error::AppError,
+    routes::paylinks::{self, ListResponse},
+};
+
+const MAX_TRIAL_DAYS: i64 = 366;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateSubscriptionRequest {
+    pub merchant_pubkey: String,
+    /// Charged each cycle, in base units of `mint`.
+    pub amount: i64,
+    pub mint: String,
+    pub interval: BillingInterval,
+    /// Defaults to now.
+    pub anchor_at: Option<DateTime<Utc>>,
+    pub trial_days: Option<i64>,
+    pub customer_ref: Option<String>,
+    pub memo_policy: MemoPolicy,
+    pub receipt_fields_policy: ReceiptFieldsPolicy,
+    #[serde(default)]
+    pub amount_tolerance: AmountTolerance,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BillingInterval {
+    pub unit: IntervalUnit,
+    #[serde(default = "one")]
+    pub count: i32,
+}
+
+fn one() -> i32 {
+    1
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelSubscriptionRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListQuery {
+    pub status: Option<String>,
+    pub page: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionResponse {
+    pub subscription: Subscription,
+    pub cycles: Vec<SubscriptionCycle>,
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", post(create_subscription).get(list_subscriptions))
+        .route("/:id", get(get_subscription))
+        .route("/:id/cancel", post(cancel_subscription))
+        .route("/:id/events", get(get_events))
+        .route("/:id/receipts", get(get_receipts))
+}
+
+async fn create_subscription(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
+    Json(payload): Json<CreateSubscriptionRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_PAYLINKS_WRITE)?;
+    paylinks::require_wallet_signature(&state, &auth, &payload.merchant_pubkey).await?;
+    if payload.amount <= 0 {
+        return Err(AppError::BadRequest("amount must be positive".to_string()));
+    }
+    if payload.interval.count <= 0 {
+        return Err(AppError::BadRequest("interval.count must be positive".to_string()));
+    }
+    if payload.trial_days.is_some_and(|d| !(1..=MAX_TRIAL_DAYS).contains(&d)) {
+        return Err(AppError::BadRequest(format!(
+            "trialDays must be between 1 and {}",
+            MAX_TRIAL_DAYS
+        )));
+    }
+    payload
+        .amount_tolerance
+        .validate()
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let token = state.tokens.resolve(&payload.mint).await?;
+
+    let now = Utc::now();
+    let mut subscription = Subscription {
+        id: Uuid::new_v4(),
+        merchant_id: auth.merchant_id,
+        merchant_pubkey: payload.merchant_pubkey,
+        amount: payload.amount,
+        mint: token.address,
+        interval_unit: payload.interval.unit,
+        interval_count: payload.interval.count,
+        anchor_at: payload.anchor_at.unwrap_or(now),
+        trial_ends_at: payload.trial_days.map(|d| now + Duration::days(d)),
+        status: if payload.trial_days.is_some() {
+            SubscriptionStatus::Trialing
+        } else {
+            SubscriptionStatus::Active
+        },
+        customer_ref: payload.customer_ref,
+        memo_policy: sqlx::types::Json(payload.memo_policy),
+        receipt_fields_policy: sqlx::types::Json(payload.receipt_fields_policy),
+        amount_tolerance: sqlx::types::Json(payload.amount_tolerance),
+        next_cycle_index: 0,
+        next_cycle_at: now,
+        created_at: now,
+        cancelled_at: None,
+    };
+    let schedule_error = || AppError::BadRequest("schedule has no billable cycle".to_string());
+    subscription.next_cycle_index = subscription.first_cycle_index(now).ok_or_else(schedule_error)?;
+    subscription.next_cycle_at = subscription
+        .cycle_start(subscription.next_cycle_index)
+        .ok_or_else(schedule_error)?;
+
+    let mut dbtx = state.db.begin().await?;
+    queries::insert_subscription(&mut dbtx, &subscription).await?;
+    queries::insert_subscription_event_tx(
+        &mut dbtx,
+        subscription.id,
+        "SUBSCRIPTION_CREATED",
+        serde_json::json!({
+            "anchorAt": subscription.anchor_at,
+            "trialEndsAt": subscription.trial_ends_at,
+            "firstCycleAt": subscription.next_cycle_at,
+        }),
+    )
+    .await?;
+    let mut cycles = Vec::new();
+    if subscription.next_cycle_at <= now {
+        let (updated, cycle) = issue_cycle(&state, &mut dbtx, &subscription).await?;
+        subscription = updated;
+        cycles.push(cycle);
+    }
+    dbtx.commit().await?;
+
+    Ok((
+        axum::http::StatusCode::CREATED,
+        Json(SubscriptionResponse {
+            subscription,
+            cycles,
+        }),
+    ))
+}
+
+/// Creates the paylink for the subscription's next cycle, which lasts until
+/// the following cycle starts, and records `SUBSCRIPTION_CYCLE_ISSUED` (and
+/// `SUBSCRIPTION_TRIAL_ENDED` for the first cycle after a trial). The
+/// subscription must be locked by `dbtx`.
+pub async fn issue_cycle(
+    state: &AppState,
+    dbtx: &mut Transaction<'_, Postgres>,
+    subscription: &Subscription,
+) -> Result<(Subscription, SubscriptionCycle), AppError> {
+    let index = subscription.next_cycle_index;
+    let period_start = subscription.next_cycle_at;
+    let period_end = subscription
+        .cycle_start(index + 1)
+        .ok_or_else(|| AppError::Other(format!("subscription {} schedule overflow", subscription.id)))?;
+    let privacy = state.rail.active();
+    let now = Utc::now();
+
+    let mut paylink = PayLink {
+        id: Uuid::new_v4(),
+        merchant_pubkey: subscription.merchant_pubkey.clone(),
+        expected_amount: subscription.amount,
+        mint: subscription.mint.clone(),
+        expires_at: period_end,
+        invoice_ref: subscription.cycle_invoice_ref(index),
+        status: PaylinkStatus::Pending,
+        amount_received: 0,
+        created_at: now,
+        paid_signature: None,
+        paid_slot: None,
+        privacy_rail: privacy.name().to_string(),
+        reference: Some(crate::solana::new_reference_key()),
+        memo_policy: subscription.memo_policy.clone(),
+        receipt_fields_policy: subscription.receipt_fields_policy.clone(),
+        amount_tolerance: subscription.amount_tolerance,
+        expected_memo: None,
+        merchant_id: Some(subscription.merchant_id),
+        multi_use: false,
+        max_uses: None,
+        max_total: None,
+        open_amount: false,
+        min_amount: None,
+        max_amount: None,
+        use_count: 0,
+        subscription_id: Some(subscription.id),
+        has_invoice: false,
+        fiat_currency: None,
+        fiat_amount: None,
+        price_quote: None,
+        quote_ttl_secs: None,
+        token: None,
+    };
+    paylink.expected_memo = paylink.memo_policy.render(&paylink);
+
+    queries::insert_paylink(dbtx, &paylink).await?;
+    queries::insert_activity_event_tx(
+        dbtx,
+        paylink.id,
+        "PAYLINK_CREATED",
+        serde_json::json!({
+            "memoPolicy": paylink.memo_policy,
+            "receiptFieldsPolicy": paylink.receipt_fields_policy,
+            "amountTolerance": paylink.amount_tolerance,
+            "subscriptionId": subscription.id,
+            "cycleIndex": index,
+        }),
+    )
+    .await?;
+    queries::insert_activity_event_tx(
+        dbtx,
+        paylink.id,
+        "RAIL_SELECTED",
+        serde_json::json!({"rail": privacy.name()}),
+    )
+    .await?;
+
+    let cycle = SubscriptionCycle {
+        id: Uuid::new_v4(),
+        subscription_id: subscription.id,
+        cycle_index: index,
+        period_start,
+        period_end,
+        paylink_id: paylink.id,
+        status: CycleStatus::Open,
+        created_at: now,
+        settled_at: None,
+    };
+    let updated = queries::insert_subscription_cycle(dbtx, &cycle, period_end).await?;
+    if subscription.status == SubscriptionStatus::Trialing {
+        queries::insert_subscription_event_tx(
+            dbtx,
+            subscription.id,
+            "SUBSCRIPTION_TRIAL_ENDED",
+            serde_json::json!({"trialEndsAt": subscription.trial_ends_at}),
+        )
+        .await?;
+    }
+    queries::insert_subscription_event_tx(
+        dbtx,
+        subscription.id,
+        "SUBSCRIPTION_CYCLE_ISSUED",
+        serde_json::json!({
+            "cycleIndex": index,
+            "periodStart": period_start,
+            "periodEnd": period_end,
+            "paylinkId": paylink.id,
+            "payUrl": paylinks::pay_url(state, &paylink),
+        }),
+    )
+    .await?;
+    Ok((updated, cycle))
+}
+
+async fn list_subscriptions(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
+    Query(query): Query<ListQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_PAYLINKS_READ)?;
+    let page = query.page.unwrap_or(1).max(1);
+    let page_size = 20;
+    let (items, total) =
+        queries::list_subscriptions(&state.db, auth.merchant_id, query.status, page, page_size)
+            .await?;
+
+    Ok(Json(ListResponse {
+        items,
+        page,
+        page_size,
+        total,
+    }))
+}
+
+async fn get_subscription(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_PAYLINKS_READ)?;
+    let subscription = queries::get_merchant_subscription(&state.db, auth.merchant_id, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("subscription not found".to_string()))?;
+    let cycles = queries::list_subscription_cycles(&state.db, id).await?;
+    Ok(Json(SubscriptionResponse {
+        subscription,
+        cycles,
+    }))
+}
+
+async fn cancel_subscription(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
+    Path(id): Path<Uuid>,
+    payload: Option<Json<CancelSubscriptionRequest>>,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_PAYLINKS_WRITE)?;
+    let reason = payload.and_then(|Json(p)| p.reason);
+    let subscription =
+        match queries::cancel_subscription(&state.db, auth.merchant_id, id, reason.as_deref())
+            .await?
+        {
+            Some(subscription) => subscription,
+            None => {
+                let subscription =
+                    queries::get_merchant_subscription(&state.db, auth.merchant_id, id)
+                        .await?
+                        .ok_or_else(|| AppError::NotFound("subscription not found".to_string()))?;
+                return Err(AppError::BadRequest(format!(
+                    "subscription is {}",
+                    subscription.status
+                )));
+            }
+        };
+    let cycles = queries::list_subscription_cycles(&state.db, id).await?;
+    Ok(Json(SubscriptionResponse {
+        subscription,
+        cycles,
+    }))
+}
+
+async fn get_events(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_PAYLINKS_READ)?;
+    let events = queries::list_subscription_events(&state.db, auth.merchant_id, id).await?;
+    let items: Vec<serde_json::Value> = events
+        .into_iter()
+        .map(|ev| serde_json::json!({"type": ev.r#type, "at": ev.at, "detail": ev.detail}))
+        .collect();
+    Ok(Json(serde_json::json!({"events": items})))
+}
+
+async fn get_receipts(
+    State(state): State<AppState>,
+    auth: MerchantAuth,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    auth.require(SCOPE_RECEIPTS_READ)?;
+    let items: Vec<serde_json::Value> =
+        queries::list_receipts_by_subscription(&state.db, auth.merchant_id, id)
+            .await?
+            .into_iter()
+            .map(|receipt| serde_json::json!({
+                "id": receipt.id,
+                "paylinkId": receipt.paylink_id,
+                "commitment": receipt.commitment,
+                "issuedAt": receipt.issued_at,
+                "simulated": receipt.simulated,
+                "paymentId": receipt.payment_id,
+                "subscriptionId": receipt.subscription_id,
+            }))
+            .collect();
+    Ok(Json(serde_json::json!({"items": items})))
+}
@@ -0,0 +1,172 @@
+use axum::{extract::{Path, Query, State}, response::IntoResponse, Json};
+use base64::Engine;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app::AppState,
+    db::{models::PaylinkStatus, queries},
+    error::AppError,
+    routes::paylinks,
+    solana::tx::{self, decode_pubkey, Instruction},
+};
+
+/// Plenty for a memo, creating the merchant's token account and one transfer;
+/// keeps the priority fee bounded.
+const COMPUTE_UNIT_LIMIT: u32 = 100_000;
+
+#[derive(Debug, Serialize)]
+pub struct MetadataResponse {
+    pub label: String,
+    pub icon: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransactionRequest {
+    pub account: String,
+}
+
+/// Open-amount paylinks take the amount, in base units, from the transaction
+/// request URL (`/paylinks/:id/tx?amount=…`).
+#[derive(Debug, Deserialize)]
+pub struct TransactionQuery {
+    pub amount: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransactionResponse {
+    pub transaction: String,
+    pub message: String,
+}
+
+/// Solana Pay transaction request, GET leg: what the wallet shows before the
+/// payer connects.
+pub async fn metadata(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    queries::get_paylink(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("paylink not found".to_string()))?;
+    Ok(Json(MetadataResponse {
+        label: state.config.pay_label.clone(),
+        icon: state.config.pay_icon_url.clone(),
+    }))
+}
+
+/// Solana Pay transaction request, POST leg: builds the unsigned payment for
+/// `account` so the amount, mint, memo and reference are fixed by us rather
+/// than by the wallet.
+pub async fn build(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<TransactionQuery>,
+    Json(payload): Json<TransactionRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let paylink = queries::get_paylink(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("paylink not found".to_string()))?;
+    let open = matches!(paylink.status, PaylinkStatus::Pending | PaylinkStatus::PartiallyPaid);
+    if !open || paylink.expires_at <= Utc::now() {
+        return Err(AppError::BadRequest(format!("paylink is {}", paylink.status)));
+    }
+    let paylink = paylinks::refresh_quote(&state, paylink).await?;
+    let payer = decode_pubkey(&payload.account)
+        .ok_or_else(|| AppError::BadRequest("invalid account".to_string()))?;
+    let merchant = decode_pubkey(&paylink.merchant_pubkey)
+        .ok_or_else(|| AppError::Other("paylink has an invalid merchant pubkey".to_string()))?;
+    // A partially paid link asks only for what is still owed.
+    let amount = if paylink.open_amount {
+        let amount = query
+            .amount
+            .ok_or_else(|| AppError::BadRequest("amount is required for open-amount paylinks".to_string()))?;
+        paylink
+            .check_payment_amount(amount)
+            .map_err(AppError::BadRequest)?;
+        amount
+    } else {
+        paylink.remaining_amount()
+    };
+    let amount = u64::try_from(amount)
+        .ok()
+        .filter(|a| *a > 0)
+        .ok_or_else(|| AppError::BadRequest("amount must be positive".to_string()))?;
+
+    let mint = state.tokens.resolve(&paylink.mint).await?;
+    let mut create_account = None;
+    let mut transfer = if mint.native {
+        tx::system_transfer(payer, merchant, amount)
+    } else {
+        let mint_key = decode_pubkey(&mint.address)
+            .ok_or_else(|| AppError::Other("invalid mint address".to_string()))?;
+        let token_program = decode_pubkey(&mint.token_program)
+            .ok_or_else(|| AppError::Other("invalid token program".to_string()))?;
+        // The merchant may not hold this token yet; the payer funds its
+        // account, and the instruction is a no-op when it exists.
+        create_account = Some(tx::create_associated_token_account_idempotent(
+            payer,
+            merchant,
+            mint_key,
+            token_program,
+        ));
+        tx::transfer_checked(
+            token_program,
+            tx::associated_token_address(&payer, &mint_key, &token_program),
+            mint_key,
+            tx::associated_token_address(&merchant, &mint_key, &token_program),
+            payer,
+            amount,
+            mint.decimals,
+        )
+    };
+    if let Some(reference) = paylink.reference.as_deref().and_then(decode_pubkey) {
+        transfer.accounts.push(tx::AccountMeta::readonly(reference, false));
+    }
+
+    let fees = state
+        .chain
+        .priority_fee_estimate(None, Some(vec![paylink.merchant_pubkey.clone()]))
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("priority fee estimate failed, using defaults: {:?}", err);
+            crate::chain::default_priority_fees()
+        });
+
+    let mut instructions: Vec<Instruction> = vec![
+        tx::set_compute_unit_limit(COMPUTE_UNIT_LIMIT),
+        tx::set_compute_unit_price(fees.recommended.max(0) as u64),
+    ];
+    // The memo is only written when the merchant's memo policy asks for one;
+    // the reference key already ties the transfer to the paylink.
+    if let Some(memo) = paylink.expected_memo.as_deref() {
+        instructions.push(tx::memo(memo));
+    }
+    instructions.extend(create_account);
+    instructions.push(transfer);
+
+    let blockhash = state.chain.latest_blockhash().await?;
+    let blockhash = decode_pubkey(&blockhash)
+        .ok_or_else(|| AppError::Other("invalid blockhash".to_string()))?;
+    let bytes = tx::unsigned_transaction(payer, &instructions, blockhash);
+
+    // The endpoint is public and wallets may call it repeatedly, so only the
+    // first request from each account is recorded.
+    queries::insert_activity_event_once(
+        &state.db,
+        paylink.id,
+        "TX_REQUESTED",
+        ("account", &payload.account),
+        serde_json::json!({"account": payload.account, "computeUnitPrice": fees.recommended}),
+    )
+    .await?;
+
+    let message = match &paylink.invoice_ref {
+        Some(invoice_ref) => format!("Invoice {}", invoice_ref),
+        None => format!("PayLink {}", paylink.id),
+    };
+    Ok(Json(TransactionResponse {
+        transaction: base64::engine::general_purpose::STANDARD.encode(bytes),
+        message,
+    }))
+}
@@ -0,0 +1,44 @@
+pub mod pay;
+pub mod tx;
+
+use rand::RngCore;
+
+pub const MEMO_PROGRAM_ID: &str = "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr";
+pub const MEMO_V1_PROGRAM_ID: &str = "Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo";
+
+pub const WRAPPED_SOL_MINT: &str = "So11111111111111111111111111111111111111112";
+pub const USDC_MAINNET_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+pub const USDC_DEVNET_MINT: &str = "4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU";
+
+pub fn is_native_mint(mint: &str) -> bool {
+    mint == "SOL" || mint == WRAPPED_SOL_MINT
+}
+
+pub fn usdc_mint(cluster: &str) -> &'static str {
+    match cluster {
+        "mainnet" => USDC_MAINNET_MINT,
+        _ => USDC_DEVNET_MINT,
+    }
+}
+
+pub fn is_memo_program(program_id: &str) -> bool {
+    program_id == MEMO_PROGRAM_ID || program_id == MEMO_V1_PROGRAM_ID
+}
+
+/// A fresh Solana Pay `reference`: 32 random bytes, base58-encoded. It never
+/// needs a private key; payers add it as a read-only account so the payment
+/// can be found by address.
+pub fn new_reference_key() -> String {
+    let mut buf = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut buf);
+    bs58::encode(buf).into_string()
+}
+
+pub fn is_pubkey(s: &str) -> bool {
+    matches!(bs58::decode(s).into_vec(), Ok(bytes) if bytes.len() == 32)
+}
+
+/// A transaction signature is a base58-encoded 64-byte ed25519 signature.
+pub fn is_signature(s: &str) -> bool {
+    matches!(bs58::decode(s).into_vec(), Ok(bytes) if bytes.len() == 64)
+}
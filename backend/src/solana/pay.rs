@@ -0,0 +1,61 @@
+/// A Solana Pay transfer request, rendered as a `solana:` URI that wallets
+/// can open directly or from a QR code.
+#[derive(Debug, Clone, Default)]
+pub struct TransferRequest {
+    pub recipient: String,
+    /// Amount in UI units, e.g. "1.5".
+    pub amount: Option<String>,
+    /// Omitted for native SOL.
+    pub spl_token: Option<String>,
+    pub references: Vec<String>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+    pub memo: Option<String>,
+}
+
+impl TransferRequest {
+    pub fn to_uri(&self) -> String {
+        let mut params: Vec<(&str, &str)> = Vec::new();
+        if let Some(amount) = &self.amount {
+            params.push(("amount", amount));
+        }
+        if let Some(token) = &self.spl_token {
+            params.push(("spl-token", token));
+        }
+        for reference in &self.references {
+            params.push(("reference", reference));
+        }
+        if let Some(label) = &self.label {
+            params.push(("label", label));
+        }
+        if let Some(message) = &self.message {
+            params.push(("message", message));
+        }
+        if let Some(memo) = &self.memo {
+            params.push(("memo", memo));
+        }
+
+        let mut uri = format!("solana:{}", self.recipient);
+        for (i, (key, value)) in params.iter().enumerate() {
+            uri.push(if i == 0 { '?' } else { '&' });
+            uri.push_str(key);
+            uri.push('=');
+            uri.push_str(&encode_component(value));
+        }
+        uri
+    }
+}
+
+/// Percent-encodes like JavaScript's `encodeURIComponent`, which is what the
+/// Solana Pay reference implementation uses to parse these URIs.
+fn encode_component(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => out.push(byte as char),
+            b'-' | b'_' | b'.' | b'!' | b'~' | b'*' | b'\'' | b'(' | b')' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
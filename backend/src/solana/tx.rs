@@ -0,0 +1,314 @@
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use sha2::{Digest, Sha256};
+
+use super::MEMO_PROGRAM_ID;
+
+pub const SYSTEM_PROGRAM_ID: &str = "11111111111111111111111111111111";
+pub const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
+pub const COMPUTE_BUDGET_PROGRAM_ID: &str = "ComputeBudget111111111111111111111111111111";
+
+pub type Pubkey = [u8; 32];
+
+pub fn decode_pubkey(s: &str) -> Option<Pubkey> {
+    bs58::decode(s).into_vec().ok()?.try_into().ok()
+}
+
+fn program(id: &str) -> Pubkey {
+    decode_pubkey(id).expect("program ids are valid pubkeys")
+}
+
+#[derive(Debug, Clone)]
+pub struct AccountMeta {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl AccountMeta {
+    pub fn writable(pubkey: Pubkey, is_signer: bool) -> Self {
+        Self { pubkey, is_signer, is_writable: true }
+    }
+
+    pub fn readonly(pubkey: Pubkey, is_signer: bool) -> Self {
+        Self { pubkey, is_signer, is_writable: false }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    pub program_id: Pubkey,
+    pub accounts: Vec<AccountMeta>,
+    pub data: Vec<u8>,
+}
+
+pub fn set_compute_unit_limit(units: u32) -> Instruction {
+    let mut data = vec![2u8];
+    data.extend_from_slice(&units.to_le_bytes());
+    Instruction { program_id: program(COMPUTE_BUDGET_PROGRAM_ID), accounts: Vec::new(), data }
+}
+
+pub fn set_compute_unit_price(micro_lamports: u64) -> Instruction {
+    let mut data = vec![3u8];
+    data.extend_from_slice(&micro_lamports.to_le_bytes());
+    Instruction { program_id: program(COMPUTE_BUDGET_PROGRAM_ID), accounts: Vec::new(), data }
+}
+
+pub fn memo(text: &str) -> Instruction {
+    Instruction {
+        program_id: program(MEMO_PROGRAM_ID),
+        accounts: Vec::new(),
+        data: text.as_bytes().to_vec(),
+    }
+}
+
+pub fn system_transfer(from: Pubkey, to: Pubkey, lamports: u64) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction {
+        program_id: program(SYSTEM_PROGRAM_ID),
+        accounts: vec![AccountMeta::writable(from, true), AccountMeta::writable(to, false)],
+        data,
+    }
+}
+
+/// SPL Token `TransferChecked`; works for both the token and token-2022
+/// programs.
+pub fn transfer_checked(
+    token_program: Pubkey,
+    source: Pubkey,
+    mint: Pubkey,
+    destination: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Instruction {
+    let mut data = vec![12u8];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(decimals);
+    Instruction {
+        program_id: token_program,
+        accounts: vec![
+            AccountMeta::writable(source, false),
+            AccountMeta::readonly(mint, false),
+            AccountMeta::writable(destination, false),
+            AccountMeta::readonly(owner, true),
+        ],
+        data,
+    }
+}
+
+/// Associated Token Account `CreateIdempotent`: creates `wallet`'s token
+/// account for `mint`, paid by `payer`, and succeeds if it already exists.
+pub fn create_associated_token_account_idempotent(
+    payer: Pubkey,
+    wallet: Pubkey,
+    mint: Pubkey,
+    token_program: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: program(ASSOCIATED_TOKEN_PROGRAM_ID),
+        accounts: vec![
+            AccountMeta::writable(payer, true),
+            AccountMeta::writable(associated_token_address(&wallet, &mint, &token_program), false),
+            AccountMeta::readonly(wallet, false),
+            AccountMeta::readonly(mint, false),
+            AccountMeta::readonly(program(SYSTEM_PROGRAM_ID), false),
+            AccountMeta::readonly(token_program, false),
+        ],
+        data: vec![1],
+    }
+}
+
+pub fn associated_token_address(wallet: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    find_program_address(
+        &[wallet, token_program, mint],
+        &program(ASSOCIATED_TOKEN_PROGRAM_ID),
+    )
+}
+
+fn find_program_address(seeds: &[&[u8]], program_id: &Pubkey) -> Pubkey {
+    for bump in (0..=255u8).rev() {
+        let mut hasher = Sha256::new();
+        for seed in seeds {
+            hasher.update(seed);
+        }
+        hasher.update([bump]);
+        hasher.update(program_id);
+        hasher.update(b"ProgramDerivedAddress");
+        let candidate: Pubkey = hasher.finalize().into();
+        if CompressedEdwardsY(candidate).decompress().is_none() {
+            return candidate;
+        }
+    }
+    unreachable!("no viable bump seed")
+}
+
+/// Serializes a legacy transaction with `fee_payer` as the only required
+/// signer slot left zeroed, ready for a wallet to sign.
+pub fn unsigned_transaction(
+    fee_payer: Pubkey,
+    instructions: &[Instruction],
+    recent_blockhash: Pubkey,
+) -> Vec<u8> {
+    let mut metas: Vec<AccountMeta> = vec![AccountMeta::writable(fee_payer, true)];
+    for ix in instructions {
+        for meta in ix.accounts.iter().cloned().chain(std::iter::once(AccountMeta::readonly(ix.program_id, false))) {
+            match metas.iter_mut().find(|m| m.pubkey == meta.pubkey) {
+                Some(existing) => {
+                    existing.is_signer |= meta.is_signer;
+                    existing.is_writable |= meta.is_writable;
+                }
+                None => metas.push(meta),
+            }
+        }
+    }
+    // Fee payer stays first; the rest are grouped signer/writable as the
+    // message format requires. The sort is stable so order is otherwise kept.
+    metas[1..].sort_by_key(|m| (!m.is_signer, !m.is_writable));
+
+    let num_signers = metas.iter().filter(|m| m.is_signer).count();
+    let readonly_signed = metas.iter().filter(|m| m.is_signer && !m.is_writable).count();
+    let readonly_unsigned = metas.iter().filter(|m| !m.is_signer && !m.is_writable).count();
+    let index_of = |key: &Pubkey| metas.iter().position(|m| &m.pubkey == key).unwrap() as u8;
+
+    let mut message = vec![num_signers as u8, readonly_signed as u8, readonly_unsigned as u8];
+    push_compact_len(&mut message, metas.len());
+    for meta in &metas {
+        message.extend_from_slice(&meta.pubkey);
+    }
+    message.extend_from_slice(&recent_blockhash);
+    push_compact_len(&mut message, instructions.len());
+    for ix in instructions {
+        message.push(index_of(&ix.program_id));
+        push_compact_len(&mut message, ix.accounts.len());
+        for meta in &ix.accounts {
+            message.push(index_of(&meta.pubkey));
+        }
+        push_compact_len(&mut message, ix.data.len());
+        message.extend_from_slice(&ix.data);
+    }
+
+    let mut tx = Vec::with_capacity(1 + num_signers * 64 + message.len());
+    push_compact_len(&mut tx, num_signers);
+    tx.resize(tx.len() + num_signers * 64, 0);
+    tx.extend_from_slice(&message);
+    tx
+}
+
+/// Solana's "shortvec" length prefix.
+fn push_compact_len(out: &mut Vec<u8>, len: usize) {
+    let mut rem = len as u16;
+    loop {
+        let mut byte = (rem & 0x7f) as u8;
+        rem >>= 7;
+        if rem == 0 {
+            out.push(byte);
+            break;
+        }
+        byte |= 0x80;
+        out.push(byte);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WALLET: &str = "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin";
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    fn key(s: &str) -> Pubkey {
+        decode_pubkey(s).unwrap()
+    }
+
+    fn encode(key: &Pubkey) -> String {
+        bs58::encode(key).into_string()
+    }
+
+    #[test]
+    fn derives_associated_token_addresses_per_program() {
+        let wallet = key(WALLET);
+        let mint = key(USDC);
+        assert_eq!(
+            encode(&associated_token_address(&wallet, &mint, &program(TOKEN_PROGRAM_ID))),
+            "F4YA4H7HeXLCvjLRKdh56FgE4cyHpPqLP1VCM6fEqEmX"
+        );
+        assert_eq!(
+            encode(&associated_token_address(&wallet, &mint, &program(TOKEN_2022_PROGRAM_ID))),
+            "D8jEyFGKCYMSSieSQykhVgbZRZunSditY2PQa7oJt1tu"
+        );
+        // Needs a bump below 255.
+        assert_eq!(
+            encode(&associated_token_address(
+                &wallet,
+                &key("4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU"),
+                &program(TOKEN_PROGRAM_ID)
+            )),
+            "5K3tmjKHJcLujL7SLZcEjutEZnrzMruUXTzQV6z5cQT6"
+        );
+    }
+
+    #[test]
+    fn create_idempotent_targets_the_wallet_ata() {
+        let payer = [7u8; 32];
+        let ix = create_associated_token_account_idempotent(payer, key(WALLET), key(USDC), program(TOKEN_PROGRAM_ID));
+        assert_eq!(ix.program_id, program(ASSOCIATED_TOKEN_PROGRAM_ID));
+        assert_eq!(ix.data, vec![1]);
+        assert_eq!(encode(&ix.accounts[1].pubkey), "F4YA4H7HeXLCvjLRKdh56FgE4cyHpPqLP1VCM6fEqEmX");
+        assert!(ix.accounts[0].is_signer && ix.accounts[0].is_writable);
+        assert!(ix.accounts[1].is_writable && !ix.accounts[1].is_signer);
+        assert!(ix.accounts[2..].iter().all(|m| !m.is_signer && !m.is_writable));
+    }
+
+    #[test]
+    fn serializes_an_unsigned_legacy_transaction() {
+        let payer = [1u8; 32];
+        let merchant = [2u8; 32];
+        let reference = [3u8; 32];
+        let blockhash = [9u8; 32];
+        let mut transfer = system_transfer(payer, merchant, 5);
+        transfer.accounts.push(AccountMeta::readonly(reference, false));
+        let instructions = vec![memo("hi"), transfer];
+
+        let tx = unsigned_transaction(payer, &instructions, blockhash);
+
+        // One zeroed signature slot for the payer.
+        assert_eq!(tx[0], 1);
+        assert!(tx[1..65].iter().all(|b| *b == 0));
+        let message = &tx[65..];
+        // Header: 1 signer, 0 read-only signers, 3 read-only unsigned. Keys
+        // keep first-use order within each group.
+        assert_eq!(&message[..3], &[1, 0, 3]);
+        assert_eq!(message[3], 5);
+        let keys: Vec<&[u8]> = message[4..4 + 5 * 32].chunks(32).collect();
+        assert_eq!(keys[0], &payer);
+        assert_eq!(keys[1], &merchant);
+        assert_eq!(keys[2], &program(MEMO_PROGRAM_ID));
+        assert_eq!(keys[3], &reference);
+        assert_eq!(keys[4], &program(SYSTEM_PROGRAM_ID));
+
+        let rest = &message[4 + 5 * 32..];
+        assert_eq!(&rest[..32], &blockhash);
+        let rest = &rest[32..];
+        assert_eq!(rest[0], 2);
+        // Memo: program index 2, no accounts, 2 bytes of data.
+        assert_eq!(&rest[1..6], &[2, 0, 2, b'h', b'i']);
+        // Transfer: program index 4, accounts payer, merchant, reference.
+        assert_eq!(&rest[6..11], &[4, 3, 0, 1, 3]);
+        assert_eq!(rest[11] as usize, 12);
+        assert_eq!(&rest[12..16], &2u32.to_le_bytes());
+        assert_eq!(&rest[16..24], &5u64.to_le_bytes());
+        assert_eq!(rest.len(), 24);
+    }
+
+    #[test]
+    fn encodes_shortvec_lengths() {
+        for (len, expected) in [(0usize, vec![0u8]), (127, vec![0x7f]), (128, vec![0x80, 0x01]), (16384, vec![0x80, 0x80, 0x01])] {
+            let mut out = Vec::new();
+            push_compact_len(&mut out, len);
+            assert_eq!(out, expected, "length {}", len);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Setup shared by tests that need a database. Each test gets a fresh database
+//! next to the one in `DATABASE_URL`, so tests never see each other's rows.
+//! Tests using it are skipped when `DATABASE_URL` is not set.
+
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use sqlx::{types::Json, Connection, Executor, PgConnection};
+use uuid::Uuid;
+
+use crate::{
+    app::AppState,
+    chain,
+    config::{Config, Environment},
+    db::{
+        self,
+        models::{AmountTolerance, MemoPolicy, PayLink, PaylinkStatus, ReceiptFieldsPolicy},
+    },
+    pricing,
+    privacy::rail::RailSelector,
+};
+
+/// Merchant paid by the native transfer in the fixture transaction
+/// `FIXTURE_NATIVE_SIGNATURE`.
+pub const FIXTURE_MERCHANT: &str = "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin";
+pub const FIXTURE_NATIVE_SIGNATURE: &str =
+    "4Jgs8aGj66iQCt88mNPXyjoJpPaCpYwurgym6AtJ2dBf9HZBqwV8ioNXwUw9Sq4iCHjVFPCDthZ6FbPoeeWjXZwh";
+pub const FIXTURE_NATIVE_LAMPORTS: i64 = 1_000_000;
+
+pub struct TestApp {
+    pub state: AppState,
+    server_url: String,
+    database: String,
+}
+
+/// An `AppState` on a fresh, migrated database, using the fixture chain
+/// provider, the transparent rail and no webhook secret.
+pub async fn app() -> Option<TestApp> {
+    dotenvy::dotenv().ok();
+    let url = match std::env::var("DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            eprintln!("DATABASE_URL is not set; skipping database test");
+            return None;
+        }
+    };
+    let (server_url, _) = url.rsplit_once('/').expect("DATABASE_URL has a database name");
+    let database = format!("receiptless_test_{}", Uuid::new_v4().simple());
+    let mut conn = PgConnection::connect(&url).await.expect("connect to DATABASE_URL");
+    conn.execute(format!("CREATE DATABASE {}", database).as_str())
+        .await
+        .expect("create test database");
+
+    let db = db::connect(&format!("{}/{}", server_url, database))
+        .await
+        .expect("connect to test database");
+    sqlx::migrate!("./migrations").run(&db).await.expect("migrate test database");
+
+    let mut config = Config::from_env();
+    config.environment = Environment::Dev;
+    config.webhook_secrets = Vec::new();
+    config.chain_provider = "fixture".to_string();
+    config.chain_fixture_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures").to_string();
+    config.privacy_rail = "transparent".to_string();
+    config.price_source = "static".to_string();
+    config.token_registry_path = String::new();
+
+    let http = reqwest::Client::new();
+    let chain = chain::from_config(&config, http.clone()).expect("fixture chain provider");
+    let state = AppState {
+        db,
+        rail: RailSelector::new(&config.privacy_rail),
+        owners: Arc::new(chain::owners::OwnerResolver::new(chain.clone())),
+        tokens: Arc::new(chain::tokens::TokenRegistry::load(
+            chain.clone(),
+            &config.helius_cluster,
+            &config.token_registry_path,
+        )),
+        prices: pricing::from_config(&config, http),
+        chain,
+        config,
+    };
+    Some(TestApp {
+        state,
+        server_url: server_url.to_string(),
+        database,
+    })
+}
+
+impl TestApp {
+    /// Drops the test database, closing any connection the pool has not shut
+    /// down yet. Not reached when a test fails, which leaves the database
+    /// behind for inspection.
+    pub async fn cleanup(self) {
+        self.state.db.close().await;
+        let mut conn = PgConnection::connect(&format!("{}/postgres", self.server_url))
+            .await
+            .expect("connect to postgres database");
+        conn.execute(format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", self.database).as_str())
+            .await
+            .expect("drop test database");
+    }
+}
+
+/// A pending single-use paylink with no policies set.
+pub fn paylink(merchant_pubkey: &str, mint: &str, expected_amount: i64) -> PayLink {
+    let now = Utc::now();
+    PayLink {
+        id: Uuid::new_v4(),
+        merchant_pubkey: merchant_pubkey.to_string(),
+        expected_amount,
+        mint: mint.to_string(),
+        expires_at: now + Duration::hours(1),
+        invoice_ref: None,
+        status: PaylinkStatus::Pending,
+        amount_received: 0,
+        created_at: now,
+        paid_signature: None,
+        paid_slot: None,
+        privacy_rail: "transparent".to_string(),
+        reference: None,
+        memo_policy: Json(MemoPolicy {
+            enabled: false,
+            template: String::new(),
+        }),
+        receipt_fields_policy: Json(ReceiptFieldsPolicy {
+            merchant: true,
+            amount: true,
+            token: true,
+            time_window: true,
+            invoice_ref: true,
+            paylink_id: true,
+            invoice: false,
+        }),
+        amount_tolerance: Json(AmountTolerance::default()),
+        expected_memo: None,
+        merchant_id: None,
+        multi_use: false,
+        max_uses: None,
+        max_total: None,
+        open_amount: false,
+        min_amount: None,
+        max_amount: None,
+        use_count: 0,
+        subscription_id: None,
+        has_invoice: false,
+        fiat_currency: None,
+        fiat_amount: None,
+        price_quote: None,
+        quote_ttl_secs: None,
+        token: None,
+    }
+}
@@ -0,0 +1,135 @@
+/// Converts a decimal UI amount ("1.25", "1e-6") into integer base units for
+/// a token with `decimals` places. Returns `None` if the value has more
+/// precision than the token supports or does not fit in an `i64`.
+pub fn ui_to_base_units(ui: &str, decimals: u8) -> Option<i64> {
+    let ui = ui.trim();
+    let (mantissa, exponent) = match ui.find(['e', 'E']) {
+        Some(idx) => (&ui[..idx], ui[idx + 1..].parse::<i32>().ok()?),
+        None => (ui, 0),
+    };
+    if mantissa.starts_with('-') {
+        return None;
+    }
+    let mantissa = mantissa.trim_start_matches('+');
+    let (whole, frac) = match mantissa.split_once('.') {
+        Some((w, f)) => (w, f),
+        None => (mantissa, ""),
+    };
+    if whole.is_empty() && frac.is_empty() {
+        return None;
+    }
+    if !whole.chars().chain(frac.chars()).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let digits = format!("{}{}", whole, frac);
+    if digits.chars().all(|c| c == '0') {
+        return Some(0);
+    }
+    let shift = i32::from(decimals)
+        .checked_add(exponent)?
+        .checked_sub(i32::try_from(frac.len()).ok()?)?;
+    let digits = if shift >= 0 {
+        // An i64 has at most 19 digits; refuse before padding a huge exponent.
+        if shift > 19 {
+            return None;
+        }
+        format!("{}{}", digits, "0".repeat(shift as usize))
+    } else {
+        let cut = digits.len().saturating_sub(shift.unsigned_abs() as usize);
+        let (keep, dropped) = digits.split_at(cut);
+        if dropped.chars().any(|c| c != '0') {
+            return None;
+        }
+        keep.to_string()
+    };
+
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    digits.parse::<i64>().ok()
+}
+
+/// Formats integer base units as a plain decimal string ("1500000", 6 ->
+/// "1.5"), without trailing zeros or exponent notation.
+pub fn base_units_to_ui(amount: i64, decimals: u8) -> String {
+    let digits = amount.unsigned_abs().to_string();
+    let decimals = decimals as usize;
+    let padded = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, frac) = padded.split_at(padded.len() - decimals);
+    let frac = frac.trim_end_matches('0');
+    let sign = if amount < 0 { "-" } else { "" };
+    if frac.is_empty() {
+        format!("{}{}", sign, whole)
+    } else {
+        format!("{}{}.{}", sign, whole, frac)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_ui_amounts_to_base_units() {
+        assert_eq!(ui_to_base_units("1.25", 6), Some(1_250_000));
+        assert_eq!(ui_to_base_units(" 1 ", 9), Some(1_000_000_000));
+        assert_eq!(ui_to_base_units(".5", 2), Some(50));
+        assert_eq!(ui_to_base_units("5.", 2), Some(500));
+        assert_eq!(ui_to_base_units("+0.000001", 6), Some(1));
+        assert_eq!(ui_to_base_units("1.2500", 2), Some(125));
+        assert_eq!(ui_to_base_units("0", 6), Some(0));
+        assert_eq!(ui_to_base_units("0.000", 0), Some(0));
+        assert_eq!(ui_to_base_units("42", 0), Some(42));
+    }
+
+    #[test]
+    fn converts_exponent_notation() {
+        assert_eq!(ui_to_base_units("1e-6", 6), Some(1));
+        assert_eq!(ui_to_base_units("2.5E3", 0), Some(2_500));
+        assert_eq!(ui_to_base_units("1e+2", 2), Some(10_000));
+        assert_eq!(ui_to_base_units("1500e-3", 1), Some(15));
+        assert_eq!(ui_to_base_units("1500e-3", 0), None);
+        assert_eq!(ui_to_base_units("0e999999999", 6), Some(0));
+    }
+
+    #[test]
+    fn refuses_unrepresentable_amounts() {
+        assert_eq!(ui_to_base_units("0.0000001", 6), None);
+        assert_eq!(ui_to_base_units("1e-7", 6), None);
+        assert_eq!(ui_to_base_units("-1", 6), None);
+        assert_eq!(ui_to_base_units("", 6), None);
+        assert_eq!(ui_to_base_units(".", 6), None);
+        assert_eq!(ui_to_base_units("1.2.3", 6), None);
+        assert_eq!(ui_to_base_units("1,5", 6), None);
+        assert_eq!(ui_to_base_units("abc", 6), None);
+        assert_eq!(ui_to_base_units("1e", 6), None);
+        assert_eq!(ui_to_base_units("9223372036854775808", 0), None);
+        assert_eq!(ui_to_base_units("9223372036854775807", 0), Some(i64::MAX));
+        assert_eq!(ui_to_base_units("1e999999999", 6), None);
+        assert_eq!(ui_to_base_units("1e2147483647", 9), None);
+        assert_eq!(ui_to_base_units("1e-2147483648", 9), None);
+        // A shift of exactly i32::MIN once the fraction digits are counted.
+        assert_eq!(ui_to_base_units("1.11e-2147483648", 2), None);
+    }
+
+    #[test]
+    fn formats_base_units() {
+        assert_eq!(base_units_to_ui(1_500_000, 6), "1.5");
+        assert_eq!(base_units_to_ui(1, 9), "0.000000001");
+        assert_eq!(base_units_to_ui(0, 6), "0");
+        assert_eq!(base_units_to_ui(42, 0), "42");
+        assert_eq!(base_units_to_ui(2_000_000, 6), "2");
+        assert_eq!(base_units_to_ui(-1_250, 3), "-1.25");
+        assert_eq!(base_units_to_ui(i64::MIN, 0), "-9223372036854775808");
+    }
+
+    #[test]
+    fn round_trips() {
+        for (amount, decimals) in [(1_234_567, 6), (1, 9), (i64::MAX, 9), (100, 2), (7, 0)] {
+            let ui = base_units_to_ui(amount, decimals);
+            assert_eq!(ui_to_base_units(&ui, decimals), Some(amount), "{}", ui);
+        }
+    }
+}
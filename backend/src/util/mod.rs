@@ -1,3 +1,6 @@
+pub mod amount;
 pub mod crypto;
 pub mod json_scan;
 pub mod idempotency;
+pub mod net;
+pub mod webhook_auth;
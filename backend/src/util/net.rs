@@ -0,0 +1,133 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use reqwest::Url;
+
+/// Checks a merchant webhook URL before it is stored or called: it must be
+/// http(s) and its host must resolve only to public addresses, so merchants
+/// cannot point deliveries at our own network (localhost, RFC 1918 ranges,
+/// the cloud metadata service at 169.254.169.254, ...). Returns the resolved
+/// addresses, which the caller should connect to rather than resolving the
+/// host again. `allow_private` skips the address check, for local testing.
+pub async fn resolve_public_url(url: &Url, allow_private: bool) -> Result<Vec<SocketAddr>, String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("webhook url must be http or https".to_string());
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| "webhook url has no host".to_string())?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| "webhook url has no port".to_string())?;
+    if !allow_private && (host.eq_ignore_ascii_case("localhost") || host.ends_with(".localhost")) {
+        return Err("webhook url must not point at localhost".to_string());
+    }
+
+    let addrs: Vec<SocketAddr> = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| format!("could not resolve {}: {}", host, e))?
+            .collect(),
+    };
+    if addrs.is_empty() {
+        return Err(format!("could not resolve {}", host));
+    }
+    if !allow_private {
+        if let Some(addr) = addrs.iter().find(|a| !is_public(a.ip())) {
+            return Err(format!("webhook url resolves to non-public address {}", addr.ip()));
+        }
+    }
+    Ok(addrs)
+}
+
+/// Whether `ip` is a globally routable unicast address.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_public_v4(v4),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // Carrier-grade NAT, 100.64.0.0/10.
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments and benchmarking, 192.0.0.0/24 and 198.18.0.0/15.
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        || (a == 198 && (b == 18 || b == 19))
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local, fc00::/7.
+        || (first & 0xfe00) == 0xfc00
+        // Link-local, fe80::/10.
+        || (first & 0xffc0) == 0xfe80
+        // Documentation, 2001:db8::/32.
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8)
+        // NAT64 and IPv4-compatible forms can reach internal IPv4 hosts.
+        || (first == 0x0064 && ip.segments()[1] == 0xff9b)
+        || ip.to_ipv4().is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn classifies_addresses() {
+        for private in [
+            "127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "0.0.0.0",
+            "100.64.0.1", "255.255.255.255", "::1", "::", "fd00::1", "fe80::1", "::ffff:127.0.0.1",
+            "64:ff9b::a00:1",
+        ] {
+            assert!(!is_public(private.parse().unwrap()), "{} should not be public", private);
+        }
+        for public in ["1.1.1.1", "93.184.216.34", "172.32.0.1", "2606:4700:4700::1111"] {
+            assert!(is_public(public.parse().unwrap()), "{} should be public", public);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_private_and_non_http_urls() {
+        for bad in [
+            "http://localhost:8080/hook",
+            "http://api.localhost/hook",
+            "http://127.0.0.1/hook",
+            "http://[::1]/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.0.0.5/hook",
+            "ftp://93.184.216.34/hook",
+        ] {
+            assert!(resolve_public_url(&url(bad), false).await.is_err(), "{} was accepted", bad);
+        }
+    }
+
+    #[tokio::test]
+    async fn accepts_public_ips_and_private_ones_when_allowed() {
+        let addrs = resolve_public_url(&url("https://93.184.216.34/hook"), false).await.unwrap();
+        assert_eq!(addrs, vec!["93.184.216.34:443".parse().unwrap()]);
+        let addrs = resolve_public_url(&url("http://127.0.0.1:9000/hook"), true).await.unwrap();
+        assert_eq!(addrs, vec!["127.0.0.1:9000".parse().unwrap()]);
+    }
+}
@@ -0,0 +1,168 @@
+use axum::http::HeaderMap;
+
+use crate::{config::Config, error::AppError, util::crypto};
+
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+pub const SECRET_HEADER: &str = "x-webhook-secret";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebhookAuthMode {
+    /// Shared secret sent verbatim, either in `Authorization` (how Helius sends
+    /// the webhook `authHeader`) or in the legacy `x-webhook-secret` header.
+    Header,
+    /// `x-webhook-signature: t=<unix>,v1=<hex hmac-sha256 of "<t>.<body>">`.
+    Hmac,
+}
+
+impl WebhookAuthMode {
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "hmac" => Self::Hmac,
+            _ => Self::Header,
+        }
+    }
+}
+
+/// Checks an incoming webhook against every configured secret so that a new
+/// secret can be rolled out before the old one is removed. Returns whether the
+/// request was authenticated; without any secret it is accepted unauthenticated
+/// outside production.
+pub fn verify(config: &Config, headers: &HeaderMap, body: &[u8]) -> Result<bool, AppError> {
+    if config.webhook_secrets.is_empty() {
+        if config.environment.requires_webhook_auth() {
+            return Err(AppError::Unauthorized(
+                "webhook secret is not configured".to_string(),
+            ));
+        }
+        return Ok(false);
+    }
+    let ok = match config.webhook_auth_mode {
+        WebhookAuthMode::Header => verify_header(&config.webhook_secrets, headers),
+        WebhookAuthMode::Hmac => verify_hmac(
+            &config.webhook_secrets,
+            headers,
+            body,
+            config.webhook_tolerance_secs,
+            chrono::Utc::now().timestamp(),
+        )?,
+    };
+    if !ok {
+        return Err(AppError::Unauthorized("invalid webhook secret".to_string()));
+    }
+    Ok(true)
+}
+
+fn verify_header(secrets: &[String], headers: &HeaderMap) -> bool {
+    let candidates = [
+        headers
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok()),
+        headers.get(SECRET_HEADER).and_then(|v| v.to_str().ok()),
+    ];
+    candidates.into_iter().flatten().any(|provided| {
+        let provided = provided.strip_prefix("Bearer ").unwrap_or(provided);
+        secrets
+            .iter()
+            .any(|secret| crypto::constant_time_eq(provided.as_bytes(), secret.as_bytes()))
+    })
+}
+
+fn verify_hmac(
+    secrets: &[String],
+    headers: &HeaderMap,
+    body: &[u8],
+    tolerance_secs: i64,
+    now: i64,
+) -> Result<bool, AppError> {
+    let header = headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| AppError::Unauthorized("missing webhook signature".to_string()))?;
+
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", v)) => timestamp = v.parse::<i64>().ok(),
+            Some(("v1", v)) => signatures.push(v.to_string()),
+            _ => {}
+        }
+    }
+    let timestamp = timestamp
+        .ok_or_else(|| AppError::Unauthorized("missing webhook signature timestamp".to_string()))?;
+    if (now - timestamp).abs() > tolerance_secs {
+        return Err(AppError::Unauthorized(
+            "webhook signature timestamp outside tolerance".to_string(),
+        ));
+    }
+
+    Ok(secrets.iter().any(|secret| {
+        let expected = sign(secret, timestamp, body);
+        signatures
+            .iter()
+            .any(|sig| crypto::constant_time_eq(sig.as_bytes(), expected.as_bytes()))
+    }))
+}
+
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut message = format!("{}.", timestamp).into_bytes();
+    message.extend_from_slice(body);
+    crypto::hmac_sha256_hex(secret.as_bytes(), &message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    const BODY: &[u8] = br#"[{"signature":"abc"}]"#;
+    const NOW: i64 = 1_700_000_000;
+
+    fn headers(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(SIGNATURE_HEADER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn secrets() -> Vec<String> {
+        vec!["old".to_string(), "new".to_string()]
+    }
+
+    #[test]
+    fn accepts_a_signature_from_any_configured_secret() {
+        for secret in ["old", "new"] {
+            let header = format!("t={},v1={}", NOW, sign(secret, NOW, BODY));
+            assert!(verify_hmac(&secrets(), &headers(&header), BODY, 300, NOW).unwrap());
+        }
+    }
+
+    #[test]
+    fn accepts_any_of_several_v1_signatures() {
+        let header = format!("t={}, v1=deadbeef, v1={}", NOW, sign("new", NOW, BODY));
+        assert!(verify_hmac(&secrets(), &headers(&header), BODY, 300, NOW).unwrap());
+    }
+
+    #[test]
+    fn rejects_a_wrong_secret_or_tampered_body() {
+        let header = format!("t={},v1={}", NOW, sign("other", NOW, BODY));
+        assert!(!verify_hmac(&secrets(), &headers(&header), BODY, 300, NOW).unwrap());
+
+        let header = format!("t={},v1={}", NOW, sign("new", NOW, BODY));
+        assert!(!verify_hmac(&secrets(), &headers(&header), b"[]", 300, NOW).unwrap());
+    }
+
+    #[test]
+    fn rejects_timestamps_outside_tolerance() {
+        let signed_at = NOW - 301;
+        let header = format!("t={},v1={}", signed_at, sign("new", signed_at, BODY));
+        assert!(verify_hmac(&secrets(), &headers(&header), BODY, 300, NOW).is_err());
+        assert!(verify_hmac(&secrets(), &headers(&header), BODY, 301, NOW).unwrap());
+    }
+
+    #[test]
+    fn rejects_missing_header_or_timestamp() {
+        assert!(verify_hmac(&secrets(), &HeaderMap::new(), BODY, 300, NOW).is_err());
+        let header = format!("v1={}", sign("new", NOW, BODY));
+        assert!(verify_hmac(&secrets(), &headers(&header), BODY, 300, NOW).is_err());
+    }
+}
@@ -12,6 +12,10 @@ const statusConfig: Record<string, { label: string; className: string }> = {
     label: 'Pending',
     className: 'bg-warning/20 text-warning border-warning/30',
   },
+  partially_paid: {
+    label: 'Partially paid',
+    className: 'bg-warning/20 text-warning border-warning/30',
+  },
   paid: {
     label: 'Paid',
     className: 'bg-success/20 text-success border-success/30',
@@ -52,7 +56,7 @@ export function StatusPill({ status, size = 'md', className }: StatusPillProps)
     >
       <span className={cn(
         'mr-1.5 h-1.5 w-1.5 rounded-full',
-        status === 'pending' && 'bg-warning animate-pulse',
+        (status === 'pending' || status === 'partially_paid') && 'bg-warning animate-pulse',
         status === 'paid' && 'bg-success',
         status === 'valid' && 'bg-success',
         (status === 'expired' || status === 'unknown') && 'bg-muted-foreground',
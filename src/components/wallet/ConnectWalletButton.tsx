@@ -1,7 +1,8 @@
-import { FC, useEffect } from 'react';
+import { FC, useEffect, useState } from 'react';
 import { useWallet } from '@solana/wallet-adapter-react';
 import { useWalletModal } from '@solana/wallet-adapter-react-ui';
-import { Wallet, LogOut } from 'lucide-react';
+import { Wallet, LogOut, KeyRound } from 'lucide-react';
+import { toast } from 'sonner';
 import { Button } from '@/components/ui/button';
 import {
   DropdownMenu,
@@ -12,11 +13,15 @@ import {
 } from '@/components/ui/dropdown-menu';
 import { Identicon } from '@/components/ui/Identicon';
 import { CopyButton } from '@/components/ui/CopyButton';
-import { setMerchantPubkey } from '@/lib/merchant';
+import { getMerchantSettings, setMerchantPubkey, setMerchantSettings } from '@/lib/merchant';
+import { api } from '@/lib/api';
+
+const toBase64 = (bytes: Uint8Array) => btoa(String.fromCharCode(...bytes));
 
 export const ConnectWalletButton: FC = () => {
-  const { publicKey, disconnect, connected } = useWallet();
+  const { publicKey, disconnect, connected, signMessage } = useWallet();
   const { setVisible } = useWalletModal();
+  const [signedIn, setSignedIn] = useState(() => !!getMerchantSettings().sessionToken);
 
   // Auto-sync connected wallet with merchant settings
   useEffect(() => {
@@ -29,7 +34,42 @@ export const ConnectWalletButton: FC = () => {
     setVisible(true);
   };
 
+  const handleSignIn = async () => {
+    if (!publicKey || !signMessage) {
+      toast.error('This wallet cannot sign messages');
+      return;
+    }
+    const pubkey = publicKey.toBase58();
+    const challenge = await api.getSignInNonce(pubkey);
+    if (challenge.error || !challenge.data) {
+      toast.error(challenge.error || 'Could not start sign-in');
+      return;
+    }
+    try {
+      const signature = await signMessage(new TextEncoder().encode(challenge.data.message));
+      const session = await api.verifySignIn({
+        pubkey,
+        nonce: challenge.data.nonce,
+        signature: toBase64(signature),
+      });
+      if (session.error || !session.data) {
+        toast.error(session.error || 'Sign-in failed');
+        return;
+      }
+      setMerchantSettings({ sessionToken: session.data.token });
+      setSignedIn(true);
+      toast.success('Signed in with wallet');
+    } catch (err) {
+      toast.error((err as Error).message);
+    }
+  };
+
   const handleDisconnect = async () => {
+    if (getMerchantSettings().sessionToken) {
+      await api.signOut();
+      setMerchantSettings({ sessionToken: undefined });
+      setSignedIn(false);
+    }
     await disconnect();
     setMerchantPubkey('');
   };
@@ -62,6 +102,12 @@ export const ConnectWalletButton: FC = () => {
               </div>
             </div>
             <DropdownMenuSeparator />
+            {!signedIn && (
+              <DropdownMenuItem onClick={handleSignIn}>
+                <KeyRound className="mr-2 h-4 w-4" />
+                Sign in with wallet
+              </DropdownMenuItem>
+            )}
             <DropdownMenuItem onClick={handleDisconnect} className="text-destructive">
               <LogOut className="mr-2 h-4 w-4" />
               Disconnect
@@ -8,10 +8,13 @@ import type {
   ApiResponse,
   PaginatedResponse,
   PayLinkStatus,
+  AmountTolerance,
+  InvoiceInput,
+  FiatPriceInput,
   TokenType,
   ReceiptFieldPolicy,
 } from './types';
-import { getMerchantPubkey } from './merchant';
+import { getMerchantPubkey, getMerchantSettings } from './merchant';
 
 const API_BASE = import.meta.env.VITE_API_BASE ?? '/api';
 const SOL_MINT = 'So11111111111111111111111111111111111111112';
@@ -25,11 +28,15 @@ const defaultReceiptFields: ReceiptFieldPolicy = {
   paylinkId: true,
 };
 
-const mapMintToToken = (mint: string): { token: TokenType; tokenMint?: string } => {
-  if (mint === 'SOL' || mint === SOL_MINT) {
+// Mints come back as addresses; `symbol` is the backend's registry symbol.
+const mapMintToToken = (
+  mint: string,
+  symbol?: string | null
+): { token: TokenType; tokenMint?: string } => {
+  if (mint === 'SOL' || mint === SOL_MINT || symbol === 'SOL') {
     return { token: 'SOL' };
   }
-  if (mint.toUpperCase() === 'USDC') {
+  if (mint.toUpperCase() === 'USDC' || symbol === 'USDC') {
     return { token: 'USDC' };
   }
   return { token: 'custom', tokenMint: mint };
@@ -43,17 +50,19 @@ const mapTokenToMint = (token: TokenType, tokenMint?: string): string => {
 
 const normalizeStatus = (status?: string): PayLinkStatus => {
   if (status === 'paid') return 'paid';
+  if (status === 'partially_paid') return 'partially_paid';
   if (status === 'expired') return 'expired';
   if (status === 'cancelled') return 'cancelled';
   return 'pending';
 };
 
 const mapPayLink = (p: any): PayLink => {
-  const { token, tokenMint } = mapMintToToken(p.mint || 'SOL');
+  const { token, tokenMint } = mapMintToToken(p.mint || 'SOL', p.token?.symbol);
   return {
     id: p.id,
     merchantPubkey: p.merchantPubkey,
     amount: p.expectedAmount ?? p.amount ?? 0,
+    amountReceived: p.amountReceived ?? 0,
     token,
     tokenMint,
     status: normalizeStatus(p.status),
@@ -63,13 +72,26 @@ const mapPayLink = (p: any): PayLink => {
     invoiceRef: p.invoiceRef ?? undefined,
     memoEnabled: true,
     receiptFields: defaultReceiptFields,
+    multiUse: p.multiUse ?? false,
+    openAmount: p.openAmount ?? false,
+    useCount: p.useCount ?? 0,
+    maxUses: p.maxUses ?? undefined,
+    maxTotal: p.maxTotal ?? undefined,
+    minAmount: p.minAmount ?? undefined,
+    maxAmount: p.maxAmount ?? undefined,
+    subscriptionId: p.subscriptionId ?? undefined,
+    hasInvoice: p.hasInvoice ?? false,
+    fiatCurrency: p.fiatCurrency ?? undefined,
+    fiatAmount: p.fiatAmount ?? undefined,
+    priceQuote: p.priceQuote ?? undefined,
+    tokenAmounts: p.token ?? undefined,
   };
 };
 
 const mapReceipt = (r: any): Receipt => {
   const facts = r.facts || {};
   const mint = facts.mint || 'SOL';
-  const { token } = mapMintToToken(mint);
+  const { token } = mapMintToToken(mint, r.token?.symbol);
 
   return {
     id: r.id,
@@ -92,6 +114,11 @@ const mapActivity = (e: any): ActivityEvent => {
         return 'webhook_received';
       case 'TX_VERIFIED_MATCH':
         return 'verified';
+      case 'PAYMENT_RECEIVED':
+      case 'PAYMENT_ACCEPTED':
+        return 'payment_received';
+      case 'PAYMENT_OVERPAID':
+        return 'overpaid';
       case 'RECEIPT_ISSUED':
         return 'receipt_issued';
       case 'PAYLINK_MARKED_PAID':
@@ -129,8 +156,11 @@ class ApiClient {
     options?: RequestInit
   ): Promise<ApiResponse<T>> {
     const url = `${API_BASE}${endpoint}`;
+    const { sessionToken, apiKey } = getMerchantSettings();
+    const token = sessionToken || apiKey;
     const headers: HeadersInit = {
       'Content-Type': 'application/json',
+      ...(token ? { Authorization: `Bearer ${token}` } : {}),
       ...(options?.headers || {}),
     };
 
@@ -196,6 +226,12 @@ class ApiClient {
     return { status: res.status, data: mapPayLink(res.data.paylink) };
   }
 
+  async getPublicPayLink(id: string): Promise<ApiResponse<PayLink>> {
+    const res = await this.request<any>(`/public/paylinks/${id}`);
+    if (res.error || !res.data) return res as ApiResponse<PayLink>;
+    return { status: res.status, data: mapPayLink(res.data.paylink) };
+  }
+
   async createPayLink(data: {
     amount: number;
     token: TokenType;
@@ -205,13 +241,23 @@ class ApiClient {
     privacyLevel?: 'standard' | 'enhanced' | 'maximum';
     memoEnabled: boolean;
     receiptFields: ReceiptFieldPolicy;
+    amountTolerance?: AmountTolerance;
+    multiUse?: boolean;
+    maxUses?: number;
+    maxTotal?: number;
+    openAmount?: boolean;
+    minAmount?: number;
+    maxAmount?: number;
+    invoice?: InvoiceInput;
+    fiat?: FiatPriceInput;
   }): Promise<ApiResponse<PayLink>> {
     const merchantPubkey = getMerchantPubkey();
     const mint = mapTokenToMint(data.token, data.tokenMint);
 
     const payload = {
       merchantPubkey,
-      expectedAmount: Math.round(data.amount),
+      expectedAmount:
+        data.openAmount || data.invoice || data.fiat ? undefined : Math.round(data.amount),
       mint,
       expiresAt: data.expiresAt,
       invoiceRef: data.invoiceRef,
@@ -227,7 +273,17 @@ class ApiClient {
         timeWindow: data.receiptFields.timeWindow,
         invoiceRef: data.receiptFields.invoiceRef,
         paylinkId: data.receiptFields.paylinkId,
+        invoice: data.receiptFields.invoice ?? false,
       },
+      amountTolerance: data.amountTolerance,
+      multiUse: data.multiUse,
+      maxUses: data.maxUses,
+      maxTotal: data.maxTotal,
+      openAmount: data.openAmount,
+      minAmount: data.minAmount,
+      maxAmount: data.maxAmount,
+      invoice: data.invoice,
+      fiat: data.fiat,
     };
 
     const res = await this.request<any>(`/paylinks`, {
@@ -239,11 +295,25 @@ class ApiClient {
     return { status: res.status, data: mapPayLink(res.data.paylink) };
   }
 
-  async cancelPayLink(id: string): Promise<ApiResponse<PayLink>> {
-    return {
-      status: 400,
-      error: 'Cancel is not supported by the backend API',
-    };
+  async cancelPayLink(id: string, reason?: string): Promise<ApiResponse<PayLink>> {
+    const res = await this.request<any>(`/paylinks/${id}/cancel`, {
+      method: 'POST',
+      body: JSON.stringify({ reason }),
+    });
+    if (res.error || !res.data) return res as ApiResponse<PayLink>;
+    return { status: res.status, data: mapPayLink(res.data.paylink) };
+  }
+
+  async updatePayLink(
+    id: string,
+    data: { expiresAt?: string; invoiceRef?: string | null }
+  ): Promise<ApiResponse<PayLink>> {
+    const res = await this.request<any>(`/paylinks/${id}`, {
+      method: 'PATCH',
+      body: JSON.stringify(data),
+    });
+    if (res.error || !res.data) return res as ApiResponse<PayLink>;
+    return { status: res.status, data: mapPayLink(res.data.paylink) };
   }
 
   async getPayLinkActivity(id: string): Promise<ApiResponse<ActivityEvent[]>> {
@@ -294,7 +364,7 @@ class ApiClient {
   }
 
   async getReceipt(id: string): Promise<ApiResponse<Receipt>> {
-    const res = await this.request<any>(`/receipts/${id}`);
+    const res = await this.request<any>(`/public/receipts/${id}`);
     if (res.error || !res.data) return res as ApiResponse<Receipt>;
     return { status: res.status, data: mapReceipt(res.data.receipt) };
   }
@@ -303,7 +373,7 @@ class ApiClient {
     id: string,
     disclosed: ReceiptFieldPolicy
   ): Promise<ApiResponse<{ proof: any }>> {
-    const res = await this.request<any>(`/receipts/${id}/proof`, {
+    const res = await this.request<any>(`/public/receipts/${id}/proof`, {
       method: 'POST',
       body: JSON.stringify({ disclosed }),
     });
@@ -344,6 +414,7 @@ class ApiClient {
         signature: res.data.details?.paidSignature,
         paylinkStatus: res.data.verified ? 'paid' : undefined,
         mismatches: res.data.verified ? [] : [res.data.reason],
+        simulated: res.data.details?.simulated ?? false,
       },
     };
   }
@@ -367,13 +438,55 @@ class ApiClient {
     };
   }
 
-  async simulatePayment(paylinkId: string): Promise<ApiResponse<{ signature: string }>> {
+  async simulatePayment(
+    paylinkId: string,
+    amount?: number
+  ): Promise<ApiResponse<{ signature: string }>> {
     const res = await this.request<any>(`/paylinks/${paylinkId}/simulate`, {
       method: 'POST',
+      body: amount !== undefined ? JSON.stringify({ amount: Math.round(amount) }) : undefined,
     });
     if (res.error || !res.data) return res as ApiResponse<{ signature: string }>;
     return { status: res.status, data: res.data };
   }
+
+  async setWebhook(url: string): Promise<ApiResponse<{ url: string | null; secret: string | null }>> {
+    if (!url) {
+      return this.request(`/merchant/webhook`, { method: 'DELETE' });
+    }
+    return this.request(`/merchant/webhook`, {
+      method: 'PUT',
+      body: JSON.stringify({ url }),
+    });
+  }
+
+  async getSignInNonce(
+    pubkey: string
+  ): Promise<ApiResponse<{ nonce: string; message: string; expiresAt: string }>> {
+    return this.request(`/auth/siws/nonce`, {
+      method: 'POST',
+      body: JSON.stringify({ pubkey }),
+    });
+  }
+
+  // The first sign-in for a wallet links it to the merchant behind the API
+  // key, so the key is sent even if an older session token is stored.
+  async verifySignIn(data: {
+    pubkey: string;
+    nonce: string;
+    signature: string;
+  }): Promise<ApiResponse<{ token: string; pubkey: string; merchantId: string; expiresAt: string; scopes: string[] }>> {
+    const apiKey = getMerchantSettings().apiKey;
+    return this.request(`/auth/siws/verify`, {
+      method: 'POST',
+      body: JSON.stringify(data),
+      headers: apiKey ? { Authorization: `Bearer ${apiKey}` } : {},
+    });
+  }
+
+  async signOut(): Promise<ApiResponse<{ revoked: boolean }>> {
+    return this.request(`/auth/logout`, { method: 'POST' });
+  }
 }
 
 export const api = new ApiClient();
@@ -1,11 +1,18 @@
-export type PayLinkStatus = 'pending' | 'paid' | 'expired' | 'cancelled';
+export type PayLinkStatus = 'pending' | 'partially_paid' | 'paid' | 'expired' | 'cancelled';
 
 export type TokenType = 'SOL' | 'USDC' | 'custom';
 
+// `value` is in base units for `absolute`, hundredths of a percent for `bps`.
+export interface AmountTolerance {
+  kind: 'absolute' | 'bps';
+  value: number;
+}
+
 export interface PayLink {
   id: string;
   merchantPubkey: string;
   amount: number;
+  amountReceived: number;
   token: TokenType;
   tokenMint?: string;
   status: PayLinkStatus;
@@ -16,6 +23,52 @@ export interface PayLink {
   invoiceRef?: string;
   memoEnabled: boolean;
   receiptFields: ReceiptFieldPolicy;
+  multiUse: boolean;
+  openAmount: boolean;
+  useCount: number;
+  maxUses?: number;
+  maxTotal?: number;
+  minAmount?: number;
+  maxAmount?: number;
+  subscriptionId?: string;
+  hasInvoice: boolean;
+  fiatCurrency?: FiatCurrency;
+  fiatAmount?: number;
+  priceQuote?: PriceQuote;
+  tokenAmounts?: TokenAmounts;
+}
+
+// Token details from the backend registry, with amounts in whole tokens.
+export interface TokenAmounts {
+  symbol?: string;
+  decimals: number;
+  tokenProgram: string;
+  expectedAmount: string;
+  amountReceived: string;
+  minAmount?: string;
+  maxAmount?: string;
+  maxTotal?: string;
+}
+
+export type FiatCurrency = 'USD' | 'EUR';
+
+// `rate` is the price of one whole token; `amount` is in base units.
+export interface PriceQuote {
+  source: string;
+  asset: string;
+  currency: FiatCurrency;
+  fiatAmount: number;
+  rate: string;
+  amount: number;
+  quotedAt: string;
+  expiresAt?: string;
+}
+
+export interface FiatPriceInput {
+  currency: FiatCurrency;
+  amount: string;
+  lock?: 'creation' | 'open';
+  quoteTtlSecs?: number;
 }
 
 export interface ReceiptFieldPolicy {
@@ -25,6 +78,16 @@ export interface ReceiptFieldPolicy {
   timeWindow: boolean;
   invoiceRef: boolean;
   paylinkId: boolean;
+  invoice?: boolean;
+}
+
+export interface InvoiceInput {
+  customer: { name: string; email?: string; address?: string; taxId?: string };
+  lineItems: { description: string; quantity: number; unitPrice: number }[];
+  discounts?: { description: string; kind: 'absolute' | 'bps'; value: number }[];
+  taxes?: { name: string; rateBps: number }[];
+  dueDate?: string;
+  notes?: string;
 }
 
 export interface Receipt {
@@ -68,7 +131,7 @@ export interface ReceiptProof {
 export interface ActivityEvent {
   id: string;
   paylinkId: string;
-  type: 'created' | 'webhook_received' | 'verified' | 'receipt_issued' | 'expired' | 'cancelled';
+  type: 'created' | 'webhook_received' | 'payment_received' | 'overpaid' | 'verified' | 'receipt_issued' | 'expired' | 'cancelled';
   timestamp: string;
   details?: string;
 }
@@ -90,6 +153,7 @@ export interface VerifyResponse {
   signature?: string;
   paylinkStatus?: PayLinkStatus;
   mismatches?: string[];
+  simulated?: boolean;
 }
 
 export interface ApiResponse<T> {
@@ -112,4 +176,6 @@ export interface MerchantSettings {
   accentColor: string;
   environment: 'devnet' | 'mainnet';
   webhookUrl?: string;
+  apiKey?: string;
+  sessionToken?: string;
 }
@@ -27,6 +27,7 @@ import {
 } from '@/components/ui/dialog';
 import { RadioGroup, RadioGroupItem } from '@/components/ui/radio-group';
 import { Label } from '@/components/ui/label';
+import { Input } from '@/components/ui/input';
 import { Identicon } from '@/components/ui/Identicon';
 import { StatusPill } from '@/components/ui/StatusPill';
 import { PrivacyBadge } from '@/components/ui/PrivacyBadge';
@@ -55,6 +56,7 @@ export default function Pay() {
   const [loadingFees, setLoadingFees] = useState(false);
   const [processing, setProcessing] = useState(false);
   const [paid, setPaid] = useState(false);
+  const [openAmount, setOpenAmount] = useState('');
 
   useEffect(() => {
     if (id) {
@@ -64,7 +66,7 @@ export default function Pay() {
 
   const fetchPayLink = async () => {
     setLoading(true);
-    const response = await api.getPayLink(id!);
+    const response = await api.getPublicPayLink(id!);
     
     if (response.error) {
       setError(response.error);
@@ -101,7 +103,10 @@ export default function Pay() {
     setStep('processing');
     setProcessing(true);
     
-    const response = await api.simulatePayment(id!);
+    const response = await api.simulatePayment(
+      id!,
+      paylink?.openAmount ? Number(openAmount) : undefined
+    );
     
     if (response.error) {
       toast.error('Payment failed');
@@ -190,7 +195,7 @@ export default function Pay() {
             {/* Amount */}
             <div className="text-center py-6 border-y border-border">
               <p className="text-4xl font-bold mb-2">
-                {paylink.amount}
+                {paylink.openAmount ? 'Any amount' : paylink.amount}
                 <span className="text-2xl text-muted-foreground ml-2">{paylink.token}</span>
               </p>
               {paylink.invoiceRef && (
@@ -278,10 +283,24 @@ export default function Pay() {
                   </DialogDescription>
                 </DialogHeader>
                 <div className="space-y-4 py-4">
-                  <div className="flex justify-between py-2 border-b border-border">
-                    <span className="text-muted-foreground">Amount</span>
-                    <span className="font-medium">{paylink.amount} {paylink.token}</span>
-                  </div>
+                  {paylink.openAmount ? (
+                    <div className="space-y-2 py-2 border-b border-border">
+                      <Label htmlFor="open-amount">Amount ({paylink.token})</Label>
+                      <Input
+                        id="open-amount"
+                        type="number"
+                        min={paylink.minAmount ?? 1}
+                        max={paylink.maxAmount}
+                        value={openAmount}
+                        onChange={(e) => setOpenAmount(e.target.value)}
+                      />
+                    </div>
+                  ) : (
+                    <div className="flex justify-between py-2 border-b border-border">
+                      <span className="text-muted-foreground">Amount</span>
+                      <span className="font-medium">{paylink.amount} {paylink.token}</span>
+                    </div>
+                  )}
                   <div className="flex justify-between py-2 border-b border-border">
                     <span className="text-muted-foreground">Recipient</span>
                     <span className="font-mono text-sm">{paylink.merchantPubkey.slice(0, 12)}...</span>
@@ -292,7 +311,11 @@ export default function Pay() {
                       <span>{paylink.invoiceRef}</span>
                     </div>
                   )}
-                  <Button className="w-full" onClick={handleConfirm}>
+                  <Button
+                    className="w-full"
+                    onClick={handleConfirm}
+                    disabled={paylink.openAmount && !(Number(openAmount) > 0)}
+                  >
                     Continue
                     <ArrowRight className="ml-2 h-4 w-4" />
                   </Button>
@@ -86,6 +86,8 @@ export default function PayLinkDetails() {
     switch (type) {
       case 'created': return <Circle className="h-4 w-4 text-primary" />;
       case 'webhook_received': return <Activity className="h-4 w-4 text-[#fe5f00]" />;
+      case 'payment_received': return <Activity className="h-4 w-4 text-success" />;
+      case 'overpaid': return <AlertCircle className="h-4 w-4 text-warning" />;
       case 'verified': return <CheckCircle className="h-4 w-4 text-success" />;
       case 'receipt_issued': return <Receipt className="h-4 w-4 text-primary" />;
       case 'expired': return <AlertCircle className="h-4 w-4 text-destructive" />;
@@ -149,6 +151,11 @@ export default function PayLinkDetails() {
               <h2 className="text-2xl font-bold">{paylink.amount} {paylink.token}</h2>
               <StatusPill status={paylink.status} />
             </div>
+            {paylink.status === 'partially_paid' && (
+              <p className="text-sm text-muted-foreground">
+                {paylink.amountReceived} of {paylink.amount} {paylink.token} received
+              </p>
+            )}
             <div className="flex items-center gap-2 text-sm text-muted-foreground">
               <Clock className="h-3 w-3" />
               <span className={expiryStatus?.color}>{expiryStatus?.text}</span>
@@ -18,6 +18,8 @@ import {
   SelectValue,
 } from '@/components/ui/select';
 import { Identicon } from '@/components/ui/Identicon';
+import { CopyButton } from '@/components/ui/CopyButton';
+import { api } from '@/lib/api';
 import { getMerchantSettings, setMerchantSettings } from '@/lib/merchant';
 import type { MerchantSettings } from '@/lib/types';
 import { toast } from 'sonner';
@@ -34,14 +36,26 @@ const accentColors = [
 export default function Settings() {
   const [settings, setSettings] = useState<MerchantSettings>(getMerchantSettings());
   const [saving, setSaving] = useState(false);
+  const [webhookSecret, setWebhookSecret] = useState<string | null>(null);
 
-  const handleSave = () => {
+  const handleSave = async () => {
     setSaving(true);
+    const previousWebhookUrl = getMerchantSettings().webhookUrl || '';
+    const webhookUrl = settings.webhookUrl?.trim() || '';
+    if (webhookUrl !== previousWebhookUrl) {
+      const res = await api.setWebhook(webhookUrl);
+      if (res.error) {
+        setSaving(false);
+        toast.error(`Webhook not saved: ${res.error}`);
+        return;
+      }
+      setWebhookSecret(res.data?.secret ?? null);
+    }
+    // Only stored once the backend accepted the webhook, so a rejected URL
+    // is not shown as saved on the next visit.
     setMerchantSettings(settings);
-    setTimeout(() => {
-      setSaving(false);
-      toast.success('Settings saved');
-    }, 500);
+    setSaving(false);
+    toast.success('Settings saved');
   };
 
   return (
@@ -89,6 +103,21 @@ export default function Settings() {
               Shown to payers on the checkout page
             </p>
           </div>
+
+          <div className="space-y-2">
+            <Label htmlFor="apiKey">API Key</Label>
+            <Input
+              id="apiKey"
+              type="password"
+              placeholder="rl_..."
+              value={settings.apiKey ?? ''}
+              onChange={(e) => setSettings({ ...settings, apiKey: e.target.value })}
+              className="font-mono text-sm"
+            />
+            <p className="text-xs text-muted-foreground">
+              Sent with every dashboard request; issued by your operator
+            </p>
+          </div>
         </CardContent>
       </Card>
 
@@ -181,11 +210,17 @@ export default function Settings() {
               placeholder="https://your-server.com/webhook"
               value={settings.webhookUrl || ''}
               onChange={(e) => setSettings({ ...settings, webhookUrl: e.target.value })}
-              disabled
             />
             <p className="text-xs text-muted-foreground">
-              Coming soon. Helius webhooks will be configured automatically.
+              Receives signed PAYLINK_CREATED, PAYLINK_MARKED_PAID, RECEIPT_ISSUED,
+              TX_VERIFIED_MISMATCH, PAYLINK_EXPIRED and PAYLINK_CANCELLED events
             </p>
+            {webhookSecret && (
+              <div className="flex items-center gap-2">
+                <p className="font-mono text-xs flex-1 truncate">{webhookSecret}</p>
+                <CopyButton value={webhookSecret} size="sm" />
+              </div>
+            )}
           </div>
         </CardContent>
       </Card>